@@ -0,0 +1,32 @@
+//! Split every focused window along its longer side, like the autotiling script.
+
+extern crate i3ipc;
+
+use i3ipc::autotiling;
+use i3ipc::event::inner::WindowChange;
+use i3ipc::event::Event;
+use i3ipc::I3Connection;
+use i3ipc::I3EventListener;
+use i3ipc::Subscription;
+
+fn main() {
+    let mut connection = I3Connection::connect().expect("failed to connect");
+    let mut listener = I3EventListener::connect().expect("failed to connect");
+    listener
+        .subscribe(&[Subscription::Window])
+        .expect("failed to subscribe");
+    for event in listener.listen() {
+        match event {
+            Ok(Event::WindowEvent(w)) if w.change == WindowChange::Focus => {
+                let tree = connection.get_tree().expect("failed to get the tree");
+                if let Some(split) = autotiling::recommended_split(&tree) {
+                    connection
+                        .run_command(split.as_str())
+                        .expect("failed to split");
+                }
+            }
+            Ok(_) => {}
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Asynchronous versions of `I3Connection` and `I3EventListener` built on async-std.
+//!
+//! async-std's `UnixStream` already implements the `futures` io traits, so this module only
+//! knows how to connect it; the framing lives in the `transport` module.
+
+use async_std::os::unix::net::UnixStream;
+
+use crate::transport::{AsyncI3Connection, AsyncI3EventListener};
+use crate::EstablishError;
+
+async fn connect_stream() -> Result<UnixStream, EstablishError> {
+    match crate::find_socket_path(cfg!(feature = "sway")) {
+        Ok((path, source)) => match UnixStream::connect(&path).await {
+            Ok(stream) => Ok(stream),
+            Err(error) => Err(crate::socket_error(path, source, error)),
+        },
+        Err(error) => Err(EstablishError::GetSocketPathError(error)),
+    }
+}
+
+/// Abstraction over an async ipc socket to i3. Handles events.
+pub type I3EventListener = AsyncI3EventListener<UnixStream>;
+
+impl AsyncI3EventListener<UnixStream> {
+    /// Establishes the IPC connection.
+    ///
+    /// Note that finding the socket path may fall back to running `i3 --get-socketpath`, which
+    /// briefly blocks the calling task.
+    pub async fn connect() -> Result<I3EventListener, EstablishError> {
+        Ok(AsyncI3EventListener::new(connect_stream().await?))
+    }
+}
+
+/// Abstraction over an async ipc socket to i3. Handles messages/replies.
+pub type I3Connection = AsyncI3Connection<UnixStream>;
+
+impl AsyncI3Connection<UnixStream> {
+    /// Establishes the IPC connection.
+    ///
+    /// Note that finding the socket path may fall back to running `i3 --get-socketpath`, which
+    /// briefly blocks the calling task.
+    pub async fn connect() -> Result<I3Connection, EstablishError> {
+        Ok(AsyncI3Connection::new(connect_stream().await?))
+    }
+}
@@ -0,0 +1,64 @@
+//! Choosing the split direction from the shape of the focused window.
+//!
+//! The classic autotiling policy splits a window along its longer side, so that windows wider
+//! than tall get their next neighbour to the right and the others get it below. Running the
+//! command from `recommended_split` after every focus change keeps the layout near a spiral:
+//!
+//! ```no_run
+//! use i3ipc::event::inner::WindowChange;
+//! use i3ipc::event::Event;
+//! use i3ipc::{autotiling, I3Connection, I3EventListener, Subscription};
+//!
+//! let mut connection = I3Connection::connect().unwrap();
+//! let mut listener = I3EventListener::connect().unwrap();
+//! listener.subscribe(&[Subscription::Window]).unwrap();
+//! for event in listener.listen() {
+//!     if let Event::WindowEvent(info) = event.unwrap() {
+//!         if info.change == WindowChange::Focus {
+//!             let tree = connection.get_tree().unwrap();
+//!             if let Some(split) = autotiling::recommended_split(&tree) {
+//!                 connection.run_command(split.as_str()).unwrap();
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::command::Command;
+use crate::reply::{Node, NodeLayout, Rect};
+use crate::tree::TreeIndex;
+
+/// The split for a window covering `rect`: vertical if it's taller than wide, horizontal
+/// otherwise.
+pub fn split_for(rect: &Rect) -> Command {
+    if rect.height > rect.width {
+        Command::split_vertical()
+    } else {
+        Command::split_horizontal()
+    }
+}
+
+/// The split command for the focused container of `tree`, or `None` if there's nothing to
+/// change: the focused container is floating or fullscreen, sits in a tabbed or stacked
+/// container, or its parent already splits in the recommended direction.
+pub fn recommended_split(tree: &Node) -> Option<Command> {
+    let focused = tree.find_focused()?;
+    if focused.is_fullscreen() {
+        return None;
+    }
+    let index = TreeIndex::new(tree);
+    if index.floating_con_of(focused.id).is_some() {
+        return None;
+    }
+    let parent = index.parent_of(focused.id)?;
+    let wanted = if focused.rect.height > focused.rect.width {
+        NodeLayout::SplitV
+    } else {
+        NodeLayout::SplitH
+    };
+    match parent.layout {
+        NodeLayout::Stacked | NodeLayout::Tabbed => None,
+        ref layout if *layout == wanted => None,
+        _ => Some(split_for(&focused.rect)),
+    }
+}
@@ -0,0 +1,110 @@
+//! Finding out at runtime what the running window manager supports.
+//!
+//! Every message and event this crate knows about is always compiled in, whichever i3 the
+//! user's machine runs. `I3Connection::connect` asks for the version, and requests that version
+//! doesn't understand fail with `MessageError::Unsupported` instead of waiting for a reply old
+//! i3 versions never send. Anything else that old versions lack can be checked up front:
+//!
+//! ```no_run
+//! use i3ipc::event::inner::WindowChange;
+//! use i3ipc::event::Event;
+//! use i3ipc::{I3Connection, I3EventListener, Subscription};
+//!
+//! let mut connection = I3Connection::connect().unwrap();
+//! if connection.capabilities().unwrap().window_mark_event {
+//!     let mut listener = I3EventListener::connect().unwrap();
+//!     listener.subscribe(&[Subscription::Window]).unwrap();
+//!     for event in listener.listen() {
+//!         if let Event::WindowEvent(info) = event.unwrap() {
+//!             if info.change == WindowChange::Mark {
+//!                 println!("marks: {:?}", connection.get_marks().unwrap().marks);
+//!             }
+//!         }
+//!     }
+//! } else {
+//!     // i3 older than 4.13 doesn't send window events for marks, so poll them instead
+//!     println!("marks: {:?}", connection.get_marks().unwrap().marks);
+//! }
+//! ```
+
+use crate::reply::Version;
+
+/// The messages, events and fields supported by a particular i3 or sway version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether this is sway rather than i3.
+    pub sway: bool,
+    /// The major version, such as 4 for i3 or 1 for sway.
+    pub major: i32,
+    /// The minor version.
+    pub minor: i32,
+    /// The patch version.
+    pub patch: i32,
+    /// Whether window events are sent with `WindowChange::Mark` (i3 4.13).
+    pub window_mark_event: bool,
+    /// Whether `get_binding_modes` is understood (i3 4.13).
+    pub binding_modes: bool,
+    /// Whether `get_config` is understood (i3 4.14).
+    pub config: bool,
+    /// Whether shutdown events are sent (i3 4.14).
+    pub shutdown_event: bool,
+    /// Whether `send_tick` and tick events are supported (i3 4.15).
+    pub tick: bool,
+    /// Whether `sync` is understood (i3 4.16). sway accepts it but always fails it.
+    pub sync: bool,
+    /// Whether `get_binding_state` is understood (i3 4.19, sway 1.5).
+    pub binding_state: bool,
+    /// Whether `get_config` fills in `included_configs` (i3 4.20).
+    pub included_configs: bool,
+    /// Whether `get_inputs`, `get_seats` and input events are supported (sway only).
+    pub inputs: bool,
+}
+
+impl Capabilities {
+    /// Works out the capabilities from the reply to `get_version`.
+    pub fn from_version(version: &Version) -> Capabilities {
+        let sway = version.variant.as_deref() == Some("sway");
+        let at_least = |major: i32, minor: i32| (version.major, version.minor) >= (major, minor);
+        let i3 = |major: i32, minor: i32| !sway && at_least(major, minor);
+        Capabilities {
+            sway,
+            major: version.major,
+            minor: version.minor,
+            patch: version.patch,
+            window_mark_event: sway || i3(4, 13),
+            binding_modes: sway || i3(4, 13),
+            config: sway || i3(4, 14),
+            shutdown_event: sway || i3(4, 14),
+            tick: sway || i3(4, 15),
+            sync: i3(4, 16),
+            binding_state: (sway && at_least(1, 5)) || i3(4, 19),
+            included_configs: i3(4, 20),
+            inputs: sway,
+        }
+    }
+
+    /// Whether messages of the given type are understood. Types this doesn't know about are
+    /// assumed to be.
+    pub fn supports_message(&self, message_type: u32) -> bool {
+        match message_type {
+            8 => self.binding_modes,
+            9 => self.config,
+            10 => self.tick,
+            11 => self.sync,
+            12 => self.binding_state,
+            100 | 101 => self.inputs,
+            _ => true,
+        }
+    }
+
+    /// Whether the version is at least `major.minor.patch`.
+    pub fn at_least(&self, major: i32, minor: i32, patch: i32) -> bool {
+        (self.major, self.minor, self.patch) >= (major, minor, patch)
+    }
+}
+
+impl<'a> From<&'a Version> for Capabilities {
+    fn from(version: &'a Version) -> Capabilities {
+        Capabilities::from_version(version)
+    }
+}
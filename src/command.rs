@@ -0,0 +1,294 @@
+//! A typed builder for i3 commands.
+//!
+//! Instead of concatenating command strings by hand, start from one of the constructors on
+//! `Command` and pass the result to `I3Connection::run_command`:
+//!
+//! ```no_run
+//! use i3ipc::command::Command;
+//! use i3ipc::I3Connection;
+//!
+//! let mut connection = I3Connection::connect().unwrap();
+//! let command = Command::move_container().to_workspace_number(5);
+//! connection.run_command(command.as_str()).unwrap();
+//! ```
+//!
+//! Arguments that can contain arbitrary text (workspace names, output names, marks, programs to
+//! exec) are quoted for you.
+
+use std::fmt;
+
+/// Quotes `s` as an i3 string argument, escaping backslashes and double quotes.
+pub(crate) fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A single i3 command, ready to be sent with `I3Connection::run_command`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    text: String,
+}
+
+impl Command {
+    /// Wraps an already formatted command string. No quoting is applied.
+    pub fn raw<S: Into<String>>(text: S) -> Command {
+        Command { text: text.into() }
+    }
+
+    /// Starts a command that moves the focus.
+    pub fn focus() -> Focus {
+        Focus(())
+    }
+
+    /// Starts a command that moves the focused container.
+    pub fn move_container() -> Move {
+        Move(())
+    }
+
+    /// Runs the given program. It is passed to `sh -c` as is, so it can be a whole shell command
+    /// line; quotes, semicolons and commas in it are escaped from i3's own command parser.
+    pub fn exec(program: &str) -> Command {
+        Command::raw(format!("exec {}", quote(program)))
+    }
+
+    /// Runs the given program without startup notification support.
+    pub fn exec_no_startup_id(program: &str) -> Command {
+        Command::raw(format!("exec --no-startup-id {}", quote(program)))
+    }
+
+    /// Closes the focused container.
+    pub fn kill() -> Command {
+        Command::raw("kill")
+    }
+
+    /// Marks the focused container with the given identifier.
+    pub fn mark(mark: &str) -> Command {
+        Command::raw(format!("mark {}", quote(mark)))
+    }
+
+    /// Adds the given mark to the focused container, keeping its other marks.
+    pub fn mark_add(mark: &str) -> Command {
+        Command::raw(format!("mark --add {}", quote(mark)))
+    }
+
+    /// Adds the given mark to the focused container, or removes it if the container already
+    /// has it.
+    pub fn mark_toggle(mark: &str) -> Command {
+        Command::raw(format!("mark --add --toggle {}", quote(mark)))
+    }
+
+    /// Removes the given mark from all containers.
+    pub fn unmark(mark: &str) -> Command {
+        Command::raw(format!("unmark {}", quote(mark)))
+    }
+
+    /// Renames the workspace called `old` to `new`.
+    pub fn rename_workspace(old: &str, new: &str) -> Command {
+        Command::raw(format!("rename workspace {} to {}", quote(old), quote(new)))
+    }
+
+    /// Moves the focused workspace to the given output.
+    pub fn move_workspace_to_output(output: &str) -> Command {
+        Command::raw(format!("move workspace to output {}", quote(output)))
+    }
+
+    /// Shows the scratchpad window, or cycles through them if there are several. With
+    /// criteria, shows the matching scratchpad window instead.
+    pub fn scratchpad_show() -> Command {
+        Command::raw("scratchpad show")
+    }
+
+    /// Resizes the focused floating container to the given width and height in pixels.
+    pub fn resize_set(width: i32, height: i32) -> Command {
+        Command::raw(format!("resize set {} px {} px", width, height))
+    }
+
+    /// Makes the next window opened next to the focused container appear to the right of it.
+    pub fn split_horizontal() -> Command {
+        Command::raw("split h")
+    }
+
+    /// Makes the next window opened next to the focused container appear below it.
+    pub fn split_vertical() -> Command {
+        Command::raw("split v")
+    }
+
+    /// Toggles fullscreen mode of the focused container.
+    pub fn fullscreen_toggle() -> Command {
+        Command::raw("fullscreen toggle")
+    }
+
+    /// Swaps the focused container with the container with the given i3 container ID.
+    pub fn swap_with_con_id(id: i64) -> Command {
+        Command::raw(format!("swap container with con_id {}", id))
+    }
+
+    /// Toggles whether the focused container is floating.
+    pub fn floating_toggle() -> Command {
+        Command::raw("floating toggle")
+    }
+
+    /// Switches to the given binding mode.
+    pub fn mode(mode: &str) -> Command {
+        Command::raw(format!("mode {}", quote(mode)))
+    }
+
+    /// Does nothing, but shows up in i3's log with the given comment.
+    pub fn nop(comment: &str) -> Command {
+        Command::raw(format!("nop {}", quote(comment)))
+    }
+
+    /// The command in i3's syntax.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl AsRef<str> for Command {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl From<&str> for Command {
+    fn from(text: &str) -> Command {
+        Command::raw(text)
+    }
+}
+
+impl From<String> for Command {
+    fn from(text: String) -> Command {
+        Command::raw(text)
+    }
+}
+
+/// Builder for focus commands, created by `Command::focus`.
+#[derive(Debug)]
+pub struct Focus(());
+
+impl Focus {
+    /// Switches to the workspace with the given name.
+    pub fn workspace(self, name: &str) -> Command {
+        Command::raw(format!("workspace {}", quote(name)))
+    }
+
+    /// Switches to the workspace with the given number, whatever its name.
+    pub fn workspace_number(self, number: i32) -> Command {
+        Command::raw(format!("workspace number {}", number))
+    }
+
+    /// Focuses the given output.
+    pub fn output(self, name: &str) -> Command {
+        Command::raw(format!("focus output {}", quote(name)))
+    }
+
+    /// Focuses the container to the left.
+    pub fn left(self) -> Command {
+        Command::raw("focus left")
+    }
+
+    /// Focuses the container to the right.
+    pub fn right(self) -> Command {
+        Command::raw("focus right")
+    }
+
+    /// Focuses the container above.
+    pub fn up(self) -> Command {
+        Command::raw("focus up")
+    }
+
+    /// Focuses the container below.
+    pub fn down(self) -> Command {
+        Command::raw("focus down")
+    }
+
+    /// Focuses the parent container.
+    pub fn parent(self) -> Command {
+        Command::raw("focus parent")
+    }
+
+    /// Focuses the child container.
+    pub fn child(self) -> Command {
+        Command::raw("focus child")
+    }
+
+    /// Toggles focus between floating and tiling containers.
+    pub fn mode_toggle(self) -> Command {
+        Command::raw("focus mode_toggle")
+    }
+}
+
+/// Builder for move commands, created by `Command::move_container`.
+#[derive(Debug)]
+pub struct Move(());
+
+impl Move {
+    /// Moves the container to the workspace with the given name.
+    pub fn to_workspace(self, name: &str) -> Command {
+        Command::raw(format!("move container to workspace {}", quote(name)))
+    }
+
+    /// Moves the container to the workspace with the given number, whatever its name.
+    pub fn to_workspace_number(self, number: i32) -> Command {
+        Command::raw(format!("move container to workspace number {}", number))
+    }
+
+    /// Moves the container to the given output.
+    pub fn to_output(self, name: &str) -> Command {
+        Command::raw(format!("move container to output {}", quote(name)))
+    }
+
+    /// Moves the container next to the container with the given mark.
+    pub fn to_mark(self, mark: &str) -> Command {
+        Command::raw(format!("move container to mark {}", quote(mark)))
+    }
+
+    /// Moves the container to the scratchpad.
+    pub fn to_scratchpad(self) -> Command {
+        Command::raw("move scratchpad")
+    }
+
+    /// Moves the floating container so its top left corner is at (x, y) on the screen.
+    pub fn to_position(self, x: i32, y: i32) -> Command {
+        Command::raw(format!("move position {} px {} px", x, y))
+    }
+
+    /// Moves the floating container to the center of its workspace.
+    pub fn to_center(self) -> Command {
+        Command::raw("move position center")
+    }
+
+    /// Moves the container to the left.
+    pub fn left(self) -> Command {
+        Command::raw("move left")
+    }
+
+    /// Moves the container to the right.
+    pub fn right(self) -> Command {
+        Command::raw("move right")
+    }
+
+    /// Moves the container up.
+    pub fn up(self) -> Command {
+        Command::raw("move up")
+    }
+
+    /// Moves the container down.
+    pub fn down(self) -> Command {
+        Command::raw("move down")
+    }
+}
@@ -1,218 +1,386 @@
 //! Some common code used by both the event and reply modules.
-use reply;
+use crate::event;
+use crate::{MessageError, Subscription};
+use serde::de::{Deserialize, DeserializeOwned};
 use serde_json as json;
-use std::collections::HashMap;
-
-/// Recursively build the tree of containers from the given json value.
-pub fn build_tree(val: &json::Value) -> reply::Node {
-    reply::Node {
-        focus: match val.get("focus") {
-            Some(xs) => xs
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|x| x.as_i64().unwrap())
-                .collect(),
-            None => vec![],
-        },
-        nodes: match val.get("nodes") {
-            Some(nds) => nds
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|n| build_tree(n))
-                .collect(),
-            None => vec![],
-        },
-        floating_nodes: match val.get("floating_nodes") {
-            Some(nds) => nds
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|n| build_tree(n))
-                .collect(),
-            None => vec![],
-        },
-        id: val.get("id").unwrap().as_i64().unwrap(),
-        name: match val.get("name") {
-            Some(n) => match n.as_str() {
-                Some(s) => Some(s.to_owned()),
-                None => None,
-            },
-            None => None,
-        },
-        nodetype: match val.get("type").unwrap().as_str().unwrap() {
-            "root" => reply::NodeType::Root,
-            "output" => reply::NodeType::Output,
-            "con" => reply::NodeType::Con,
-            "floating_con" => reply::NodeType::FloatingCon,
-            "workspace" => reply::NodeType::Workspace,
-            "dockarea" => reply::NodeType::DockArea,
-            other => {
-                warn!(target: "i3ipc", "Unknown NodeType {}", other);
-                reply::NodeType::Unknown
+use std::error::Error;
+
+/// Implements `Deserialize` and `Serialize` for an enum that i3 sends as a plain string. Strings
+/// that aren't listed are logged as a warning and kept in the enum's `Unknown` variant, so newer
+/// versions of i3 don't break parsing and callers can still branch on them. The enum also gets
+/// an `as_str` method returning the string i3 uses.
+macro_rules! serde_enum_str {
+    ($name:ident { $( $(#[$attr:meta])* $value:literal => $variant:ident, )* }) => {
+        impl $name {
+            /// The string i3 uses for this value.
+            pub fn as_str(&self) -> &str {
+                match *self {
+                    $( $(#[$attr])* $name::$variant => $value, )*
+                    $name::Unknown(ref other) => other,
+                }
             }
-        },
-        border: match val.get("border").unwrap().as_str().unwrap() {
-            "normal" => reply::NodeBorder::Normal,
-            "none" => reply::NodeBorder::None,
-            "pixel" => reply::NodeBorder::Pixel,
-            other => {
-                warn!(target: "i3ipc", "Unknown NodeBorder {}", other);
-                reply::NodeBorder::Unknown
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                let value = <String as ::serde::Deserialize>::deserialize(deserializer)?;
+                Ok(match value.as_str() {
+                    $( $(#[$attr])* $value => $name::$variant, )*
+                    other => {
+                        crate::telemetry::unknown_value(stringify!($name), other);
+                        $name::Unknown(other.to_owned())
+                    }
+                })
             }
-        },
-        current_border_width: val.get("current_border_width").unwrap().as_i64().unwrap() as i32,
-        layout: match val.get("layout").unwrap().as_str().unwrap() {
-            "splith" => reply::NodeLayout::SplitH,
-            "splitv" => reply::NodeLayout::SplitV,
-            "stacked" => reply::NodeLayout::Stacked,
-            "tabbed" => reply::NodeLayout::Tabbed,
-            "dockarea" => reply::NodeLayout::DockArea,
-            "output" => reply::NodeLayout::Output,
-            other => {
-                warn!(target: "i3ipc", "Unknown NodeLayout {}", other);
-                reply::NodeLayout::Unknown
+        }
+
+        impl ::serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: ::serde::Serializer,
+            {
+                serializer.serialize_str(self.as_str())
             }
-        },
-        percent: match *val.get("percent").unwrap() {
-            json::Value::Number(ref f) => Some(f.as_f64().unwrap()),
-            json::Value::Null => None,
-            _ => unreachable!(),
-        },
-        rect: build_rect(val.get("rect").unwrap()),
-        window_rect: build_rect(val.get("window_rect").unwrap()),
-        deco_rect: build_rect(val.get("deco_rect").unwrap()),
-        geometry: build_rect(val.get("geometry").unwrap()),
-        window: match val.get("window").unwrap().clone() {
-            json::Value::Number(i) => Some(i.as_i64().unwrap() as i32),
-            json::Value::Null => None,
-            _ => unreachable!(),
-        },
-        window_properties: build_window_properties(val.get("window_properties")),
-        urgent: val.get("urgent").unwrap().as_bool().unwrap(),
-        focused: val.get("focused").unwrap().as_bool().unwrap(),
+        }
+    };
+}
+
+/// Builds the payload of a `subscribe` message: a JSON array of event names.
+pub fn build_subscribe_payload(events: &[Subscription]) -> String {
+    "[ ".to_owned()
+        + &events
+            .iter()
+            .map(|s| match *s {
+                Subscription::Workspace => "\"workspace\"",
+                Subscription::Output => "\"output\"",
+                Subscription::Mode => "\"mode\"",
+                Subscription::Window => "\"window\"",
+                Subscription::BarConfig => "\"barconfig_update\"",
+                Subscription::Binding => "\"binding\"",
+                Subscription::Shutdown => "\"shutdown\"",
+                Subscription::Tick => "\"tick\"",
+                #[cfg(feature = "sway")]
+                Subscription::Input => "\"input\"",
+                #[cfg(feature = "sway")]
+                Subscription::BarStateUpdate => "\"bar_state_update\"",
+            })
+            .collect::<Vec<_>>()
+            .join(", ")[..]
+        + " ]"
+}
+
+/// How much of a payload that failed to parse is kept in the error.
+const SNIPPET_LEN: usize = 256;
+
+/// The start of `payload`, cut at a character boundary.
+fn snippet(payload: &str) -> String {
+    if payload.len() <= SNIPPET_LEN {
+        return payload.to_owned();
     }
+    let mut end = SNIPPET_LEN;
+    while !payload.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &payload[..end])
 }
 
-pub fn build_window_properties(
-    j: Option<&json::Value>,
-) -> Option<HashMap<reply::WindowProperty, String>> {
-    match j {
-        None => None,
-        Some(props) => {
-            let properties = props.as_object().unwrap();
-            let mut map = HashMap::new();
-            for (key, val) in properties {
-                let window_property = match key.as_ref() {
-                    "class" => Some(reply::WindowProperty::Class),
-                    "instance" => Some(reply::WindowProperty::Instance),
-                    "window_role" => Some(reply::WindowProperty::WindowRole),
-                    "title" => Some(reply::WindowProperty::Title),
-                    "transient_for" => Some(reply::WindowProperty::TransientFor),
-                    other => {
-                        warn!(target: "i3ipc", "Unknown WindowProperty {}", other);
-                        return None;
-                    }
-                };
-                if let Some(window_property) = window_property {
-                    map.insert(
-                        window_property,
-                        val.as_str().unwrap_or_default().to_string(),
-                    );
+/// How deeply containers may be nested in a payload by default. Every container sits in an
+/// array in its parent, so a tree this deep is nested about 128 levels deep in JSON, as deep as
+/// the JSON parser goes unless the limit is raised.
+pub const MAX_TREE_DEPTH: usize = 64;
+
+/// How many levels of JSON a payload may be nested beyond two per allowed level of the tree,
+/// for the objects and arrays in the fields of a container, once the limit is raised above
+/// `MAX_TREE_DEPTH`.
+const NESTING_SLACK: usize = 16;
+
+/// How deeply containers are nested in `payload`, and how deeply its JSON is nested, worked out
+/// by counting brackets rather than by recursing. Only the objects in `nodes` and
+/// `floating_nodes` arrays make a level of the tree.
+fn nesting(payload: &[u8]) -> (usize, usize) {
+    // for each open bracket, whether it's a `nodes` or `floating_nodes` array.
+    let mut open = Vec::new();
+    let (mut node_lists, mut tree_depth, mut json_depth) = (0_usize, 0, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    let (mut string_start, mut last_string) = (0, 0..0);
+    let mut after_node_key = false;
+    for (i, &byte) in payload.iter().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => {
+                    in_string = false;
+                    last_string = string_start..i;
                 }
+                _ => {}
             }
-            Some(map)
+            continue;
         }
+        match byte {
+            b' ' | b'\t' | b'\n' | b'\r' => continue,
+            b'"' => {
+                in_string = true;
+                string_start = i + 1;
+            }
+            b':' => {
+                let key = &payload[last_string.clone()];
+                after_node_key = key == b"nodes" || key == b"floating_nodes";
+                continue;
+            }
+            b'{' => {
+                open.push(false);
+                json_depth = json_depth.max(open.len());
+                tree_depth = tree_depth.max(node_lists + 1);
+            }
+            b'[' => {
+                open.push(after_node_key);
+                json_depth = json_depth.max(open.len());
+                if after_node_key {
+                    node_lists += 1;
+                }
+            }
+            b'}' | b']' => {
+                let closes_node_list = open.pop() == Some(true);
+                node_lists -= usize::from(closes_node_list);
+            }
+            _ => {}
+        }
+        after_node_key = false;
     }
+    (tree_depth, json_depth)
 }
 
-pub fn build_rect(jrect: &json::Value) -> (i32, i32, i32, i32) {
-    let x = jrect.get("x").unwrap().as_i64().unwrap() as i32;
-    let y = jrect.get("y").unwrap().as_i64().unwrap() as i32;
-    let width = jrect.get("width").unwrap().as_i64().unwrap() as i32;
-    let height = jrect.get("height").unwrap().as_i64().unwrap() as i32;
-    (x, y, width, height)
+/// How deeply serde_json nests JSON unless its limit is lifted.
+const JSON_RECURSION_LIMIT: usize = 128;
+
+/// How deeply the JSON parser may recurse into a payload.
+#[derive(Clone, Copy, PartialEq)]
+enum Recursion {
+    /// The payload is nested no deeper than serde_json goes by default.
+    Limited,
+    /// The payload is nested deeper than serde_json goes by default, so it is refused.
+    Refused,
+    /// The payload is nested deeper than serde_json goes by default, but no deeper than a
+    /// raised tree depth limit allows, so serde_json's limit is lifted.
+    Unbounded,
 }
 
-pub fn build_bar_config(j: &json::Value) -> reply::BarConfig {
-    reply::BarConfig {
-        id: j.get("id").unwrap().as_str().unwrap().to_owned(),
-        mode: j.get("mode").unwrap().as_str().unwrap().to_owned(),
-        position: j.get("position").unwrap().as_str().unwrap().to_owned(),
-        status_command: j
-            .get("status_command")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_owned(),
-        font: j.get("font").unwrap().as_str().unwrap().to_owned(),
-        workspace_buttons: j.get("workspace_buttons").unwrap().as_bool().unwrap(),
-        binding_mode_indicator: j.get("binding_mode_indicator").unwrap().as_bool().unwrap(),
-        verbose: j.get("verbose").unwrap().as_bool().unwrap(),
-        colors: {
-            let colors = j.get("colors").unwrap().as_object().unwrap();
-            let mut map = HashMap::new();
-            for c in colors.keys() {
-                let enum_key = match c.as_ref() {
-                    "background" => reply::ColorableBarPart::Background,
-                    "statusline" => reply::ColorableBarPart::Statusline,
-                    "separator" => reply::ColorableBarPart::Separator,
-
-                    #[cfg(feature = "i3-4-12")]
-                    "focused_background" => reply::ColorableBarPart::FocusedBackground,
-
-                    #[cfg(feature = "i3-4-12")]
-                    "focused_statusline" => reply::ColorableBarPart::FocusedStatusline,
-
-                    #[cfg(feature = "i3-4-12")]
-                    "focused_separator" => reply::ColorableBarPart::FocusedSeparator,
-
-                    "focused_workspace_text" => reply::ColorableBarPart::FocusedWorkspaceText,
-                    "focused_workspace_bg" => reply::ColorableBarPart::FocusedWorkspaceBg,
-                    "focused_workspace_border" => reply::ColorableBarPart::FocusedWorkspaceBorder,
-                    "active_workspace_text" => reply::ColorableBarPart::ActiveWorkspaceText,
-                    "active_workspace_bg" => reply::ColorableBarPart::ActiveWorkspaceBg,
-                    "active_workspace_border" => reply::ColorableBarPart::ActiveWorkspaceBorder,
-                    "inactive_workspace_text" => reply::ColorableBarPart::InactiveWorkspaceText,
-                    "inactive_workspace_bg" => reply::ColorableBarPart::InactiveWorkspaceBg,
-                    "inactive_workspace_border" => reply::ColorableBarPart::InactiveWorkspaceBorder,
-                    "urgent_workspace_text" => reply::ColorableBarPart::UrgentWorkspaceText,
-                    "urgent_workspace_bg" => reply::ColorableBarPart::UrgentWorkspaceBg,
-                    "urgent_workspace_border" => reply::ColorableBarPart::UrgentWorkspaceBorder,
-                    "binding_mode_text" => reply::ColorableBarPart::BindingModeText,
-                    "binding_mode_bg" => reply::ColorableBarPart::BindingModeBg,
-                    "binding_mode_border" => reply::ColorableBarPart::BindingModeBorder,
-                    other => {
-                        warn!(target: "i3ipc", "Unknown ColorableBarPart {}", other);
-                        reply::ColorableBarPart::Unknown
-                    }
-                };
-                let hex = colors.get(c).unwrap().as_str().unwrap().to_owned();
-                map.insert(enum_key, hex);
-            }
-            map
-        },
+/// Refuses a payload with containers nested more than `max_depth` deep, before parsing it
+/// could exhaust the stack. JSON nested deeper than serde_json goes by default is only parsed
+/// when `max_depth` is above the default and the JSON isn't nested much deeper than the
+/// containers.
+fn check_depth(
+    message_type: u32,
+    payload: &[u8],
+    max_depth: usize,
+) -> Result<Recursion, MessageError> {
+    let (depth, json_depth) = nesting(payload);
+    if depth > max_depth {
+        return Err(MessageError::TreeTooDeep {
+            message_type,
+            depth,
+            limit: max_depth,
+        });
     }
+    Ok(if json_depth <= JSON_RECURSION_LIMIT {
+        Recursion::Limited
+    } else if max_depth > MAX_TREE_DEPTH && json_depth <= 2 * max_depth + NESTING_SLACK {
+        Recursion::Unbounded
+    } else {
+        Recursion::Refused
+    })
 }
 
-#[cfg(feature = "sway-1-1")]
-pub fn build_modes(j: &json::Value) -> Vec<reply::Mode> {
-    let mut res: Vec<reply::Mode>= Vec::new();
-    for mode in j.as_array().unwrap() {
-        res.push(build_mode(mode))
+/// Parses a payload that came with a message of the given type (with the event bit set for
+/// events). A payload that isn't valid JSON is reported as `JsonCouldntParse`, while valid
+/// JSON that doesn't have the expected shape (a missing or renamed field, a field of the wrong
+/// type) is reported as `InvalidField` along with the path of the offending field, and one with
+/// containers nested more than `max_depth` deep as `TreeTooDeep`.
+pub fn parse<T: DeserializeOwned>(
+    message_type: u32,
+    payload: &str,
+    max_depth: usize,
+) -> Result<T, MessageError> {
+    let recursion = check_depth(message_type, payload.as_bytes(), max_depth)?;
+    deserialize(message_type, payload, recursion)
+}
+
+fn deserialize<T: DeserializeOwned>(
+    message_type: u32,
+    payload: &str,
+    recursion: Recursion,
+) -> Result<T, MessageError> {
+    // simd-json parses in place, so it needs a copy of the payload. When it fails, the payload
+    // is parsed again below, as only serde_json can say which field was wrong.
+    #[cfg(feature = "simd-json")]
+    if recursion != Recursion::Refused {
+        let mut bytes = payload.as_bytes().to_vec();
+        if let Ok(value) = simd_json::serde::from_slice(&mut bytes) {
+            return Ok(value);
+        }
     }
-    res
+    deserialize_slice(message_type, payload.as_bytes(), recursion)
 }
 
-#[cfg(feature = "sway-1-1")]
-pub fn build_mode(jmode: &json::Value) -> reply::Mode {
-    let width = jmode.get("width").unwrap().as_i64().unwrap() as i32;
-    let height = jmode.get("height").unwrap().as_i64().unwrap() as i32;
-    let refresh = jmode.get("refresh").unwrap().as_i64().unwrap() as i32;
-    reply::Mode {
-        width: width,
-        height: height,
-        refresh: refresh
+/// Parses with serde_json, which can borrow strings from `payload`.
+fn deserialize_slice<'de, T: Deserialize<'de>>(
+    message_type: u32,
+    payload: &'de [u8],
+    recursion: Recursion,
+) -> Result<T, MessageError> {
+    let snippet = || snippet(&String::from_utf8_lossy(payload));
+    let couldnt_parse = |error| MessageError::JsonCouldntParse {
+        message_type,
+        payload: snippet(),
+        error,
+    };
+    let mut deserializer = json::Deserializer::from_slice(payload);
+    if recursion == Recursion::Unbounded {
+        deserializer.disable_recursion_limit();
     }
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let error = e.into_inner();
+        if !error.is_data() {
+            return couldnt_parse(error);
+        }
+        // serde reports a missing field at the path of the struct that lacks it.
+        let message = error.to_string();
+        let field = match message.strip_prefix("missing field `") {
+            Some(rest) => {
+                let name = rest.split('`').next().unwrap_or_default();
+                if path == "." {
+                    name.to_owned()
+                } else {
+                    format!("{}.{}", path, name)
+                }
+            }
+            None => path,
+        };
+        MessageError::InvalidField {
+            message_type,
+            payload: snippet(),
+            field,
+            error,
+        }
+    })?;
+    deserializer.end().map_err(couldnt_parse)?;
+    Ok(value)
+}
+
+/// Makes the i3 event. The msgtype passed in should have its highest order bit stripped.
+pub fn build_event(msgtype: u32, payload: &str) -> Result<event::Event, MessageError> {
+    build_event_limited(msgtype, payload, MAX_TREE_DEPTH)
+}
+
+/// Like `build_event`, but refuses payloads with containers nested more than `max_depth` deep.
+pub fn build_event_limited(
+    msgtype: u32,
+    payload: &str,
+    max_depth: usize,
+) -> Result<event::Event, MessageError> {
+    check_depth(crate::EVENT_BIT | msgtype, payload.as_bytes(), max_depth)
+        .and_then(|recursion| build_event_inner(msgtype, payload, recursion))
+        .inspect_err(crate::telemetry::parse_failed)
+}
+
+/// Like `build_event_limited`, but in lossy mode a payload that can't be parsed is logged and
+/// made into `Event::Malformed` instead of an error.
+pub fn build_event_lossy(
+    msgtype: u32,
+    payload: &str,
+    max_depth: usize,
+    lossy: bool,
+) -> Result<event::Event, MessageError> {
+    match build_event_limited(msgtype, payload, max_depth) {
+        Err(ref e) if lossy => {
+            let error = match e.source() {
+                Some(source) => format!("{}: {}", e, source),
+                None => e.to_string(),
+            };
+            crate::telemetry::malformed_event(&error);
+            Ok(event::Event::Malformed {
+                subscription: Subscription::from_event_type(msgtype),
+                payload: payload.to_owned(),
+                error,
+            })
+        }
+        result => result,
+    }
+}
+
+fn build_event_inner(
+    msgtype: u32,
+    payload: &str,
+    recursion: Recursion,
+) -> Result<event::Event, MessageError> {
+    let message_type = crate::EVENT_BIT | msgtype;
+    Ok(match msgtype {
+        0 => event::Event::WorkspaceEvent(deserialize(message_type, payload, recursion)?),
+        1 => event::Event::OutputEvent(deserialize(message_type, payload, recursion)?),
+        2 => event::Event::ModeEvent(deserialize(message_type, payload, recursion)?),
+        3 => event::Event::WindowEvent(deserialize(message_type, payload, recursion)?),
+        4 => event::Event::BarConfigEvent(deserialize(message_type, payload, recursion)?),
+        5 => event::Event::BindingEvent(deserialize(message_type, payload, recursion)?),
+
+        6 => event::Event::ShutdownEvent(deserialize(message_type, payload, recursion)?),
+
+        7 => event::Event::TickEvent(deserialize(message_type, payload, recursion)?),
+
+        #[cfg(feature = "sway")]
+        20 => event::Event::BarStateUpdateEvent(deserialize(message_type, payload, recursion)?),
+
+        #[cfg(feature = "sway")]
+        21 => event::Event::InputEvent(deserialize(message_type, payload, recursion)?),
+
+        _ => return Err(MessageError::UnknownEvent(msgtype)),
+    })
+}
+
+/// Makes the borrowed form of an i3 event. The msgtype passed in should have its highest order
+/// bit stripped.
+pub fn build_event_ref(
+    msgtype: u32,
+    payload: &[u8],
+    max_depth: usize,
+) -> Result<event::EventRef<'_>, MessageError> {
+    check_depth(crate::EVENT_BIT | msgtype, payload, max_depth)
+        .and_then(|recursion| build_event_ref_inner(msgtype, payload, recursion))
+        .inspect_err(crate::telemetry::parse_failed)
+}
+
+fn build_event_ref_inner(
+    msgtype: u32,
+    payload: &[u8],
+    recursion: Recursion,
+) -> Result<event::EventRef<'_>, MessageError> {
+    let message_type = crate::EVENT_BIT | msgtype;
+    Ok(match msgtype {
+        0 => event::EventRef::WorkspaceEvent(deserialize_slice(message_type, payload, recursion)?),
+        1 => event::EventRef::OutputEvent(deserialize_slice(message_type, payload, recursion)?),
+        2 => event::EventRef::ModeEvent(deserialize_slice(message_type, payload, recursion)?),
+        3 => event::EventRef::WindowEvent(deserialize_slice(message_type, payload, recursion)?),
+        4 => event::EventRef::BarConfigEvent(deserialize_slice(message_type, payload, recursion)?),
+        5 => event::EventRef::BindingEvent(deserialize_slice(message_type, payload, recursion)?),
+
+        6 => event::EventRef::ShutdownEvent(deserialize_slice(message_type, payload, recursion)?),
+
+        7 => event::EventRef::TickEvent(deserialize_slice(message_type, payload, recursion)?),
+
+        #[cfg(feature = "sway")]
+        20 => event::EventRef::BarStateUpdateEvent(deserialize_slice(
+            message_type,
+            payload,
+            recursion,
+        )?),
+
+        #[cfg(feature = "sway")]
+        21 => event::EventRef::InputEvent(deserialize_slice(message_type, payload, recursion)?),
+
+        _ => return Err(MessageError::UnknownEvent(msgtype)),
+    })
 }
@@ -0,0 +1,324 @@
+//! A builder for the `[...]` criteria that select which containers a command applies to.
+//!
+//! ```
+//! use i3ipc::command::Command;
+//! use i3ipc::criteria::Criteria;
+//!
+//! let command = Criteria::new().class("Firefox").title("Private").apply(Command::kill());
+//! assert_eq!(command.as_str(), r#"[class="Firefox" title="Private"] kill"#);
+//!
+//! // plain string commands work too
+//! let command = Criteria::new().con_mark("scratch").apply("scratchpad show");
+//! assert_eq!(command.as_str(), r#"[con_mark="scratch"] scratchpad show"#);
+//! ```
+//!
+//! As in the i3 config, the string values of `class`, `instance`, `title` and friends are
+//! regular expressions; they are only quoted, not regex-escaped.
+//!
+//! A `Matcher` checks containers against the criteria without asking i3, for example to pick
+//! out window events with `EventIterator::filter_matching` or the new window to wait for with
+//! `I3EventListener::wait_for_window`:
+//!
+//! ```
+//! use i3ipc::criteria::Criteria;
+//! # let tree: i3ipc::reply::Node = serde_json::from_str(r#"{"id": 1, "type": "root",
+//! #     "border": "none", "current_border_width": 0, "layout": "splith", "percent": null,
+//! #     "rect": {"x": 0, "y": 0, "width": 0, "height": 0}, "window_rect": {"x": 0, "y": 0,
+//! #     "width": 0, "height": 0}, "deco_rect": {"x": 0, "y": 0, "width": 0, "height": 0},
+//! #     "geometry": {"x": 0, "y": 0, "width": 0, "height": 0}, "window": null, "name": null,
+//! #     "urgent": false, "focused": false}"#).unwrap();
+//!
+//! let terminals = Criteria::new().class("(?i)^(urxvt|alacritty)$").workspace("^dev").matcher();
+//! for window in terminals.find_all(&tree) {
+//!     println!("{:?}", window.name);
+//! }
+//! ```
+
+use std::fmt;
+
+use crate::command::{quote, Command};
+use crate::pattern::Pattern;
+use crate::reply::{Node, NodeType};
+use crate::tree::is_window;
+
+/// Selects containers by their properties. An empty `Criteria` matches nothing in particular
+/// and is rendered as an empty string.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Criteria {
+    parts: Vec<(&'static str, Option<String>)>,
+}
+
+impl Criteria {
+    /// Creates criteria without any conditions.
+    pub fn new() -> Criteria {
+        Criteria::default()
+    }
+
+    fn with(mut self, key: &'static str, value: &str) -> Criteria {
+        self.parts.push((key, Some(value.to_owned())));
+        self
+    }
+
+    fn flag(mut self, key: &'static str) -> Criteria {
+        self.parts.push((key, None));
+        self
+    }
+
+    /// Matches the window class (the second part of WM_CLASS).
+    pub fn class(self, class: &str) -> Criteria {
+        self.with("class", class)
+    }
+
+    /// Matches the window instance (the first part of WM_CLASS).
+    pub fn instance(self, instance: &str) -> Criteria {
+        self.with("instance", instance)
+    }
+
+    /// Matches the window role (WM_WINDOW_ROLE).
+    pub fn window_role(self, role: &str) -> Criteria {
+        self.with("window_role", role)
+    }
+
+    /// Matches the window type (`normal`, `dialog`, `utility`, ...).
+    pub fn window_type(self, window_type: &str) -> Criteria {
+        self.with("window_type", window_type)
+    }
+
+    /// Matches the window title.
+    pub fn title(self, title: &str) -> Criteria {
+        self.with("title", title)
+    }
+
+    /// Matches the X11 window id.
+    pub fn id(self, id: u32) -> Criteria {
+        self.with("id", &id.to_string())
+    }
+
+    /// Matches the i3 container id, as found in `reply::Node::id`.
+    pub fn con_id(self, id: i64) -> Criteria {
+        self.with("con_id", &id.to_string())
+    }
+
+    /// Matches containers carrying the given mark.
+    pub fn con_mark(self, mark: &str) -> Criteria {
+        self.with("con_mark", mark)
+    }
+
+    /// Matches windows on the workspace with the given name.
+    pub fn workspace(self, name: &str) -> Criteria {
+        self.with("workspace", name)
+    }
+
+    /// Matches urgent windows; `latest` picks the most recently urgent one if several are.
+    pub fn urgent(self, latest: bool) -> Criteria {
+        self.with("urgent", if latest { "latest" } else { "oldest" })
+    }
+
+    /// Matches floating windows.
+    pub fn floating(self) -> Criteria {
+        self.flag("floating")
+    }
+
+    /// Matches tiling windows.
+    pub fn tiling(self) -> Criteria {
+        self.flag("tiling")
+    }
+
+    /// Whether no conditions were added.
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Whether `node` meets all conditions, checked locally the way i3 would. See `Matcher`,
+    /// which this compiles the criteria to, for what is supported; conditions on the workspace
+    /// never match here.
+    pub fn matches(&self, node: &Node) -> bool {
+        self.matcher().matches(node)
+    }
+
+    /// Compiles the criteria for checking many containers against them.
+    pub fn matcher(&self) -> Matcher {
+        Matcher::new(self)
+    }
+
+    /// Prefixes the given command with these criteria, so it only applies to the matching
+    /// containers.
+    pub fn apply<C: Into<Command>>(&self, command: C) -> Command {
+        let command = command.into();
+        if self.is_empty() {
+            command
+        } else {
+            Command::raw(format!("{} {}", self, command))
+        }
+    }
+}
+
+impl fmt::Display for Criteria {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+        f.write_str("[")?;
+        for (i, (key, value)) in self.parts.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            match value {
+                Some(value) => write!(f, "{}={}", key, quote(value))?,
+                None => f.write_str(key)?,
+            }
+        }
+        f.write_str("]")
+    }
+}
+
+/// Criteria compiled for checking containers locally, following i3's rules.
+///
+/// Regular expressions are limited to the common part of PCRE: literal text, `.`, `^`, `$`,
+/// classes like `[a-z]`, `\d`, `\w`, `\s`, groups with `|`, the usual quantifiers and a
+/// leading `(?i)`; a condition using anything else matches nothing, as do special values like
+/// `__focused__`. `urgent` matches any urgent container, regardless of `latest`. Empty criteria
+/// match every container.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Class(Option<Pattern>),
+    Instance(Option<Pattern>),
+    WindowRole(Option<Pattern>),
+    Title(Option<Pattern>),
+    Mark(Option<Pattern>),
+    Workspace(Option<Pattern>),
+    WindowType(String),
+    Window(String),
+    ConId(String),
+    Urgent,
+    Floating(bool),
+    Unsupported,
+}
+
+impl Matcher {
+    /// Compiles `criteria`.
+    pub fn new(criteria: &Criteria) -> Matcher {
+        let conditions = criteria
+            .parts
+            .iter()
+            .map(|(key, value)| {
+                let value = value.as_deref().unwrap_or("");
+                let pattern = || Pattern::new(value);
+                match *key {
+                    "class" => Condition::Class(pattern()),
+                    "instance" => Condition::Instance(pattern()),
+                    "window_role" => Condition::WindowRole(pattern()),
+                    "title" => Condition::Title(pattern()),
+                    "con_mark" => Condition::Mark(pattern()),
+                    "workspace" => Condition::Workspace(pattern()),
+                    "window_type" => Condition::WindowType(value.to_owned()),
+                    "id" => Condition::Window(value.to_owned()),
+                    "con_id" => Condition::ConId(value.to_owned()),
+                    "urgent" => Condition::Urgent,
+                    "floating" => Condition::Floating(true),
+                    "tiling" => Condition::Floating(false),
+                    _ => Condition::Unsupported,
+                }
+            })
+            .collect();
+        Matcher { conditions }
+    }
+
+    /// Whether `node` meets all conditions. Conditions on the workspace never match, as a
+    /// container doesn't say which workspace it is on; use `matches_on` or `find_all` for them.
+    pub fn matches(&self, node: &Node) -> bool {
+        self.check(node, None)
+    }
+
+    /// Whether `node`, which is on the workspace named `workspace`, meets all conditions.
+    pub fn matches_on(&self, node: &Node, workspace: &str) -> bool {
+        self.check(node, Some(workspace))
+    }
+
+    /// The windows in `tree` that meet all conditions, in tree order. Like i3, criteria with a
+    /// `con_id` or `con_mark` condition can match other containers too.
+    pub fn find_all<'a>(&self, tree: &'a Node) -> Vec<&'a Node> {
+        let any_container = self
+            .conditions
+            .iter()
+            .any(|c| matches!(c, Condition::ConId(_) | Condition::Mark(_)));
+        let mut found = Vec::new();
+        self.find_below(tree, None, any_container, &mut found);
+        found
+    }
+
+    fn find_below<'a>(
+        &self,
+        node: &'a Node,
+        workspace: Option<&'a str>,
+        any_container: bool,
+        found: &mut Vec<&'a Node>,
+    ) {
+        for child in node.nodes.iter().chain(node.floating_nodes.iter()) {
+            let workspace = match child.nodetype {
+                NodeType::Workspace => Some(child.name.as_deref().unwrap_or("")),
+                _ => {
+                    if let Some(workspace) = workspace {
+                        if (any_container || is_window(child)) && self.matches_on(child, workspace)
+                        {
+                            found.push(child);
+                        }
+                    }
+                    workspace
+                }
+            };
+            self.find_below(child, workspace, any_container, found);
+        }
+    }
+
+    fn check(&self, node: &Node, workspace: Option<&str>) -> bool {
+        let properties = node.window_properties.as_ref();
+        let floating = node
+            .floating
+            .as_ref()
+            .map_or(node.nodetype == NodeType::FloatingCon, |f| f.is_floating());
+        let regex = |pattern: &Option<Pattern>, text: Option<&str>| match (pattern, text) {
+            (Some(pattern), Some(text)) => pattern.is_match(text),
+            _ => false,
+        };
+        self.conditions.iter().all(|condition| match condition {
+            Condition::Class(p) => regex(p, properties.and_then(|p| p.class.as_deref())),
+            Condition::Instance(p) => regex(p, properties.and_then(|p| p.instance.as_deref())),
+            Condition::WindowRole(p) => regex(p, properties.and_then(|p| p.window_role.as_deref())),
+            Condition::Title(p) => regex(
+                p,
+                properties
+                    .and_then(|p| p.title.as_deref())
+                    .or(node.name.as_deref()),
+            ),
+            Condition::Mark(p) => node.marks.iter().any(|mark| regex(p, Some(mark))),
+            Condition::Workspace(p) => regex(p, workspace),
+            Condition::WindowType(value) => node
+                .window_type
+                .as_ref()
+                .is_some_and(|t| t.as_str() == value),
+            Condition::Window(value) => node.window.is_some_and(|w| w.to_string() == *value),
+            Condition::ConId(value) => node.id.to_string() == *value,
+            Condition::Urgent => node.urgent,
+            Condition::Floating(wanted) => floating == *wanted,
+            Condition::Unsupported => false,
+        })
+    }
+}
+
+impl From<&Criteria> for Matcher {
+    fn from(criteria: &Criteria) -> Matcher {
+        Matcher::new(criteria)
+    }
+}
+
+impl From<Criteria> for Matcher {
+    fn from(criteria: Criteria) -> Matcher {
+        Matcher::new(&criteria)
+    }
+}
@@ -0,0 +1,181 @@
+//! Merging bursts of events before they reach the program.
+//!
+//! Some events come in bursts where only the last one matters: a terminal printing its
+//! working directory into the title on every prompt, or a layout change moving a dozen
+//! windows at once. `I3EventListener::debounce` holds events back until the listener has been
+//! quiet for a while and drops the ones a later event of the burst replaces:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use i3ipc::{I3EventListener, Subscription};
+//!
+//! let mut listener = I3EventListener::connect().unwrap();
+//! listener.subscribe(&[Subscription::Window]).unwrap();
+//! for event in listener.debounce(Duration::from_millis(100)) {
+//!     println!("{:?}", event.unwrap());
+//! }
+//! ```
+//!
+//! `I3EventListener::coalesce_title_changes` only merges title changes that have already
+//! arrived together, without holding anything back to wait for more.
+
+use std::collections::VecDeque;
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use crate::event::inner::{WindowChange, WorkspaceChange};
+use crate::event::Event;
+use crate::{I3EventListener, MessageError};
+
+/// How long `coalesce_title_changes` waits for the title changes that arrived together with the
+/// first one.
+const ARRIVED_TOGETHER: Duration = Duration::from_millis(1);
+
+/// How many times `quiet` a burst is held back at most by default, so that steady traffic
+/// still gets through.
+const MAX_WAIT_QUIETS: u32 = 10;
+
+/// Iterates over events, merging bursts. Created by `I3EventListener::debounce` and
+/// `I3EventListener::coalesce_title_changes`.
+#[derive(Debug)]
+pub struct Debounced<'a> {
+    listener: &'a mut I3EventListener<UnixStream>,
+    quiet: Duration,
+    /// How long a burst is held back at most, however busy the listener stays.
+    max_wait: Duration,
+    /// Whether to wait for a burst after this event, or to hand it out right away.
+    starts_burst: fn(&Event) -> bool,
+    /// Whether the first event makes the second, earlier one obsolete.
+    replaces: fn(&Event, &Event) -> bool,
+    ready: VecDeque<Result<Event, MessageError>>,
+}
+
+impl<'a> Debounced<'a> {
+    pub(crate) fn debounce(
+        listener: &'a mut I3EventListener<UnixStream>,
+        quiet: Duration,
+    ) -> Debounced<'a> {
+        Debounced {
+            listener,
+            quiet,
+            max_wait: quiet * MAX_WAIT_QUIETS,
+            starts_burst: |_| true,
+            replaces,
+            ready: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn coalesce_title_changes(
+        listener: &'a mut I3EventListener<UnixStream>,
+    ) -> Debounced<'a> {
+        Debounced {
+            listener,
+            quiet: ARRIVED_TOGETHER,
+            max_wait: ARRIVED_TOGETHER * MAX_WAIT_QUIETS,
+            starts_burst: is_title_change,
+            replaces: |later, earlier| {
+                is_title_change(later) && is_title_change(earlier) && replaces(later, earlier)
+            },
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Sets how long a burst is held back at most: once it has gone on for `max_wait`, it's
+    /// handed out even if events keep arriving. Defaults to ten times the quiet time.
+    pub fn max_wait(mut self, max_wait: Duration) -> Debounced<'a> {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Reads events until none arrives for `quiet` or `max_wait` has passed, merging them into
+    /// `burst`.
+    fn read_burst(&mut self, burst: &mut Vec<Event>) -> Result<(), MessageError> {
+        let previous = self
+            .listener
+            .stream
+            .socket
+            .read_timeout()
+            .map_err(crate::receive_failed)?;
+        let deadline = Instant::now() + self.max_wait;
+        let result = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                break Ok(());
+            }
+            if let Err(e) = self
+                .listener
+                .stream
+                .socket
+                .set_read_timeout(Some(remaining.min(self.quiet)))
+            {
+                break Err(crate::receive_failed(e));
+            }
+            match self.listener.try_next() {
+                Ok(Some(event)) => {
+                    burst.retain(|earlier| !(self.replaces)(&event, earlier));
+                    burst.push(event);
+                }
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.listener
+            .stream
+            .socket
+            .set_read_timeout(previous)
+            .map_err(crate::receive_failed)?;
+        result
+    }
+}
+
+impl<'a> Iterator for Debounced<'a> {
+    type Item = Result<Event, MessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(ready) = self.ready.pop_front() {
+            return Some(ready);
+        }
+        let first = match self.listener.listen().next()? {
+            Ok(event) => event,
+            Err(e) => return Some(Err(e)),
+        };
+        if !(self.starts_burst)(&first) {
+            return Some(Ok(first));
+        }
+        let mut burst = vec![first];
+        let result = self.read_burst(&mut burst);
+        self.ready.extend(burst.into_iter().map(Ok));
+        if let Err(e) = result {
+            self.ready.push_back(Err(e));
+        }
+        self.ready.pop_front()
+    }
+}
+
+fn is_title_change(event: &Event) -> bool {
+    matches!(event, Event::WindowEvent(info) if info.change == WindowChange::Title)
+}
+
+/// Whether `later` says everything `earlier` did about the current state: a change of the same
+/// kind to the same container, workspace or output, or a newer focus or binding mode.
+fn replaces(later: &Event, earlier: &Event) -> bool {
+    match (later, earlier) {
+        (Event::WindowEvent(later), Event::WindowEvent(earlier)) => {
+            later.change == earlier.change
+                && (later.change == WindowChange::Focus
+                    || later.container.id == earlier.container.id)
+        }
+        (Event::WorkspaceEvent(later), Event::WorkspaceEvent(earlier)) => {
+            let id = |node: &Option<crate::reply::Node>| node.as_ref().map(|node| node.id);
+            later.change == earlier.change
+                && (later.change == WorkspaceChange::Focus
+                    || id(&later.current) == id(&earlier.current))
+        }
+        (Event::OutputEvent(later), Event::OutputEvent(earlier)) => later.change == earlier.change,
+        (Event::ModeEvent(_), Event::ModeEvent(_)) => true,
+        (Event::BarConfigEvent(later), Event::BarConfigEvent(earlier)) => {
+            later.bar_config.id == earlier.bar_config.id
+        }
+        _ => false,
+    }
+}
@@ -1,14 +1,20 @@
 //! Abstractions for the events passed back from i3.
 
-use common;
-use reply;
+use crate::common;
+use crate::reply;
+use crate::{MessageError, Subscription};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json as json;
+use std::borrow::Cow;
+use std::io;
 use std::str::FromStr;
 
-use event::inner::*;
+use self::inner::*;
 
 /// An event passed back from i3.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
 pub enum Event {
     WorkspaceEvent(WorkspaceEventInfo),
     OutputEvent(OutputEventInfo),
@@ -17,13 +23,33 @@ pub enum Event {
     BarConfigEvent(BarConfigEventInfo),
     BindingEvent(BindingEventInfo),
 
-    #[cfg(feature = "i3-4-14")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
     ShutdownEvent(ShutdownEventInfo),
+
+    TickEvent(TickEventInfo),
+
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    InputEvent(InputEventInfo),
+
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    BarStateUpdateEvent(BarStateUpdateEventInfo),
+
+    /// An event that couldn't be parsed, only delivered by listeners in lossy mode (see
+    /// `I3EventListener::set_lossy`).
+    Malformed {
+        /// The subscription the event belongs to, or `None` for an event type this library
+        /// doesn't know.
+        subscription: Option<Subscription>,
+        /// The payload as i3 sent it.
+        payload: String,
+        /// Why it couldn't be parsed.
+        error: String,
+    },
 }
 
 /// Data for `WorkspaceEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct WorkspaceEventInfo {
     /// The type of change.
     pub change: WorkspaceChange,
@@ -31,69 +57,53 @@ pub struct WorkspaceEventInfo {
     pub current: Option<reply::Node>,
     /// Will be `Some` only when `change == Focus` *and* there was a previous workspace.
     /// Note that if the previous workspace was empty it will get destroyed when switching, but
-    /// will still appear here.
+    /// will still appear here. Neither i3 nor sway send the workspace as it was before a
+    /// `Move`; `tracker::WorkspaceTracker::last_move` remembers where it came from.
     pub old: Option<reply::Node>,
 }
 
+impl WorkspaceEventInfo {
+    /// The output `old` was on, if the event has an `old` workspace and says where it was.
+    pub fn old_output(&self) -> Option<&str> {
+        self.old.as_ref().and_then(|old| old.output.as_deref())
+    }
+
+    /// The output `current` is on, which for `Move` is the one the workspace moved to, if the
+    /// event says.
+    pub fn new_output(&self) -> Option<&str> {
+        self.current
+            .as_ref()
+            .and_then(|current| current.output.as_deref())
+    }
+}
+
 impl FromStr for WorkspaceEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let val: json::Value = json::from_str(s)?;
-        Ok(WorkspaceEventInfo {
-            change: match val.get("change").unwrap().as_str().unwrap() {
-                "focus" => WorkspaceChange::Focus,
-                "init" => WorkspaceChange::Init,
-                "empty" => WorkspaceChange::Empty,
-                "urgent" => WorkspaceChange::Urgent,
-                "rename" => WorkspaceChange::Rename,
-                "reload" => WorkspaceChange::Reload,
-                "move" => WorkspaceChange::Move,
-                "restored" => WorkspaceChange::Restored,
-                other => {
-                    warn!(target: "i3ipc", "Unknown WorkspaceChange {}", other);
-                    WorkspaceChange::Unknown
-                }
-            },
-            current: match val.get("current").unwrap().clone() {
-                json::Value::Null => None,
-                val => Some(common::build_tree(&val)),
-            },
-            old: match val.get("old") {
-                Some(o) => match o.clone() {
-                    json::Value::Null => None,
-                    val => Some(common::build_tree(&val)),
-                },
-                None => None,
-            },
-        })
+        json::from_str(s)
     }
 }
 
 /// Data for `OutputEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct OutputEventInfo {
     /// The type of change.
     pub change: OutputChange,
+    /// The output that changed, for window managers that send it along, as newer sway
+    /// versions do. i3 only sends `change`.
+    #[serde(default)]
+    pub output: Option<reply::Output>,
 }
 
 impl FromStr for OutputEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let val: json::Value = json::from_str(s)?;
-        Ok(OutputEventInfo {
-            change: match val.get("change").unwrap().as_str().unwrap() {
-                "unspecified" => OutputChange::Unspecified,
-                other => {
-                    warn!(target: "i3ipc", "Unknown OutputChange {}", other);
-                    OutputChange::Unknown
-                }
-            },
-        })
+        json::from_str(s)
     }
 }
 
 /// Data for `ModeEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ModeEventInfo {
     /// The name of current mode in use. It is the same as specified in config when creating a
     /// mode. The default mode is simply named default.
@@ -103,15 +113,12 @@ pub struct ModeEventInfo {
 impl FromStr for ModeEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let val: json::Value = json::from_str(s)?;
-        Ok(ModeEventInfo {
-            change: val.get("change").unwrap().as_str().unwrap().to_owned(),
-        })
+        json::from_str(s)
     }
 }
 
 /// Data for `WindowEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct WindowEventInfo {
     /// Indicates the type of change
     pub change: WindowChange,
@@ -124,33 +131,13 @@ pub struct WindowEventInfo {
 impl FromStr for WindowEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let val: json::Value = json::from_str(s)?;
-        Ok(WindowEventInfo {
-            change: match val.get("change").unwrap().as_str().unwrap() {
-                "new" => WindowChange::New,
-                "close" => WindowChange::Close,
-                "focus" => WindowChange::Focus,
-                "title" => WindowChange::Title,
-                "fullscreen_mode" => WindowChange::FullscreenMode,
-                "move" => WindowChange::Move,
-                "floating" => WindowChange::Floating,
-                "urgent" => WindowChange::Urgent,
-
-                #[cfg(feature = "i3-4-13")]
-                "mark" => WindowChange::Mark,
-
-                other => {
-                    warn!(target: "i3ipc", "Unknown WindowChange {}", other);
-                    WindowChange::Unknown
-                }
-            },
-            container: common::build_tree(val.get("container").unwrap()),
-        })
+        json::from_str(s)
     }
 }
 
 /// Data for `BarConfigEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct BarConfigEventInfo {
     /// The new i3 bar configuration.
     pub bar_config: reply::BarConfig,
@@ -159,17 +146,14 @@ pub struct BarConfigEventInfo {
 impl FromStr for BarConfigEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let val: json::Value = json::from_str(s)?;
-        Ok(BarConfigEventInfo {
-            bar_config: common::build_bar_config(&val),
-        })
+        json::from_str(s)
     }
 }
 
 /// Data for `BindingEvent`.
 ///
 /// Reports on the details of a binding that ran a command because of user input.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct BindingEventInfo {
     /// Indicates what sort of binding event was triggered (right now it will always be "run" but
     /// that may be expanded in the future).
@@ -180,75 +164,432 @@ pub struct BindingEventInfo {
 impl FromStr for BindingEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let val: json::Value = json::from_str(s)?;
-        let bind = val.get("binding").unwrap();
-        Ok(BindingEventInfo {
-            change: match val.get("change").unwrap().as_str().unwrap() {
-                "run" => BindingChange::Run,
-                other => {
-                    warn!(target: "i3ipc", "Unknown BindingChange {}", other);
-                    BindingChange::Unknown
-                }
-            },
-            binding: Binding {
-                command: bind.get("command").unwrap().as_str().unwrap().to_owned(),
-                event_state_mask: bind
-                    .get("event_state_mask")
-                    .unwrap()
-                    .as_array()
-                    .unwrap()
-                    .iter()
-                    .map(|m| m.as_str().unwrap().to_owned())
-                    .collect(),
-                input_code: bind.get("input_code").unwrap().as_i64().unwrap() as i32,
-                symbol: match bind.get("symbol").unwrap().clone() {
-                    json::Value::String(s) => Some(s),
-                    json::Value::Null => None,
-                    _ => unreachable!(),
-                },
-                input_type: match bind.get("input_type").unwrap().as_str().unwrap() {
-                    "keyboard" => InputType::Keyboard,
-                    "mouse" => InputType::Mouse,
-                    other => {
-                        warn!(target: "i3ipc", "Unknown InputType {}", other);
-                        InputType::Unknown
-                    }
-                },
-            },
-        })
+        json::from_str(s)
     }
 }
 
 /// Data for `ShutdownEvent`.
-#[derive(Debug)]
-#[cfg(feature = "i3-4-14")]
-#[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ShutdownEventInfo {
     pub change: ShutdownChange,
 }
 
-#[cfg(feature = "i3-4-14")]
-#[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
 impl FromStr for ShutdownEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let val: json::Value = json::from_str(s)?;
-        let change = match val.get("change").unwrap().as_str().unwrap() {
-            "restart" => ShutdownChange::Restart,
-            "exit" => ShutdownChange::Exit,
-            other => {
-                warn!(target: "i3ipc", "Unknown ShutdownChange {}", other);
-                ShutdownChange::Unknown
+        json::from_str(s)
+    }
+}
+
+/// Data for `TickEvent`.
+///
+/// Sent right after subscribing to tick events (with `first` set) and whenever a client sends
+/// a tick message. The payload is passed through untouched, so it can carry structured data
+/// between processes:
+///
+/// ```
+/// use std::str::FromStr;
+/// use i3ipc::event::TickEventInfo;
+///
+/// // what a listener receives after another process called
+/// // `connection.send_tick(r#"{"theme":"dark"}"#)`
+/// let info = TickEventInfo::from_str(r#"{"first":false,"payload":"{\"theme\":\"dark\"}"}"#)
+///     .unwrap();
+/// assert!(!info.first);
+/// let data: serde_json::Value = info.parse_payload().unwrap();
+/// assert_eq!(data["theme"], "dark");
+///
+/// // and it serializes back to the same event
+/// let again = TickEventInfo::from_str(&serde_json::to_string(&info).unwrap()).unwrap();
+/// assert_eq!(again.payload, info.payload);
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TickEventInfo {
+    /// Whether this is the tick sent in response to the subscription itself.
+    pub first: bool,
+    /// The payload given to the tick message, or an empty string for the first tick.
+    pub payload: String,
+}
+
+impl TickEventInfo {
+    /// Parses the payload as JSON, for ticks whose sender put structured data in it.
+    pub fn parse_payload<T: serde::de::DeserializeOwned>(&self) -> Result<T, json::Error> {
+        json::from_str(&self.payload)
+    }
+}
+
+impl FromStr for TickEventInfo {
+    type Err = json::error::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        json::from_str(s)
+    }
+}
+
+/// Data for `InputEvent`.
+///
+/// Sent by sway when an input device is added or removed, or its configuration changes. A
+/// keyboard layout switch is reported as `XkbLayout` with the new layout in
+/// `input.xkb_active_layout_name`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+pub struct InputEventInfo {
+    /// The type of change.
+    pub change: InputChange,
+    /// The input device after the change.
+    pub input: reply::Input,
+}
+
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+impl FromStr for InputEventInfo {
+    type Err = json::error::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        json::from_str(s)
+    }
+}
+
+/// Data for `BarStateUpdateEvent`.
+///
+/// Sent by sway when the visibility of a bar in `hide` mode changes because its modifier was
+/// pressed or released.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+pub struct BarStateUpdateEventInfo {
+    /// The id of the bar, as in `get_bar_ids`.
+    pub id: String,
+    /// Whether the bar should be shown because the modifier is held.
+    pub visible_by_modifier: bool,
+}
+
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+impl FromStr for BarStateUpdateEventInfo {
+    type Err = json::error::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        json::from_str(s)
+    }
+}
+
+/// Which variant of `Event` an event is, without its data. For keying dispatch tables and
+/// metrics on the type of event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EventKind {
+    Workspace,
+    Output,
+    Mode,
+    Window,
+    BarConfig,
+    Binding,
+    Shutdown,
+    Tick,
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    Input,
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    BarStateUpdate,
+    /// `Event::Malformed`, whatever subscription it belongs to.
+    Malformed,
+}
+
+impl EventKind {
+    /// The subscription that delivers events of this kind, or `None` for malformed events.
+    pub fn subscription(self) -> Option<Subscription> {
+        Some(match self {
+            EventKind::Workspace => Subscription::Workspace,
+            EventKind::Output => Subscription::Output,
+            EventKind::Mode => Subscription::Mode,
+            EventKind::Window => Subscription::Window,
+            EventKind::BarConfig => Subscription::BarConfig,
+            EventKind::Binding => Subscription::Binding,
+            EventKind::Shutdown => Subscription::Shutdown,
+            EventKind::Tick => Subscription::Tick,
+            #[cfg(feature = "sway")]
+            EventKind::Input => Subscription::Input,
+            #[cfg(feature = "sway")]
+            EventKind::BarStateUpdate => Subscription::BarStateUpdate,
+            EventKind::Malformed => return None,
+        })
+    }
+}
+
+impl From<Subscription> for EventKind {
+    fn from(subscription: Subscription) -> EventKind {
+        match subscription {
+            Subscription::Workspace => EventKind::Workspace,
+            Subscription::Output => EventKind::Output,
+            Subscription::Mode => EventKind::Mode,
+            Subscription::Window => EventKind::Window,
+            Subscription::BarConfig => EventKind::BarConfig,
+            Subscription::Binding => EventKind::Binding,
+            Subscription::Shutdown => EventKind::Shutdown,
+            Subscription::Tick => EventKind::Tick,
+            #[cfg(feature = "sway")]
+            Subscription::Input => EventKind::Input,
+            #[cfg(feature = "sway")]
+            Subscription::BarStateUpdate => EventKind::BarStateUpdate,
+        }
+    }
+}
+
+impl Event {
+    /// Which kind of event this is.
+    pub fn kind(&self) -> EventKind {
+        match *self {
+            Event::WorkspaceEvent(_) => EventKind::Workspace,
+            Event::OutputEvent(_) => EventKind::Output,
+            Event::ModeEvent(_) => EventKind::Mode,
+            Event::WindowEvent(_) => EventKind::Window,
+            Event::BarConfigEvent(_) => EventKind::BarConfig,
+            Event::BindingEvent(_) => EventKind::Binding,
+            Event::ShutdownEvent(_) => EventKind::Shutdown,
+            Event::TickEvent(_) => EventKind::Tick,
+            #[cfg(feature = "sway")]
+            Event::InputEvent(_) => EventKind::Input,
+            #[cfg(feature = "sway")]
+            Event::BarStateUpdateEvent(_) => EventKind::BarStateUpdate,
+            Event::Malformed { .. } => EventKind::Malformed,
+        }
+    }
+
+    /// The subscription this event was delivered for. For a malformed event, that's the
+    /// subscription its type belongs to, if this library knows it.
+    pub fn subscription(&self) -> Option<Subscription> {
+        match *self {
+            Event::Malformed { subscription, .. } => subscription,
+            _ => self.kind().subscription(),
+        }
+    }
+
+    /// Parses an event without copying the strings in it. `message` is the whole message as it
+    /// came from i3, header included, such as a frame in a buffer filled by a caller's own
+    /// event loop.
+    ///
+    /// A message that is cut short or isn't an event is returned as a `Receive` error.
+    pub fn parse_borrowed(message: &[u8]) -> Result<EventRef<'_>, MessageError> {
+        let invalid = |kind, text| crate::receive_failed(io::Error::new(kind, text));
+        if message.len() < 14 {
+            return Err(invalid(
+                io::ErrorKind::UnexpectedEof,
+                "message is incomplete",
+            ));
+        }
+        let mut header = [0_u8; 14];
+        header.copy_from_slice(&message[..14]);
+        let (payload_len, message_type) =
+            crate::decode_header(&header).map_err(crate::receive_failed)?;
+        let payload = match message.get(14..14 + payload_len as usize) {
+            Some(payload) => payload,
+            None => {
+                return Err(invalid(
+                    io::ErrorKind::UnexpectedEof,
+                    "message is incomplete",
+                ))
             }
         };
-        Ok(ShutdownEventInfo { change })
+        if message_type & crate::EVENT_BIT == 0 {
+            return Err(invalid(
+                io::ErrorKind::InvalidData,
+                "message is a reply, not an event",
+            ));
+        }
+        common::build_event_ref(
+            message_type & !crate::EVENT_BIT,
+            payload,
+            common::MAX_TREE_DEPTH,
+        )
     }
 }
 
+/// An event whose strings borrow from the buffer it was parsed from, created by
+/// `Event::parse_borrowed` or `I3EventListener::inspect_next`.
+///
+/// It is cheaper than `Event` for code that only looks at a couple of fields and drops the
+/// event right away, such as a bar reacting to window title changes. Only the fields that such
+/// code usually needs are kept. Strings that i3 escaped (a title with a quote in it, say) are
+/// copied, and events that are rare anyway are parsed in full.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+pub enum EventRef<'a> {
+    WorkspaceEvent(WorkspaceEventRef<'a>),
+    OutputEvent(OutputEventInfo),
+    ModeEvent(ModeEventRef<'a>),
+    WindowEvent(WindowEventRef<'a>),
+    BarConfigEvent(BarConfigEventInfo),
+    BindingEvent(BindingEventRef<'a>),
+
+    ShutdownEvent(ShutdownEventInfo),
+
+    TickEvent(TickEventRef<'a>),
+
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    InputEvent(InputEventInfo),
+
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    BarStateUpdateEvent(BarStateUpdateEventRef<'a>),
+}
+
+/// Deserializes an optional string, borrowing it if it isn't escaped.
+fn borrow_option<'de: 'a, 'a, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Cow<'a, str>>, D::Error> {
+    #[derive(Deserialize)]
+    struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+    Ok(Option::<Borrowed>::deserialize(deserializer)?.map(|borrowed| borrowed.0))
+}
+
+/// Deserializes a list of strings, borrowing those that aren't escaped.
+fn borrow_vec<'de: 'a, 'a, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Cow<'a, str>>, D::Error> {
+    #[derive(Deserialize)]
+    struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+    Ok(Vec::<Borrowed>::deserialize(deserializer)?
+        .into_iter()
+        .map(|borrowed| borrowed.0)
+        .collect())
+}
+
+/// The fields of a container that are kept in a borrowed event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ContainerRef<'a> {
+    /// The internal ID of the container.
+    pub id: i64,
+    /// The type of the container.
+    #[serde(rename = "type")]
+    pub nodetype: reply::NodeType,
+    /// The internal name of the container; for windows, their title.
+    #[serde(borrow, default, deserialize_with = "borrow_option")]
+    pub name: Option<Cow<'a, str>>,
+    /// The X11 window ID of the window in the container, if any.
+    pub window: Option<i32>,
+    /// The X11 properties of the window, if any.
+    #[serde(borrow, default)]
+    pub window_properties: Option<WindowPropertiesRef<'a>>,
+    /// The Wayland app ID of the window, if any.
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    #[serde(borrow, default, deserialize_with = "borrow_option")]
+    pub app_id: Option<Cow<'a, str>>,
+    /// The marks on the container.
+    #[serde(borrow, default, deserialize_with = "borrow_vec")]
+    pub marks: Vec<Cow<'a, str>>,
+    /// Whether the container wants attention.
+    pub urgent: bool,
+    /// Whether the container has the focus.
+    pub focused: bool,
+}
+
+/// The X11 properties of a window in a borrowed event.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct WindowPropertiesRef<'a> {
+    /// The window title.
+    #[serde(borrow, default, deserialize_with = "borrow_option")]
+    pub title: Option<Cow<'a, str>>,
+    /// The instance part of `WM_CLASS`.
+    #[serde(borrow, default, deserialize_with = "borrow_option")]
+    pub instance: Option<Cow<'a, str>>,
+    /// The class part of `WM_CLASS`.
+    #[serde(borrow, default, deserialize_with = "borrow_option")]
+    pub class: Option<Cow<'a, str>>,
+    /// The `WM_WINDOW_ROLE`.
+    #[serde(borrow, default, deserialize_with = "borrow_option")]
+    pub window_role: Option<Cow<'a, str>>,
+}
+
+/// Data for a borrowed `WorkspaceEvent`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WorkspaceEventRef<'a> {
+    /// The type of change.
+    pub change: WorkspaceChange,
+    /// The workspace affected by the change, if any.
+    #[serde(borrow)]
+    pub current: Option<ContainerRef<'a>>,
+    /// The previously focused workspace, for focus changes.
+    #[serde(borrow)]
+    pub old: Option<ContainerRef<'a>>,
+}
+
+/// Data for a borrowed `ModeEvent`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ModeEventRef<'a> {
+    /// The name of the mode now in use.
+    #[serde(borrow)]
+    pub change: Cow<'a, str>,
+}
+
+/// Data for a borrowed `WindowEvent`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WindowEventRef<'a> {
+    /// The type of change.
+    pub change: WindowChange,
+    /// The window's parent container.
+    #[serde(borrow)]
+    pub container: ContainerRef<'a>,
+}
+
+/// Data for a borrowed `BindingEvent`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BindingEventRef<'a> {
+    /// The type of change.
+    pub change: BindingChange,
+    /// The binding that was run.
+    #[serde(borrow)]
+    pub binding: BindingRef<'a>,
+}
+
+/// Details about a binding in a borrowed event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BindingRef<'a> {
+    /// The i3 command that is configured to run for this binding.
+    #[serde(borrow)]
+    pub command: Cow<'a, str>,
+    /// The group and modifier keys that were configured with this binding.
+    #[serde(borrow, deserialize_with = "borrow_vec")]
+    pub event_state_mask: Vec<Cow<'a, str>>,
+    /// The key code or mouse button count, see `Binding::input_code`.
+    pub input_code: i32,
+    /// The symbol of a binding configured with bindsym.
+    #[serde(borrow, default, deserialize_with = "borrow_option")]
+    pub symbol: Option<Cow<'a, str>>,
+    /// Whether this was a keyboard or a mouse binding.
+    pub input_type: InputType,
+}
+
+/// Data for a borrowed `TickEvent`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TickEventRef<'a> {
+    /// Whether this is the tick sent right after subscribing.
+    pub first: bool,
+    /// The payload given to `send_tick`.
+    #[serde(borrow)]
+    pub payload: Cow<'a, str>,
+}
+
+/// Data for a borrowed `BarStateUpdateEvent`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+pub struct BarStateUpdateEventRef<'a> {
+    /// The ID of the bar.
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+    /// Whether the bar should be shown because its modifier is held.
+    pub visible_by_modifier: bool,
+}
+
 /// Less important types
 pub mod inner {
+    use serde::{Deserialize, Serialize};
     /// The kind of workspace change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
+    #[non_exhaustive]
     pub enum WorkspaceChange {
         Focus,
         Init,
@@ -258,20 +599,37 @@ pub mod inner {
         Reload,
         Restored,
         Move,
-        /// A WorkspaceChange we don't support yet.
-        Unknown,
+        /// A WorkspaceChange we don't support yet, holding the raw string i3 sent.
+        Unknown(String),
     }
 
+    serde_enum_str!(WorkspaceChange {
+        "focus" => Focus,
+        "init" => Init,
+        "empty" => Empty,
+        "urgent" => Urgent,
+        "rename" => Rename,
+        "reload" => Reload,
+        "move" => Move,
+        "restored" => Restored,
+    });
+
     /// The kind of output change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
+    #[non_exhaustive]
     pub enum OutputChange {
         Unspecified,
-        /// An OutputChange we don't support yet.
-        Unknown,
+        /// An OutputChange we don't support yet, holding the raw string i3 sent.
+        Unknown(String),
     }
 
+    serde_enum_str!(OutputChange {
+        "unspecified" => Unspecified,
+    });
+
     /// The kind of window change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
+    #[non_exhaustive]
     pub enum WindowChange {
         /// The window has become managed by i3.
         New,
@@ -291,25 +649,41 @@ pub mod inner {
         Urgent,
 
         /// A mark has been added to or removed from the window.
-        #[cfg(feature = "i3-4-13")]
-        #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-13")))]
         Mark,
 
-        /// A WindowChange we don't support yet.
-        Unknown,
+        /// A WindowChange we don't support yet, holding the raw string i3 sent.
+        Unknown(String),
     }
 
+    serde_enum_str!(WindowChange {
+        "new" => New,
+        "close" => Close,
+        "focus" => Focus,
+        "title" => Title,
+        "fullscreen_mode" => FullscreenMode,
+        "move" => Move,
+        "floating" => Floating,
+        "urgent" => Urgent,
+        "mark" => Mark,
+    });
+
     /// Either keyboard or mouse.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
+    #[non_exhaustive]
     pub enum InputType {
         Keyboard,
         Mouse,
-        /// An InputType we don't support yet.
-        Unknown,
+        /// An InputType we don't support yet, holding the raw string i3 sent.
+        Unknown(String),
     }
 
+    serde_enum_str!(InputType {
+        "keyboard" => Keyboard,
+        "mouse" => Mouse,
+    });
+
     /// Contains details about the binding that was run.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
     pub struct Binding {
         /// The i3 command that is configured to run for this binding.
         pub command: String,
@@ -331,21 +705,59 @@ pub mod inner {
     }
 
     /// The kind of binding change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
+    #[non_exhaustive]
     pub enum BindingChange {
         Run,
-        /// A BindingChange we don't support yet.
-        Unknown,
+        /// A BindingChange we don't support yet, holding the raw string i3 sent.
+        Unknown(String),
     }
 
+    serde_enum_str!(BindingChange {
+        "run" => Run,
+    });
+
     /// The kind of shutdown change.
-    #[derive(Debug, PartialEq)]
-    #[cfg(feature = "i3-4-14")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
+    #[derive(Debug, Clone, PartialEq)]
+    #[non_exhaustive]
     pub enum ShutdownChange {
         Restart,
         Exit,
-        /// A ShutdownChange we don't support yet.
-        Unknown,
+        /// A ShutdownChange we don't support yet, holding the raw string i3 sent.
+        Unknown(String),
     }
+
+    serde_enum_str!(ShutdownChange {
+        "restart" => Restart,
+        "exit" => Exit,
+    });
+
+    /// The kind of input change.
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    #[non_exhaustive]
+    pub enum InputChange {
+        /// The device was added.
+        Added,
+        /// The device was removed.
+        Removed,
+        /// The keymap of a keyboard changed.
+        XkbKeymap,
+        /// The active layout of a keyboard changed.
+        XkbLayout,
+        /// The libinput configuration of the device changed.
+        LibinputConfig,
+        /// An InputChange we don't support yet, holding the raw string sway sent.
+        Unknown(String),
+    }
+
+    #[cfg(feature = "sway")]
+    serde_enum_str!(InputChange {
+        "added" => Added,
+        "removed" => Removed,
+        "xkb_keymap" => XkbKeymap,
+        "xkb_layout" => XkbLayout,
+        "libinput_config" => LibinputConfig,
+    });
 }
@@ -0,0 +1,81 @@
+//! Watching the messages a connection exchanges with i3.
+//!
+//! A `MessageHook` set on an `I3Connection` or `I3EventListener` sees every request, reply
+//! and event going through it, for metrics or request logs. The hook only observes: what it
+//! writes down, and whether it leaves out sensitive payloads such as `exec` commands, is up
+//! to it.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use i3ipc::hook::MessageHook;
+//! use i3ipc::{I3Connection, MessageError};
+//!
+//! struct SlowRequests;
+//!
+//! impl MessageHook for SlowRequests {
+//!     fn after_receive(&self, message_type: u32, payload: &str, elapsed: Duration) {
+//!         if elapsed > Duration::from_millis(50) {
+//!             eprintln!("request {} took {:?} ({} bytes)", message_type, elapsed, payload.len());
+//!         }
+//!     }
+//! }
+//!
+//! let mut connection = I3Connection::connect().unwrap();
+//! connection.set_hook(SlowRequests);
+//! connection.get_tree().unwrap();
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::MessageError;
+
+/// Called for the messages a connection sends and receives. All methods do nothing by
+/// default. They are called on the thread doing the request, so they should be quick.
+pub trait MessageHook: Send + Sync {
+    /// A request is about to be sent.
+    fn before_send(&self, message_type: u32, payload: &str) {
+        let _ = (message_type, payload);
+    }
+
+    /// The reply to a request arrived, `elapsed` after the request was sent. For pipelined
+    /// requests, that's the time since the whole pipeline was sent.
+    fn after_receive(&self, message_type: u32, payload: &str, elapsed: Duration) {
+        let _ = (message_type, payload, elapsed);
+    }
+
+    /// A request failed, `elapsed` after it was sent, because of a network error or a reply
+    /// that couldn't be parsed.
+    fn on_error(&self, message_type: u32, error: &MessageError, elapsed: Duration) {
+        let _ = (message_type, error, elapsed);
+    }
+
+    /// An event arrived. `event_type` is without the highest bit that marks events.
+    fn on_event(&self, event_type: u32, payload: &str) {
+        let _ = (event_type, payload);
+    }
+}
+
+/// The hook of a connection, if any.
+#[derive(Clone, Default)]
+pub(crate) struct Hook(Option<Arc<dyn MessageHook>>);
+
+impl Hook {
+    pub(crate) fn set<H: MessageHook + 'static>(&mut self, hook: H) {
+        self.0 = Some(Arc::new(hook));
+    }
+
+    pub(crate) fn get(&self) -> Option<&dyn MessageHook> {
+        self.0.as_deref()
+    }
+}
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Some(MessageHook)"),
+            None => f.write_str("None"),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Sharing one event connection between several parts of a program.
+//!
+//! An `EventHub` owns a listener and hands each part of the program a channel of its own,
+//! receiving only the events it asked for. i3 sees a single client subscribed to everything
+//! the parts need together:
+//!
+//! ```no_run
+//! use i3ipc::hub::EventHub;
+//! use i3ipc::Subscription;
+//!
+//! let mut hub = EventHub::connect().unwrap();
+//! let windows = hub.subscribe(&[Subscription::Window]);
+//! let workspaces = hub.subscribe(&[Subscription::Workspace, Subscription::Output]);
+//! let handle = hub.start().unwrap();
+//!
+//! std::thread::spawn(move || {
+//!     for event in workspaces {
+//!         println!("workspaces changed: {:?}", event);
+//!     }
+//! });
+//! for event in windows.iter().take(10) {
+//!     println!("window: {:?}", event);
+//! }
+//! handle.shutdown().unwrap();
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use crate::event::Event;
+use crate::{EstablishError, I3EventListener, MessageError, ShutdownHandle, Subscription};
+
+#[derive(Debug)]
+struct Subscriber {
+    events: Vec<Subscription>,
+    sender: Sender<Event>,
+}
+
+/// Fans the events of one listener out to several channels, each with its own subscriptions.
+///
+/// Subscribers are added before the hub is started; `start` then subscribes the listener to
+/// all their events and moves it onto a background thread. A subscriber whose receiver is
+/// dropped gets no more events, and the thread stops once there are none left, when the
+/// returned `ShutdownHandle` is shut down or dropped, or after an error. To keep going after
+/// events that can't be parsed, call `set_lossy` on the listener first; each subscriber gets
+/// the malformed events of its subscriptions then.
+#[derive(Debug)]
+pub struct EventHub {
+    listener: I3EventListener,
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventHub {
+    /// Takes over the given listener, which may already have subscriptions of its own. Their
+    /// events only go to the subscribers that asked for them too.
+    pub fn new(listener: I3EventListener) -> EventHub {
+        EventHub {
+            listener,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Establishes the IPC connection for a new hub.
+    pub fn connect() -> Result<EventHub, EstablishError> {
+        Ok(EventHub::new(I3EventListener::connect()?))
+    }
+
+    /// Adds a subscriber that receives the given events.
+    pub fn subscribe(&mut self, events: &[Subscription]) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(Subscriber {
+            events: events.to_vec(),
+            sender,
+        });
+        receiver
+    }
+
+    /// Subscribes to the events of all subscribers and starts delivering them from a
+    /// background thread.
+    pub fn start(mut self) -> Result<ShutdownHandle, MessageError> {
+        let mut events: Vec<Subscription> = Vec::new();
+        for subscription in self.subscribers.iter().flat_map(|s| s.events.iter()) {
+            if !events.contains(subscription) {
+                events.push(*subscription);
+            }
+        }
+        self.listener.subscribe(&events)?;
+        let socket = self
+            .listener
+            .stream
+            .socket
+            .try_clone()
+            .map_err(crate::receive_failed)?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stopped = stopped.clone();
+            std::thread::spawn(move || self.run(&stopped))
+        };
+        Ok(ShutdownHandle {
+            socket,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    fn run(mut self, stopped: &AtomicBool) -> Result<(), MessageError> {
+        let mut subscribers = std::mem::take(&mut self.subscribers);
+        for event in self.listener.listen() {
+            let event = match event {
+                Ok(event) => event,
+                Err(_) if stopped.load(Ordering::SeqCst) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let subscription = match event.subscription() {
+                Some(subscription) => subscription,
+                None => continue,
+            };
+            subscribers.retain(|subscriber| {
+                !subscriber.events.contains(&subscription)
+                    || subscriber.sender.send(event.clone()).is_ok()
+            });
+            if subscribers.is_empty() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+//! The status line side of the [i3bar protocol](https://i3wm.org/docs/i3bar-protocol.html).
+//!
+//! A status line program writes a `Header` followed by an endless array of status lines, each of
+//! them an array of `Block`s, to stdout. If it asks for click events in the header, i3bar writes
+//! an endless array of `ClickEvent`s to its stdin.
+//!
+//! ```no_run
+//! use std::io;
+//! use i3ipc::i3bar::{Block, Header, StatusWriter};
+//!
+//! let header = Header {
+//!     click_events: true,
+//!     ..Header::default()
+//! };
+//! let mut writer = StatusWriter::new(io::stdout(), &header).unwrap();
+//! let mut block = Block::new("12:00");
+//! block.name = Some("clock".to_owned());
+//! writer.write_blocks(&[block]).unwrap();
+//! ```
+
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+
+/// The first line a status line program writes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    /// The protocol version; always 1.
+    pub version: u32,
+    /// The signal i3bar should send to pause the program when the bar is hidden, instead of
+    /// SIGSTOP.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_signal: Option<i32>,
+    /// The signal i3bar should send to resume the program, instead of SIGCONT.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cont_signal: Option<i32>,
+    /// Whether i3bar should write click events to the program's stdin.
+    #[serde(default)]
+    pub click_events: bool,
+}
+
+impl Default for Header {
+    fn default() -> Header {
+        Header {
+            version: 1,
+            stop_signal: None,
+            cont_signal: None,
+            click_events: false,
+        }
+    }
+}
+
+/// How the text of a block is aligned when it's shorter than `min_width`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// How the text of a block is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Markup {
+    /// Plain text.
+    None,
+    /// Pango markup, such as `<b>bold</b>`.
+    Pango,
+}
+
+/// The minimum width of a block, in pixels or as wide as a sample text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MinWidth {
+    Pixels(u32),
+    Text(String),
+}
+
+/// One block of a status line. Only `full_text` is required; everything else is left out of
+/// the JSON when unset, so i3bar's defaults apply.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    /// The text shown in the block.
+    pub full_text: String,
+    /// A shorter text i3bar uses when the status line doesn't fit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_text: Option<String>,
+    /// The text color, as `#rrggbb`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// The background color, as `#rrggbb`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    /// The border color, as `#rrggbb`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+    /// The width of the top border in pixels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_top: Option<u32>,
+    /// The width of the right border in pixels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_right: Option<u32>,
+    /// The width of the bottom border in pixels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_bottom: Option<u32>,
+    /// The width of the left border in pixels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_left: Option<u32>,
+    /// The minimum width of the block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<MinWidth>,
+    /// How the text is aligned within `min_width`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub align: Option<Align>,
+    /// Whether the block should be shown as urgent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub urgent: Option<bool>,
+    /// Identifies the block in click events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Tells apart blocks with the same `name` in click events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Whether a separator is drawn after the block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<bool>,
+    /// The gap after the block in pixels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator_block_width: Option<u32>,
+    /// How `full_text` and `short_text` are interpreted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markup: Option<Markup>,
+}
+
+impl Block {
+    /// Creates a block showing `full_text` with i3bar's default styling.
+    pub fn new<S: Into<String>>(full_text: S) -> Block {
+        Block {
+            full_text: full_text.into(),
+            ..Block::default()
+        }
+    }
+}
+
+/// Writes the header and status lines of the protocol.
+#[derive(Debug)]
+pub struct StatusWriter<W: Write> {
+    writer: W,
+    first_line: bool,
+}
+
+impl<W: Write> StatusWriter<W> {
+    /// Writes the header and opens the endless array of status lines.
+    pub fn new(mut writer: W, header: &Header) -> io::Result<StatusWriter<W>> {
+        json::to_writer(&mut writer, header)?;
+        writer.write_all(b"\n[\n")?;
+        writer.flush()?;
+        Ok(StatusWriter {
+            writer,
+            first_line: true,
+        })
+    }
+
+    /// Writes one status line and flushes it, so i3bar shows it right away.
+    pub fn write_blocks(&mut self, blocks: &[Block]) -> io::Result<()> {
+        if !self.first_line {
+            self.writer.write_all(b",")?;
+        }
+        self.first_line = false;
+        json::to_writer(&mut self.writer, blocks)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    /// Gives back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// A mouse click on a block, sent by i3bar if the header enabled click events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClickEvent {
+    /// The `name` of the clicked block.
+    pub name: Option<String>,
+    /// The `instance` of the clicked block.
+    pub instance: Option<String>,
+    /// The X11 mouse button: 1 is left, 2 middle, 3 right, 4 and 5 the scroll wheel.
+    pub button: u32,
+    /// The modifiers held during the click, such as `Shift` or `Mod4`.
+    #[serde(default)]
+    pub modifiers: Vec<String>,
+    /// The x coordinate of the click relative to the root window.
+    pub x: i32,
+    /// The y coordinate of the click relative to the root window.
+    pub y: i32,
+    /// The x coordinate of the click relative to the block.
+    pub relative_x: Option<i32>,
+    /// The y coordinate of the click relative to the block.
+    pub relative_y: Option<i32>,
+    /// The x coordinate of the block relative to the bar.
+    pub output_x: Option<i32>,
+    /// The y coordinate of the block relative to the bar.
+    pub output_y: Option<i32>,
+    /// The width of the block in pixels.
+    pub width: Option<i32>,
+    /// The height of the block in pixels.
+    pub height: Option<i32>,
+}
+
+/// Reads the click events i3bar writes to the status line program's stdin.
+///
+/// i3bar writes one event per line, so this iterates over lines, skipping the opening `[` of
+/// the endless array and the commas between events. It ends when the input is closed.
+#[derive(Debug)]
+pub struct ClickEventReader<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> ClickEventReader<R> {
+    /// Reads click events from `reader`, usually `stdin().lock()`.
+    pub fn new(reader: R) -> ClickEventReader<R> {
+        ClickEventReader { reader }
+    }
+}
+
+impl<R: BufRead> Iterator for ClickEventReader<R> {
+    type Item = io::Result<ClickEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            let event = line.trim().trim_start_matches(&['[', ','][..]).trim();
+            if event.is_empty() {
+                continue;
+            }
+            return Some(
+                json::from_str(event).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            );
+        }
+    }
+}
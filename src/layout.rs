@@ -0,0 +1,216 @@
+//! Saving layouts in the format read by i3's `append_layout` command.
+//!
+//! This does the same job as `i3-save-tree`: the containers of a workspace are written out
+//! without their windows, and every window is replaced by swallow criteria matching its class
+//! and instance, so that windows started later are placed back into the same spot.
+//!
+//! ```no_run
+//! use i3ipc::layout::Layout;
+//! use i3ipc::tree::TreeIndex;
+//! use i3ipc::I3Connection;
+//!
+//! let mut connection = I3Connection::connect().unwrap();
+//! let tree = connection.get_tree().unwrap();
+//! let focused = tree.find_focused().unwrap();
+//! let workspace = TreeIndex::new(&tree).workspace_of(focused.id).unwrap();
+//! Layout::from_workspace(workspace).write_to("/tmp/workspace.json").unwrap();
+//!
+//! // later, on an empty workspace
+//! connection.append_layout("/tmp/workspace.json").unwrap();
+//! ```
+//!
+//! Layout managers that write their own files can get the criteria for a single window from
+//! `Swallows::for_window`.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json as json;
+
+use crate::reply::{Node, NodeType, WindowProperty};
+
+/// Escapes the regular expression metacharacters in `s`.
+fn regex_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The swallow criteria for a window, as `i3-save-tree` suggests them: each property of the
+/// window as an anchored, escaped regular expression.
+///
+/// `recommended` holds the class and the instance, which is what a restarted program usually
+/// has again; `alternatives` holds the title, the window role and the machine, which are
+/// written out commented. A window without class and instance, such as a native Wayland one,
+/// gets the alternatives recommended instead, so its criteria never match every window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swallows {
+    /// The criteria to use, as pairs of key and regular expression.
+    pub recommended: Vec<(&'static str, String)>,
+    /// Further criteria that can be used to tell windows of the same program apart.
+    pub alternatives: Vec<(&'static str, String)>,
+}
+
+impl Swallows {
+    /// Suggests swallow criteria for the window of `node`.
+    pub fn for_window(node: &Node) -> Swallows {
+        let criteria = |properties: &[(&'static str, WindowProperty)]| {
+            properties
+                .iter()
+                .filter_map(|(key, property)| {
+                    let value = node.window_property(property.clone())?;
+                    Some((*key, format!("^{}$", regex_escape(value))))
+                })
+                .collect::<Vec<_>>()
+        };
+        let recommended = criteria(&[
+            ("class", WindowProperty::Class),
+            ("instance", WindowProperty::Instance),
+        ]);
+        let alternatives = criteria(&[
+            ("title", WindowProperty::Title),
+            ("window_role", WindowProperty::WindowRole),
+            ("machine", WindowProperty::Machine),
+        ]);
+        if recommended.is_empty() {
+            Swallows {
+                recommended: alternatives,
+                alternatives: Vec::new(),
+            }
+        } else {
+            Swallows {
+                recommended,
+                alternatives,
+            }
+        }
+    }
+
+    /// The recommended criteria as the JSON object `append_layout` reads.
+    pub fn to_json(&self) -> json::Value {
+        let criteria = self
+            .recommended
+            .iter()
+            .map(|(key, regex)| ((*key).to_owned(), json::Value::String(regex.clone())))
+            .collect();
+        json::Value::Object(criteria)
+    }
+}
+
+/// Formats the criteria the way `i3-save-tree` writes them, with the alternatives commented
+/// out. They come first, so the object stays valid whichever of them are uncommented.
+impl fmt::Display for Swallows {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let string = |regex: &str| json::Value::String(regex.to_owned()).to_string();
+        writeln!(f, "{{")?;
+        for (key, regex) in &self.alternatives {
+            writeln!(f, "    // \"{}\": {},", key, string(regex))?;
+        }
+        for (i, (key, regex)) in self.recommended.iter().enumerate() {
+            let comma = if i + 1 < self.recommended.len() {
+                ","
+            } else {
+                ""
+            };
+            writeln!(f, "    \"{}\": {}{}", key, string(regex), comma)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Builds the swallow criteria matching the window of `node`.
+fn swallows(node: &Node) -> json::Value {
+    json::Value::Array(vec![Swallows::for_window(node).to_json()])
+}
+
+fn container(node: &Node) -> json::Value {
+    let mut object = json::Map::new();
+    let nodetype = match node.nodetype {
+        NodeType::FloatingCon => "floating_con",
+        _ => "con",
+    };
+    object.insert("type".to_owned(), nodetype.into());
+    object.insert("layout".to_owned(), json::to_value(&node.layout).unwrap());
+    object.insert("border".to_owned(), json::to_value(&node.border).unwrap());
+    object.insert(
+        "current_border_width".to_owned(),
+        node.current_border_width.into(),
+    );
+    object.insert("percent".to_owned(), json::to_value(node.percent).unwrap());
+    if let Some(ref name) = node.name {
+        object.insert("name".to_owned(), name.clone().into());
+    }
+    if !node.marks.is_empty() {
+        object.insert("marks".to_owned(), json::to_value(&node.marks).unwrap());
+    }
+    if node.nodetype == NodeType::FloatingCon {
+        object.insert("rect".to_owned(), json::to_value(node.rect).unwrap());
+    }
+    if node.window_properties.is_some() {
+        object.insert(
+            "geometry".to_owned(),
+            json::to_value(node.geometry).unwrap(),
+        );
+        object.insert("swallows".to_owned(), swallows(node));
+    } else {
+        let nodes = node.nodes.iter().map(container).collect();
+        object.insert("nodes".to_owned(), json::Value::Array(nodes));
+    }
+    json::Value::Object(object)
+}
+
+/// A saved layout: one or more containers with swallow criteria in place of their windows.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    containers: Vec<json::Value>,
+}
+
+impl Layout {
+    /// Saves a single container and everything in it.
+    pub fn from_node(node: &Node) -> Layout {
+        Layout {
+            containers: vec![container(node)],
+        }
+    }
+
+    /// Saves the tiling and floating containers of a workspace, but not the workspace itself,
+    /// so the layout can be appended to any workspace.
+    pub fn from_workspace(workspace: &Node) -> Layout {
+        Layout {
+            containers: workspace
+                .nodes
+                .iter()
+                .chain(workspace.floating_nodes.iter())
+                .map(container)
+                .collect(),
+        }
+    }
+
+    /// The saved containers, as JSON objects.
+    pub fn containers(&self) -> &[json::Value] {
+        &self.containers
+    }
+
+    /// Writes the layout to a file that `I3Connection::append_layout` can load.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+/// Formats the layout the way `append_layout` expects it: the containers as consecutive JSON
+/// objects.
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for container in &self.containers {
+            let pretty = json::to_string_pretty(container).map_err(|_| fmt::Error)?;
+            writeln!(f, "{}", pretty)?;
+        }
+        Ok(())
+    }
+}
@@ -6,6 +6,17 @@
 //! is to make this process as fool-proof as possible: usage should follow from the type
 //! signatures.
 //!
+//! Commands can be built with the typed builder in the `command` module instead of by hand, and
+//! restricted to certain containers with the `criteria` module. The `autotiling` module picks
+//! the split direction for the focused window from its shape.
+//!
+//! Events can be recorded to a file and replayed later with the `record` module, shared
+//! between several parts of a program with the `hub` module, and thinned out during bursts
+//! with the `debounce` module.
+//!
+//! Status line programs for i3bar can use the `i3bar` module for their side of the i3bar
+//! protocol.
+//!
 //! The types in the `event` and `reply` modules are near direct translations from the JSON
 //! used to talk to i3. The relevant
 //! documentation (meaning of each json object and field) is shamelessly stolen from the
@@ -14,125 +25,583 @@
 //!
 //! This library should cover all of i3's documented ipc features. If it's missing something
 //! please open an issue on github.
+//!
+//! The messages and events of newer i3 versions are always available. Whether the running i3
+//! actually supports them can be checked with `I3Connection::capabilities`; requests it doesn't
+//! understand fail with `MessageError::Unsupported`. The `i3-4-*` features do nothing and are
+//! only kept so existing manifests still build.
+//!
+//! Sway users can enable the `sway` feature, which prefers sway's socket and adds sway-only
+//! fields and messages to the replies.
+//!
+//! The socket is found through `$I3SOCK` or `$SWAYSOCK`, then (with the `x11` feature) the
+//! `I3_SOCKET_PATH` property of the X11 root window, and finally by running
+//! `i3 --get-socketpath`.
+//!
+//! Async applications can enable the `tokio` or `async-std` feature to get
+//! `tokio::I3Connection` and `tokio::I3EventListener` (or their `async_std` counterparts), which
+//! mirror the blocking API. Both are thin adapters over the runtime-agnostic types in the
+//! `transport` module, which work with any `AsyncRead + AsyncWrite` stream.
+//! With the `futures` feature a blocking `I3EventListener` can also be turned into a
+//! `futures::Stream` backed by a thread.
 
 #![cfg_attr(feature = "dox", feature(doc_cfg))]
 
 extern crate byteorder;
-#[macro_use]
 extern crate log;
 extern crate serde;
 extern crate serde_json;
 
+use std::collections::VecDeque;
 use std::error::Error;
 use std::io::prelude::*;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::os::unix::net::UnixStream;
-use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{env, fmt, io, process};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use serde_json as json;
 
+#[cfg(feature = "async-std")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "async-std")))]
+pub mod async_std;
+pub mod autotiling;
+pub mod capabilities;
+pub mod command;
+#[macro_use]
 mod common;
+pub mod criteria;
+pub mod debounce;
 pub mod event;
+pub mod hook;
+pub mod hub;
+pub mod i3bar;
+pub mod layout;
+mod pattern;
+pub mod pipeline;
+pub mod record;
 pub mod reply;
+pub mod retry;
+mod telemetry;
+#[cfg(any(test, feature = "testing"))]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "testing")))]
+pub mod testing;
+#[cfg(feature = "tokio")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "tokio")))]
+pub mod tokio;
+pub mod tracker;
+#[cfg(feature = "futures")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "futures")))]
+pub mod transport;
+pub mod tree;
 
 /// An error initializing a connection.
 ///
 /// It first involves first getting the i3 socket path, then connecting to the socket. Either part
 /// could go wrong which is why there are two possibilities here.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum EstablishError {
     /// An error while getting the socket path
     GetSocketPathError(io::Error),
     /// An error while accessing the socket
     SocketError(io::Error),
+    /// Connected, but asking for the version right afterwards failed. See
+    /// `ConnectionBuilder::capability_probe`.
+    ProbeError(MessageError),
 }
 
-impl Error for EstablishError {
-    fn description(&self) -> &str {
+impl EstablishError {
+    /// The socket path that couldn't be connected to, for a `SocketError` after the path was
+    /// found.
+    pub fn socket_path(&self) -> Option<&str> {
         match *self {
-            EstablishError::GetSocketPathError(_) => "Couldn't determine i3's socket path",
-            EstablishError::SocketError(_) => "Found i3's socket path but failed to connect",
+            EstablishError::SocketError(ref e) => e
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<SocketConnectError>())
+                .map(|inner| inner.path.as_str()),
+            EstablishError::GetSocketPathError(_) | EstablishError::ProbeError(_) => None,
         }
     }
-    fn cause(&self) -> Option<&dyn Error> {
+}
+
+impl Error for EstablishError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match *self {
             EstablishError::GetSocketPathError(ref e) | EstablishError::SocketError(ref e) => {
                 Some(e)
             }
+            EstablishError::ProbeError(ref e) => Some(e),
         }
     }
 }
 
 impl fmt::Display for EstablishError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+        match *self {
+            EstablishError::GetSocketPathError(_) => {
+                write!(f, "Couldn't determine i3's socket path")
+            }
+            EstablishError::SocketError(_) => {
+                write!(f, "Found i3's socket path but failed to connect")
+            }
+            EstablishError::ProbeError(_) => {
+                write!(f, "Connected to i3 but couldn't ask for its version")
+            }
+        }
     }
 }
 
 /// An error sending or receiving a message.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum MessageError {
     /// Network error sending the message.
-    Send(io::Error),
+    #[non_exhaustive]
+    Send {
+        /// The type of the message being sent, or `None` if the error didn't come from sending
+        /// a particular message (for example from setting a socket option).
+        message_type: Option<u32>,
+        error: io::Error,
+    },
     /// Network error receiving the response.
-    Receive(io::Error),
+    #[non_exhaustive]
+    Receive {
+        /// The type of the message whose reply was awaited, or `None` if the error didn't come
+        /// from waiting for a particular reply (for example while waiting for events).
+        message_type: Option<u32>,
+        error: io::Error,
+    },
     /// Got the response but couldn't parse the JSON.
-    JsonCouldntParse(json::Error),
+    #[non_exhaustive]
+    JsonCouldntParse {
+        /// The type of the message the payload came with. Events have the highest bit set, as
+        /// in the protocol.
+        message_type: u32,
+        /// The start of the payload.
+        payload: String,
+        error: json::Error,
+    },
+    /// Got valid JSON, but a field was missing or didn't have the expected type (for example
+    /// because a newer i3 renamed it). `field` is the dotted path to the offending field.
+    #[non_exhaustive]
+    InvalidField {
+        /// The type of the message the payload came with. Events have the highest bit set, as
+        /// in the protocol.
+        message_type: u32,
+        /// The start of the payload.
+        payload: String,
+        field: String,
+        error: json::Error,
+    },
+    /// Received an event with a type this library doesn't know about.
+    UnknownEvent(u32),
+    /// Got a payload with containers nested more deeply than the connection's limit, which is
+    /// refused rather than risking a stack overflow while parsing it.
+    #[non_exhaustive]
+    TreeTooDeep {
+        /// The type of the message the payload came with. Events have the highest bit set, as
+        /// in the protocol.
+        message_type: u32,
+        /// How deeply the containers are nested.
+        depth: usize,
+        /// The limit that was exceeded.
+        limit: usize,
+    },
+    /// Got a reply to a different type of message than the one sent, so the connection is out
+    /// of step with i3. The reply is discarded unparsed.
+    #[non_exhaustive]
+    UnexpectedReplyType {
+        /// The type of the message that was sent.
+        expected: u32,
+        /// The type of the reply that came back.
+        received: u32,
+    },
+    /// A request named a container by its ID, but the layout tree has no container with it.
+    NoSuchContainer(i64),
+    /// The connected i3 or sway is too old to understand messages of this type, as its
+    /// capabilities show, so the message wasn't sent.
+    Unsupported(u32),
+}
+
+impl MessageError {
+    /// The type of the message whose payload couldn't be parsed, with the highest bit set for
+    /// events, the type of the unknown event, or the type of the message whose sending or reply
+    /// failed.
+    pub fn message_type(&self) -> Option<u32> {
+        match *self {
+            MessageError::Send { message_type, .. }
+            | MessageError::Receive { message_type, .. } => message_type,
+            MessageError::JsonCouldntParse { message_type, .. }
+            | MessageError::InvalidField { message_type, .. }
+            | MessageError::TreeTooDeep { message_type, .. } => Some(message_type),
+            MessageError::UnknownEvent(msgtype) => Some(EVENT_BIT | msgtype),
+            MessageError::UnexpectedReplyType { received, .. } => Some(received),
+            MessageError::Unsupported(message_type) => Some(message_type),
+            MessageError::NoSuchContainer(_) => None,
+        }
+    }
 }
 
 impl Error for MessageError {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            MessageError::Send { error: ref e, .. }
+            | MessageError::Receive { error: ref e, .. } => Some(e),
+            MessageError::JsonCouldntParse { error: ref e, .. }
+            | MessageError::InvalidField { error: ref e, .. } => Some(e),
+            MessageError::UnknownEvent(_)
+            | MessageError::TreeTooDeep { .. }
+            | MessageError::UnexpectedReplyType { .. }
+            | MessageError::NoSuchContainer(_)
+            | MessageError::Unsupported(_) => None,
+        }
+    }
+}
+
+/// A `Send` error that didn't come from sending a particular message.
+pub(crate) fn send_failed(error: io::Error) -> MessageError {
+    MessageError::Send {
+        message_type: None,
+        error,
+    }
+}
+
+/// A `Receive` error that didn't come from waiting for a particular reply.
+pub(crate) fn receive_failed(error: io::Error) -> MessageError {
+    MessageError::Receive {
+        message_type: None,
+        error,
+    }
+}
+
+/// Describes what a payload came with, for the parse errors.
+fn payload_origin(message_type: u32) -> String {
+    if message_type & EVENT_BIT != 0 {
+        format!("an event of type {}", message_type & !EVENT_BIT)
+    } else {
+        format!("a reply to message type {}", message_type)
+    }
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            MessageError::Send(_) => "Network error while sending message to i3",
-            MessageError::Receive(_) => "Network error while receiving message from i3",
-            MessageError::JsonCouldntParse(_) => {
-                "Got a response from i3 but couldn't parse the JSON"
+            MessageError::Send {
+                message_type: Some(message_type),
+                ..
+            } => write!(
+                f,
+                "Network error while sending message type {} to i3",
+                message_type
+            ),
+            MessageError::Send { .. } => write!(f, "Network error while sending message to i3"),
+            MessageError::Receive {
+                message_type: Some(message_type),
+                ..
+            } => write!(
+                f,
+                "Network error while receiving the reply to message type {} from i3",
+                message_type
+            ),
+            MessageError::Receive { .. } => {
+                write!(f, "Network error while receiving message from i3")
+            }
+            MessageError::JsonCouldntParse { message_type, .. } => write!(
+                f,
+                "Got {} from i3 but couldn't parse the JSON",
+                payload_origin(message_type)
+            ),
+            MessageError::InvalidField {
+                message_type,
+                ref field,
+                ..
+            } => write!(
+                f,
+                "Got {} from i3 with a missing or invalid field `{}`",
+                payload_origin(message_type),
+                field
+            ),
+            MessageError::UnknownEvent(msgtype) => {
+                write!(f, "Received an unknown event of type {} from i3", msgtype)
             }
+            MessageError::TreeTooDeep {
+                message_type,
+                depth,
+                limit,
+            } => write!(
+                f,
+                "Got {} from i3 with containers nested {} deep, more than the limit of {}",
+                payload_origin(message_type),
+                depth,
+                limit
+            ),
+            MessageError::UnexpectedReplyType { expected, received } => write!(
+                f,
+                "Sent message type {} to i3 but got a reply to message type {}",
+                expected, received
+            ),
+            MessageError::NoSuchContainer(id) => {
+                write!(f, "i3 has no container with ID {}", id)
+            }
+            MessageError::Unsupported(message_type) => write!(
+                f,
+                "The running i3 doesn't support message type {}",
+                message_type
+            ),
         }
     }
-    fn cause(&self) -> Option<&dyn Error> {
+}
+
+/// The magic string every message starts with.
+const MAGIC: &[u8] = b"i3-ipc";
+
+/// Builds the bytes of a message: the magic string, the payload length, the message type and
+/// finally the payload itself.
+fn encode_message(message_type: u32, payload: &str) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(14 + payload.len());
+    bytes.extend(MAGIC); // 6 bytes
+    bytes.write_u32::<LittleEndian>(payload.len() as u32)?; // 4 bytes
+    bytes.write_u32::<LittleEndian>(message_type)?; // 4 bytes
+    bytes.extend(payload.bytes()); // payload.len() bytes
+    Ok(bytes)
+}
+
+/// Checks the magic string of a 14 byte message header and returns a tuple of
+/// (payload length, message type).
+fn decode_header(header: &[u8; 14]) -> io::Result<(u32, u32)> {
+    if &header[..6] != MAGIC {
+        let magic_string = String::from_utf8_lossy(&header[..6]);
+        let error_text = format!(
+            "unexpected magic string: expected 'i3-ipc' but got {}",
+            magic_string
+        );
+        return Err(io::Error::other(error_text));
+    }
+    let mut rest = &header[6..];
+    let payload_len = rest.read_u32::<LittleEndian>()?;
+    let message_type = rest.read_u32::<LittleEndian>()?;
+    Ok((payload_len, message_type))
+}
+
+/// Drops the bytes before the next magic string from `buffer`. If there is none, only a
+/// trailing part that could be the start of one is kept.
+fn skip_to_magic(buffer: &mut Vec<u8>) {
+    if buffer.starts_with(MAGIC) {
+        return;
+    }
+    let start = buffer
+        .windows(MAGIC.len())
+        .position(|window| window == MAGIC)
+        .unwrap_or_else(|| {
+            (buffer.len().saturating_sub(MAGIC.len() - 1)..buffer.len())
+                .find(|&i| MAGIC.starts_with(&buffer[i..]))
+                .unwrap_or(buffer.len())
+        });
+    if start > 0 {
+        telemetry::resynchronized(start);
+        buffer.drain(..start);
+    }
+}
+
+/// Looks for a complete message at the start of `buffer` and returns a tuple of
+/// (message type, length of the message including its header), or `None` if more bytes have
+/// to be read first. The payload is `buffer[14..length]`. With `resync` set, garbage in front
+/// of the message is skipped instead of being an error.
+fn next_frame(buffer: &mut Vec<u8>, resync: bool) -> io::Result<Option<(u32, usize)>> {
+    if resync {
+        skip_to_magic(buffer);
+    }
+    if buffer.len() < 14 {
+        return Ok(None);
+    }
+    let mut header = [0_u8; 14];
+    header.copy_from_slice(&buffer[..14]);
+    let (payload_len, message_type) = decode_header(&header)?;
+    let end = 14 + payload_len as usize;
+    if buffer.len() < end {
+        return Ok(None);
+    }
+    telemetry::received(message_type, payload_len as usize);
+    Ok(Some((message_type, end)))
+}
+
+/// Removes the first complete message from `buffer` and returns a tuple of
+/// (message type, payload), or `None` if more bytes have to be read first.
+fn take_message(buffer: &mut Vec<u8>, resync: bool) -> io::Result<Option<(u32, String)>> {
+    Ok(next_frame(buffer, resync)?.map(|(message_type, end)| {
+        let payload_string = String::from_utf8_lossy(&buffer[14..end]).into_owned();
+        buffer.drain(..end);
+        (message_type, payload_string)
+    }))
+}
+
+/// Where a socket path was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SocketSource {
+    EnvVar(&'static str),
+    #[cfg(feature = "x11")]
+    X11RootWindow,
+    Command(&'static str),
+    Builder,
+}
+
+impl fmt::Display for SocketSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            MessageError::Send(ref e) | MessageError::Receive(ref e) => Some(e),
-            MessageError::JsonCouldntParse(ref e) => Some(e),
+            SocketSource::EnvVar(var) => write!(f, "${}", var),
+            #[cfg(feature = "x11")]
+            SocketSource::X11RootWindow => {
+                write!(f, "the I3_SOCKET_PATH property of the root window")
+            }
+            SocketSource::Command(wm) => write!(f, "`{} --get-socketpath`", wm),
+            SocketSource::Builder => write!(f, "`ConnectionBuilder::socket_path`"),
         }
     }
 }
 
-impl fmt::Display for MessageError {
+/// A failure to connect to a socket path, saying where the path came from.
+#[derive(Debug)]
+struct SocketConnectError {
+    path: String,
+    source: SocketSource,
+    error: io::Error,
+}
+
+impl Error for SocketConnectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl fmt::Display for SocketConnectError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+        write!(
+            f,
+            "couldn't connect to {} (found via {})",
+            self.path, self.source
+        )
+    }
+}
+
+/// Builds the `SocketError` for a failed connection to a path found by `find_socket_path`.
+fn socket_error(path: String, source: SocketSource, error: io::Error) -> EstablishError {
+    let kind = error.kind();
+    EstablishError::SocketError(io::Error::new(
+        kind,
+        SocketConnectError {
+            path,
+            source,
+            error,
+        },
+    ))
+}
+
+/// Connects to the socket found by `find_socket_path`.
+fn connect_socket() -> Result<UnixStream, EstablishError> {
+    connect_socket_to(None, cfg!(feature = "sway"))
+}
+
+/// Connects to `path`, or to the socket found by `find_socket_path` if there is none.
+fn connect_socket_to(path: Option<&str>, prefer_sway: bool) -> Result<UnixStream, EstablishError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(target: "i3ipc", "connect").entered();
+    let found = match path {
+        Some(path) => Ok((path.to_owned(), SocketSource::Builder)),
+        None => find_socket_path(prefer_sway),
+    };
+    let result = match found {
+        Ok((path, source)) => match UnixStream::connect(&path) {
+            Ok(stream) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(target: "i3ipc", path = %path, source = %source, "connected");
+                Ok(stream)
+            }
+            Err(error) => Err(socket_error(path, source, error)),
+        },
+        Err(error) => Err(EstablishError::GetSocketPathError(error)),
+    };
+    #[cfg(feature = "tracing")]
+    if let Err(ref error) = result {
+        tracing::warn!(target: "i3ipc", error = %error, "couldn't connect");
     }
+    result
 }
 
-fn get_socket_path() -> io::Result<String> {
-    if let Ok(sockpath) = env::var("I3SOCK") {
-        return Ok(sockpath);
+/// Reads the I3_SOCKET_PATH property i3 sets on the X11 root window.
+#[cfg(feature = "x11")]
+fn get_x11_socket_path() -> Result<Option<String>, Box<dyn Error>> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let atom = conn.intern_atom(true, b"I3_SOCKET_PATH")?.reply()?.atom;
+    if atom == x11rb::NONE {
+        return Ok(None);
     }
-    // Sway support is an untested and unsupported feature
-    if let Ok(sockpath) = env::var("SWAYSOCK") {
-        return Ok(sockpath);
+    let reply = conn
+        .get_property(false, root, atom, AtomEnum::ANY, 0, u32::MAX / 4)?
+        .reply()?;
+    if reply.value.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&reply.value).into_owned()))
+}
+
+/// Finds the socket path, trying the environment variables, then (with the `x11` feature) the
+/// root window property, and finally asking the window manager binary. With `prefer_sway`, as
+/// with the `sway` feature, sway's own socket wins, since sway also exports I3SOCK.
+fn find_socket_path(prefer_sway: bool) -> io::Result<(String, SocketSource)> {
+    let (env_vars, wm) = if prefer_sway {
+        (["SWAYSOCK", "I3SOCK"], "sway")
+    } else {
+        (["I3SOCK", "SWAYSOCK"], "i3")
+    };
+
+    let tried = format!("${} and ${} aren't set", env_vars[0], env_vars[1]);
+    for var in &env_vars {
+        if let Ok(sockpath) = env::var(var) {
+            return Ok((sockpath, SocketSource::EnvVar(var)));
+        }
     }
 
-    let output = process::Command::new("i3").arg("--get-socketpath").output()?;
+    #[cfg(feature = "x11")]
+    let tried = match get_x11_socket_path() {
+        Ok(Some(sockpath)) => return Ok((sockpath, SocketSource::X11RootWindow)),
+        Ok(None) => tried + ", the root window has no I3_SOCKET_PATH",
+        Err(e) => format!("{}, couldn't read I3_SOCKET_PATH from X11 ({})", tried, e),
+    };
+
+    let output = match process::Command::new(wm).arg("--get-socketpath").output() {
+        Ok(output) => output,
+        Err(e) => {
+            let error_text = format!("{} and running {} failed: {}", tried, wm, e);
+            return Err(io::Error::new(e.kind(), error_text));
+        }
+    };
     if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout)
+        let sockpath = String::from_utf8_lossy(&output.stdout)
             .trim_end_matches('\n')
-            .to_owned())
+            .to_owned();
+        Ok((sockpath, SocketSource::Command(wm)))
     } else {
-        let prefix = "i3 --get-socketpath didn't return 0";
+        let prefix = format!("{} and {} --get-socketpath didn't return 0", tried, wm);
         let error_text = if !output.stderr.is_empty() {
             format!("{}. stderr: {:?}", prefix, output.stderr)
         } else {
-            prefix.to_owned()
+            prefix
         };
-        let error = io::Error::new(io::ErrorKind::Other, error_text);
+        let error = io::Error::other(error_text);
         Err(error)
     }
 }
 
 trait I3Funcs {
-    fn send_i3_message(&mut self, u32, &str) -> io::Result<()>;
+    fn send_i3_message(&mut self, message_type: u32, payload: &str) -> io::Result<()>;
     fn receive_i3_message(&mut self) -> io::Result<(u32, String)>;
     fn send_receive_i3_message<T: serde::de::DeserializeOwned>(
         &mut self,
@@ -141,34 +610,142 @@ trait I3Funcs {
     ) -> Result<T, MessageError>;
 }
 
-impl I3Funcs for UnixStream {
+/// A socket to i3 together with the framing state that has to survive a timed out read or
+/// write, so the next request picks up on a message boundary instead of reading garbage.
+#[derive(Debug)]
+struct BufferedStream<S> {
+    socket: S,
+    /// Bytes read from the socket that don't form a complete message yet.
+    read_buffer: Vec<u8>,
+    /// The unsent tail of a message whose write timed out.
+    unsent: Vec<u8>,
+    /// Replies owed to requests that timed out; they are dropped when they finally arrive.
+    stale_replies: usize,
+    /// Events that arrived while waiting for a reply.
+    events: VecDeque<(u32, String)>,
+    /// Whether to skip to the next magic string when the data doesn't start with one.
+    resync: bool,
+    /// How deeply containers may be nested in a payload.
+    max_tree_depth: usize,
+    hook: hook::Hook,
+}
+
+impl<S: Read + Write> BufferedStream<S> {
+    fn new(socket: S) -> BufferedStream<S> {
+        BufferedStream {
+            socket,
+            read_buffer: Vec::new(),
+            unsent: Vec::new(),
+            stale_replies: 0,
+            events: VecDeque::new(),
+            resync: false,
+            max_tree_depth: common::MAX_TREE_DEPTH,
+            hook: hook::Hook::default(),
+        }
+    }
+
+    fn take_message(&mut self) -> io::Result<Option<(u32, String)>> {
+        take_message(&mut self.read_buffer, self.resync)
+    }
+
+    /// Appends one read's worth of bytes from the socket to the read buffer.
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let mut chunk = [0_u8; 4096];
+        loop {
+            match self.socket.read(&mut chunk) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "i3 closed the connection",
+                    ))
+                }
+                Ok(n) => {
+                    self.read_buffer.extend_from_slice(&chunk[..n]);
+                    return Ok(());
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write_unsent(&mut self) -> io::Result<()> {
+        while !self.unsent.is_empty() {
+            match self.socket.write(&self.unsent) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "i3 stopped accepting data",
+                    ))
+                }
+                Ok(n) => {
+                    self.unsent.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        loop {
+            match self.socket.flush() {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                result => return result,
+            }
+        }
+    }
+}
+
+impl BufferedStream<UnixStream> {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.socket.set_read_timeout(Some(timeout))?;
+        self.socket.set_write_timeout(Some(timeout))
+    }
+}
+
+/// The highest order bit of the message type, set on events.
+const EVENT_BIT: u32 = 1 << 31;
+
+/// Whether an event is the shutdown event i3 sends before exiting.
+fn is_exit(event_type: u32, payload: &str) -> bool {
+    event_type == Subscription::Shutdown.event_type()
+        && json::from_str::<event::ShutdownEventInfo>(payload)
+            .is_ok_and(|info| info.change == event::inner::ShutdownChange::Exit)
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+impl<S: Read + Write> I3Funcs for BufferedStream<S> {
     fn send_i3_message(&mut self, message_type: u32, payload: &str) -> io::Result<()> {
-        let mut bytes = Vec::with_capacity(14 + payload.len());
-        bytes.extend("i3-ipc".bytes()); // 6 bytes
-        bytes.write_u32::<LittleEndian>(payload.len() as u32)?; // 4 bytes
-        bytes.write_u32::<LittleEndian>(message_type)?; // 4 bytes
-        bytes.extend(payload.bytes()); // payload.len() bytes
-        self.write_all(&bytes[..])
+        // finish a message whose write timed out first, so i3 never sees a torn frame.
+        self.write_unsent()?;
+        self.unsent = encode_message(message_type, payload)?;
+        let message_len = self.unsent.len();
+        telemetry::sent(message_type, payload.len());
+        let result = self.write_unsent();
+        if result.is_err() {
+            if self.unsent.len() == message_len {
+                // nothing went out, so the message can simply be dropped.
+                self.unsent.clear();
+            } else {
+                // i3 will answer once the rest of the message follows.
+                self.stale_replies += 1;
+            }
+        }
+        result
     }
 
     /// returns a tuple of (message type, payload)
     fn receive_i3_message(&mut self) -> io::Result<(u32, String)> {
-        let mut magic_data = [0_u8; 6];
-        self.read_exact(&mut magic_data)?;
-        let magic_string = String::from_utf8_lossy(&magic_data);
-        if magic_string != "i3-ipc" {
-            let error_text = format!(
-                "unexpected magic string: expected 'i3-ipc' but got {}",
-                magic_string
-            );
-            return Err(io::Error::new(io::ErrorKind::Other, error_text));
+        loop {
+            if let Some(message) = self.take_message()? {
+                return Ok(message);
+            }
+            self.fill_buffer()?;
         }
-        let payload_len = self.read_u32::<LittleEndian>()?;
-        let message_type = self.read_u32::<LittleEndian>()?;
-        let mut payload_data = vec![0_u8; payload_len as usize];
-        self.read_exact(&mut payload_data[..])?;
-        let payload_string = String::from_utf8_lossy(&payload_data).into_owned();
-        Ok((message_type, payload_string))
     }
 
     fn send_receive_i3_message<T: serde::de::DeserializeOwned>(
@@ -176,22 +753,145 @@ impl I3Funcs for UnixStream {
         message_type: u32,
         payload: &str,
     ) -> Result<T, MessageError> {
-        if let Err(e) = self.send_i3_message(message_type, payload) {
-            return Err(MessageError::Send(e));
+        let hook = self.hook.clone();
+        if let Some(hook) = hook.get() {
+            hook.before_send(message_type, payload);
         }
-        let received = match self.receive_i3_message() {
-            Ok((received_type, payload)) => {
-                assert_eq!(message_type, received_type);
-                payload
+        let sent = Instant::now();
+        let result = self
+            .send_i3_message(message_type, payload)
+            .map_err(|error| MessageError::Send {
+                message_type: Some(message_type),
+                error,
+            })
+            .and_then(|()| self.receive_reply(message_type))
+            .and_then(|received| {
+                if let Some(hook) = hook.get() {
+                    hook.after_receive(message_type, &received, sent.elapsed());
+                }
+                common::parse(message_type, &received, self.max_tree_depth)
+                    .inspect_err(telemetry::parse_failed)
+            });
+        if let (Err(ref e), Some(hook)) = (&result, hook.get()) {
+            hook.on_error(message_type, e, sent.elapsed());
+        }
+        result
+    }
+}
+
+impl<S: Read + Write> BufferedStream<S> {
+    /// Waits for the reply to a message of the given type, setting events that arrive first
+    /// aside and dropping replies owed to earlier requests that timed out.
+    fn receive_reply(&mut self, message_type: u32) -> Result<String, MessageError> {
+        loop {
+            match self.receive_i3_message() {
+                Ok(message) if message.0 & EVENT_BIT != 0 => self.events.push_back(message),
+                Ok(_) if self.stale_replies > 0 => self.stale_replies -= 1,
+                Ok((received_type, _)) if received_type != message_type => {
+                    return Err(MessageError::UnexpectedReplyType {
+                        expected: message_type,
+                        received: received_type,
+                    })
+                }
+                Ok((_, payload)) => return Ok(payload),
+                Err(e) => {
+                    if is_timeout(&e) {
+                        self.stale_replies += 1;
+                    }
+                    return Err(MessageError::Receive {
+                        message_type: Some(message_type),
+                        error: e,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Sends all `requests` at once, then waits for their replies in order and returns their
+    /// payloads unparsed. If this fails halfway, the replies still owed are dropped when they
+    /// arrive, as for a single request that timed out.
+    fn send_receive_many(
+        &mut self,
+        requests: &[(u32, String)],
+    ) -> Result<Vec<String>, MessageError> {
+        let hook = self.hook.clone();
+        if let Some(hook) = hook.get() {
+            for (message_type, payload) in requests {
+                hook.before_send(*message_type, payload);
+            }
+        }
+        let sent = Instant::now();
+        let mut replies = Vec::with_capacity(requests.len());
+        let result = self.send_many_receive_into(requests, &mut replies);
+        if let Some(hook) = hook.get() {
+            let elapsed = sent.elapsed();
+            for ((message_type, _), payload) in requests.iter().zip(&replies) {
+                hook.after_receive(*message_type, payload, elapsed);
+            }
+            if let Err(ref e) = result {
+                for (message_type, _) in &requests[replies.len()..] {
+                    hook.on_error(*message_type, e, elapsed);
+                }
             }
-            Err(e) => {
-                return Err(MessageError::Receive(e));
+        }
+        result.map(|()| replies)
+    }
+
+    fn send_many_receive_into(
+        &mut self,
+        requests: &[(u32, String)],
+        replies: &mut Vec<String>,
+    ) -> Result<(), MessageError> {
+        let send_failed = |message_type: u32| {
+            move |error| MessageError::Send {
+                message_type: Some(message_type),
+                error,
             }
         };
-        match json::from_str(&received) {
-            Ok(v) => Ok(v),
-            Err(e) => Err(MessageError::JsonCouldntParse(e)),
+        let first_type = match requests.first() {
+            Some(&(message_type, _)) => message_type,
+            None => return Ok(()),
+        };
+        self.write_unsent().map_err(send_failed(first_type))?;
+        let mut unsent = Vec::new();
+        for (message_type, payload) in requests {
+            unsent.extend(
+                encode_message(*message_type, payload).map_err(send_failed(*message_type))?,
+            );
+            telemetry::sent(*message_type, payload.len());
+        }
+        let message_len = unsent.len();
+        self.unsent = unsent;
+        if let Err(e) = self.write_unsent() {
+            // the first request that wasn't written in full.
+            let mut written = message_len - self.unsent.len();
+            let failed = requests
+                .iter()
+                .find(|(_, payload)| {
+                    let len = 14 + payload.len();
+                    let unfinished = written < len;
+                    written = written.saturating_sub(len);
+                    unfinished
+                })
+                .map_or(first_type, |&(message_type, _)| message_type);
+            if self.unsent.len() == message_len {
+                self.unsent.clear();
+            } else {
+                // i3 will answer all of them once the rest follows.
+                self.stale_replies += requests.len();
+            }
+            return Err(send_failed(failed)(e));
+        }
+        for (message_type, _) in requests {
+            match self.receive_reply(*message_type) {
+                Ok(payload) => replies.push(payload),
+                Err(e) => {
+                    self.stale_replies += requests.len() - replies.len() - 1;
+                    return Err(e);
+                }
+            }
         }
+        Ok(())
     }
 }
 
@@ -200,499 +900,5155 @@ impl I3Funcs for UnixStream {
 /// Each element may be `Err` or `Ok` (Err for an issue with the socket connection or data sent
 /// from i3).
 #[derive(Debug)]
-pub struct EventIterator<'a> {
-    stream: &'a mut UnixStream,
+pub struct EventIterator<'a, S = UnixStream> {
+    listener: &'a mut I3EventListener<S>,
+}
+
+impl<'a, S> EventIterator<'a, S> {
+    /// Drops the window events whose container doesn't match `criteria`, a `Criteria` or a
+    /// `Matcher` compiled from one. Other events and errors are kept.
+    pub fn filter_matching<M: Into<criteria::Matcher>>(self, criteria: M) -> MatchingEvents<'a, S> {
+        MatchingEvents {
+            events: self,
+            matcher: criteria.into(),
+        }
+    }
 }
 
-impl<'a> Iterator for EventIterator<'a> {
+impl<'a, S: Read + Write> Iterator for EventIterator<'a, S> {
     type Item = Result<event::Event, MessageError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        /// the msgtype passed in should have its highest order bit stripped
-        /// makes the i3 event
-        fn build_event(msgtype: u32, payload: &str) -> Result<event::Event, json::Error> {
-            Ok(match msgtype {
-                0 => {
-                    event::Event::WorkspaceEvent(event::WorkspaceEventInfo::from_str(payload)?)
-                }
-                1 => event::Event::OutputEvent(event::OutputEventInfo::from_str(payload)?),
-                2 => event::Event::ModeEvent(event::ModeEventInfo::from_str(payload)?),
-                3 => event::Event::WindowEvent(event::WindowEventInfo::from_str(payload)?),
-                4 => {
-                    event::Event::BarConfigEvent(event::BarConfigEventInfo::from_str(payload)?)
-                }
-                5 => event::Event::BindingEvent(event::BindingEventInfo::from_str(payload)?),
+        self.listener.receive_event(true).transpose()
+    }
+}
+
+/// Iterates over events, leaving out window events about other windows. Created by
+/// `EventIterator::filter_matching`.
+#[derive(Debug)]
+pub struct MatchingEvents<'a, S = UnixStream> {
+    events: EventIterator<'a, S>,
+    matcher: criteria::Matcher,
+}
 
-                #[cfg(feature = "i3-4-14")]
-                6 => event::Event::ShutdownEvent(event::ShutdownEventInfo::from_str(payload)?),
+impl<'a, S: Read + Write> Iterator for MatchingEvents<'a, S> {
+    type Item = Result<event::Event, MessageError>;
 
-                _ => unreachable!("received an event we aren't subscribed to!"),
-            })
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.events.next()? {
+                Ok(event::Event::WindowEvent(ref info))
+                    if !self.matcher.matches(&info.container) => {}
+                event => return Some(event),
+            }
         }
+    }
+}
 
-        match self.stream.receive_i3_message() {
-            Ok((msgint, payload)) => {
-                // strip the highest order bit indicating it's an event.
-                let msgtype = (msgint << 1) >> 1;
+/// Iterates over events, owning the listener. Created by `I3EventListener::into_events`.
+#[derive(Debug)]
+pub struct OwnedEventIterator<S = UnixStream> {
+    listener: I3EventListener<S>,
+}
 
-                Some(match build_event(msgtype, &payload) {
-                    Ok(event) => Ok(event),
-                    Err(e) => Err(MessageError::JsonCouldntParse(e)),
-                })
+impl<S> OwnedEventIterator<S> {
+    /// The listener the events come from, for subscribing to more events or changing its
+    /// settings between events.
+    pub fn listener(&mut self) -> &mut I3EventListener<S> {
+        &mut self.listener
+    }
+
+    /// Gives back the listener.
+    pub fn into_listener(self) -> I3EventListener<S> {
+        self.listener
+    }
+}
+
+impl<S: Read + Write> Iterator for OwnedEventIterator<S> {
+    type Item = Result<event::Event, MessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.listener.receive_event(true).transpose()
+    }
+}
+
+/// An event together with the JSON payload it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawEvent {
+    /// The parsed event.
+    pub event: event::Event,
+    /// The type of the event as sent by i3, without the highest bit that marks events.
+    pub event_type: u32,
+    /// The payload exactly as i3 sent it, for logging, forwarding, or reading fields this
+    /// crate doesn't know about.
+    pub payload: String,
+}
+
+/// Iterates over events along with their payloads. Created by `I3EventListener::listen_raw`.
+#[derive(Debug)]
+pub struct RawEventIterator<'a, S = UnixStream> {
+    listener: &'a mut I3EventListener<S>,
+}
+
+impl<'a, S: Read + Write> Iterator for RawEventIterator<'a, S> {
+    type Item = Result<RawEvent, MessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.listener.receive_raw_event(true).transpose()
+    }
+}
+
+/// Iterates over the events of a single subscription, already unpacked from `Event`. Created
+/// by `I3EventListener::windows_only` and its siblings.
+pub struct TypedEventIterator<'a, T, S = UnixStream> {
+    listener: &'a mut I3EventListener<S>,
+    unpack: fn(event::Event) -> Option<T>,
+}
+
+impl<'a, T, S: fmt::Debug> fmt::Debug for TypedEventIterator<'a, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypedEventIterator")
+            .field("listener", &self.listener)
+            .finish()
+    }
+}
+
+impl<'a, T, S: Read + Write> Iterator for TypedEventIterator<'a, T, S> {
+    type Item = Result<T, MessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.listener.receive_event(true) {
+                Ok(Some(event)) => {
+                    if let Some(unpacked) = (self.unpack)(event) {
+                        return Some(Ok(unpacked));
+                    }
+                }
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
             }
-            Err(e) => Some(Err(MessageError::Receive(e))),
         }
     }
 }
 
 /// A subscription for `I3EventListener`
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum Subscription {
     Workspace,
     Output,
     Mode,
     Window,
+    #[serde(rename = "barconfig_update")]
     BarConfig,
     Binding,
-    #[cfg(feature = "i3-4-14")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
     Shutdown,
+    Tick,
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    Input,
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    BarStateUpdate,
 }
 
-/// Abstraction over an ipc socket to i3. Handles events.
-#[derive(Debug)]
-pub struct I3EventListener {
-    stream: UnixStream,
-}
-
-impl I3EventListener {
-    /// Establishes the IPC connection.
-    pub fn connect() -> Result<I3EventListener, EstablishError> {
-        match get_socket_path() {
-            Ok(path) => match UnixStream::connect(path) {
-                Ok(stream) => Ok(I3EventListener { stream }),
-                Err(error) => Err(EstablishError::SocketError(error)),
-            },
-            Err(error) => Err(EstablishError::GetSocketPathError(error)),
-        }
-    }
-
-    /// Subscribes your connection to certain events.
-    pub fn subscribe(&mut self, events: &[Subscription]) -> Result<reply::Subscribe, MessageError> {
-        let json = "[ ".to_owned()
-            + &events
-                .iter()
-                .map(|s| match *s {
-                    Subscription::Workspace => "\"workspace\"",
-                    Subscription::Output => "\"output\"",
-                    Subscription::Mode => "\"mode\"",
-                    Subscription::Window => "\"window\"",
-                    Subscription::BarConfig => "\"barconfig_update\"",
-                    Subscription::Binding => "\"binding\"",
-                    #[cfg(feature = "i3-4-14")]
-                    Subscription::Shutdown => "\"shutdown\"",
-                })
-                .collect::<Vec<_>>()
-                .join(", ")[..]
-            + " ]";
-        let j: json::Value = self.stream.send_receive_i3_message(2, &json)?;
-        let is_success = j.get("success").unwrap().as_bool().unwrap();
-        Ok(reply::Subscribe {
-            success: is_success,
+impl Subscription {
+    /// The subscription whose events have the given type, if it is one this library knows.
+    pub(crate) fn from_event_type(event_type: u32) -> Option<Subscription> {
+        Some(match event_type {
+            0 => Subscription::Workspace,
+            1 => Subscription::Output,
+            2 => Subscription::Mode,
+            3 => Subscription::Window,
+            4 => Subscription::BarConfig,
+            5 => Subscription::Binding,
+            6 => Subscription::Shutdown,
+            7 => Subscription::Tick,
+            #[cfg(feature = "sway")]
+            20 => Subscription::BarStateUpdate,
+            #[cfg(feature = "sway")]
+            21 => Subscription::Input,
+            _ => return None,
         })
     }
 
-    /// Iterate over subscribed events forever.
-    pub fn listen(&mut self) -> EventIterator {
-        EventIterator {
-            stream: &mut self.stream,
+    /// The type of the events sent for this subscription.
+    fn event_type(self) -> u32 {
+        match self {
+            Subscription::Workspace => 0,
+            Subscription::Output => 1,
+            Subscription::Mode => 2,
+            Subscription::Window => 3,
+            Subscription::BarConfig => 4,
+            Subscription::Binding => 5,
+            Subscription::Shutdown => 6,
+            Subscription::Tick => 7,
+            #[cfg(feature = "sway")]
+            Subscription::Input => 21,
+            #[cfg(feature = "sway")]
+            Subscription::BarStateUpdate => 20,
         }
     }
 }
 
-/// Abstraction over an ipc socket to i3. Handles messages/replies.
+/// Abstraction over an ipc socket to i3. Handles events.
+///
+/// Events can either be consumed by blocking on `listen`, or by putting the socket in
+/// non-blocking mode with `set_nonblocking` and calling `try_next` whenever the file descriptor
+/// (available through `AsRawFd`) becomes readable.
 #[derive(Debug)]
-pub struct I3Connection {
-    stream: UnixStream,
+pub struct I3EventListener<S = UnixStream> {
+    stream: BufferedStream<S>,
+    /// Events that are still sent by i3 but no longer delivered.
+    unsubscribed: Vec<Subscription>,
+    /// Whether events that can't be parsed become `Event::Malformed` instead of errors.
+    lossy: bool,
+    /// Whether i3 announced that it is exiting, after which no more events are read.
+    shut_down: bool,
 }
 
-impl I3Connection {
+impl I3EventListener<UnixStream> {
     /// Establishes the IPC connection.
-    pub fn connect() -> Result<I3Connection, EstablishError> {
-        match get_socket_path() {
-            Ok(path) => match UnixStream::connect(path) {
-                Ok(stream) => Ok(I3Connection { stream }),
-                Err(error) => Err(EstablishError::SocketError(error)),
-            },
-            Err(error) => Err(EstablishError::GetSocketPathError(error)),
-        }
+    pub fn connect() -> Result<I3EventListener, EstablishError> {
+        Ok(I3EventListener {
+            stream: BufferedStream::new(connect_socket()?),
+            unsubscribed: Vec::new(),
+            lossy: false,
+            shut_down: false,
+        })
     }
 
-    #[deprecated(since = "0.8.0", note = "Renamed to run_command")]
-    pub fn command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
-        self.run_command(string)
+    /// Moves the underlying socket into or out of non-blocking mode. See `try_next`.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.stream.socket.set_nonblocking(nonblocking)
     }
 
-    /// The payload of the message is a command for i3 (like the commands you can bind to keys
-    /// in the configuration file) and will be executed directly after receiving it.
-    pub fn run_command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(0, string)?;
-        let commands = j.as_array().unwrap();
-        let vec: Vec<_> = commands
-            .iter()
-            .map(|c| reply::CommandOutcome {
-                success: c.get("success").unwrap().as_bool().unwrap(),
-                error: match c.get("error") {
-                    Some(val) => Some(val.as_str().unwrap().to_owned()),
-                    None => None,
-                },
+    /// Moves the listener onto a background thread that sends its events to a channel, for
+    /// programs that already have an event loop of their own.
+    ///
+    /// The thread runs until the returned `ShutdownHandle` is shut down or dropped, or until an
+    /// error occurs; either way the channel is closed afterwards. `ShutdownHandle::shutdown`
+    /// returns the error that stopped the thread, if any. To keep going after events that can't
+    /// be parsed, call `set_lossy` first.
+    pub fn into_channel(
+        mut self,
+    ) -> io::Result<(std::sync::mpsc::Receiver<event::Event>, ShutdownHandle)> {
+        let socket = self.stream.socket.try_clone()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let thread = {
+            let stopped = stopped.clone();
+            std::thread::spawn(move || {
+                for event in self.listen() {
+                    match event {
+                        Ok(event) => {
+                            if sender.send(event).is_err() {
+                                return Ok(());
+                            }
+                        }
+                        Err(_) if stopped.load(Ordering::SeqCst) => return Ok(()),
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok(())
             })
-            .collect();
+        };
+        Ok((
+            receiver,
+            ShutdownHandle {
+                socket,
+                stopped,
+                thread: Some(thread),
+            },
+        ))
+    }
 
-        Ok(reply::Command { outcomes: vec })
+    /// Gives this listener the timeouts, lossy mode, resynchronizing, tree depth limit and hook
+    /// of `old`, which it replaces after a reconnect.
+    fn take_settings_from(&mut self, old: &I3EventListener) -> io::Result<()> {
+        self.stream
+            .socket
+            .set_read_timeout(old.stream.socket.read_timeout()?)?;
+        self.stream
+            .socket
+            .set_write_timeout(old.stream.socket.write_timeout()?)?;
+        self.lossy = old.lossy;
+        self.stream.resync = old.stream.resync;
+        self.stream.max_tree_depth = old.stream.max_tree_depth;
+        self.stream.hook = old.stream.hook.clone();
+        Ok(())
     }
 
-    /// Gets the current workspaces.
-    pub fn get_workspaces(&mut self) -> Result<reply::Workspaces, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(1, "")?;
-        let jworkspaces = j.as_array().unwrap();
-        let workspaces: Vec<_> = jworkspaces
-            .iter()
-            .map(|w| reply::Workspace {
-                num: w.get("num").unwrap().as_i64().unwrap() as i32,
-                name: w.get("name").unwrap().as_str().unwrap().to_owned(),
-                visible: w.get("visible").unwrap().as_bool().unwrap(),
-                focused: w.get("focused").unwrap().as_bool().unwrap(),
-                urgent: w.get("urgent").unwrap().as_bool().unwrap(),
-                rect: common::build_rect(w.get("rect").unwrap()),
-                output: w.get("output").unwrap().as_str().unwrap().to_owned(),
-            })
-            .collect();
-        Ok(reply::Workspaces { workspaces })
+    /// Sets a read and write timeout on the underlying socket, so a hung i3 can't block
+    /// `listen` or `subscribe` forever; they return a `Receive` or `Send` error with a
+    /// `WouldBlock` or `TimedOut` kind instead. A partially received event is kept, so listening
+    /// can simply be resumed afterwards. With a timeout set, `try_next` returns `Ok(None)` once
+    /// it expires.
+    pub fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.stream.set_timeout(timeout)
     }
 
-    /// Gets the current outputs.
-    pub fn get_outputs(&mut self) -> Result<reply::Outputs, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(3, "")?;
-        let joutputs = j.as_array().unwrap();
-        let outputs: Vec<_> = joutputs
-            .iter()
-            .map(|o| reply::Output {
-                name: o.get("name").unwrap().as_str().unwrap().to_owned(),
-                #[cfg(feature = "sway-1-1")]
-                make: o.get("make").unwrap().as_str().unwrap().to_owned(),
-                #[cfg(feature = "sway-1-1")]
-                model: o.get("model").unwrap().as_str().unwrap().to_owned(),
-                #[cfg(feature = "sway-1-1")]
-                serial: o.get("serial").unwrap().as_str().unwrap().to_owned(),
-                #[cfg(feature = "sway-1-1")]
-                scale: o.get("scale").map(|s| s.as_f64().unwrap().to_owned()),
-                #[cfg(feature = "sway-1-1")]
-                subpixel_hinting: o.get("subpixel_hinting").map(|s| s.as_str() .unwrap().to_owned()),
-                #[cfg(feature = "sway-1-1")]
-                transform: o.get("transform").map(|s| s.as_str().unwrap().to_owned()),
-                #[cfg(feature = "sway-1-1")]
-                modes: common::build_modes(o.get("modes").unwrap()),
-                #[cfg(feature = "sway-1-1")]
-                current_mode: o.get("current_mode").map(|s| common::build_mode(s)),
-                active: o.get("active").unwrap().as_bool().unwrap(),
-                primary: o.get("primary").unwrap().as_bool().unwrap(),
-                current_workspace: match o.get("current_workspace").unwrap().clone() {
-                    json::Value::String(c_w) => Some(c_w),
-                    json::Value::Null => None,
-                    _ => unreachable!(),
-                },
-                #[cfg(feature = "sway-1-1")]
-                dpms: o.get("dpms").unwrap().as_bool().unwrap(),
-                rect: common::build_rect(o.get("rect").unwrap()),
-            })
+    /// Subscribes to window events and waits for a new window for which `predicate` returns
+    /// true, for scripts that launch a program and then move or resize its window. Returns
+    /// `Ok(None)` if no such window appears within `timeout`.
+    ///
+    /// Only the properties a window has when i3 starts managing it can be matched on; many
+    /// programs set their final title later. Call `subscribe` with `Subscription::Window`
+    /// before launching the program, or its window may appear before this is called;
+    /// `exec_and_wait` takes care of that for programs launched through i3. Other
+    /// events received meanwhile are dropped, and the socket's read timeout is put back
+    /// afterwards. To wait for a window matching i3 criteria, pass
+    /// `|node| matcher.matches(node)` with a `criteria::Matcher`.
+    pub fn wait_for_window<F: FnMut(&reply::Node) -> bool>(
+        &mut self,
+        timeout: Duration,
+        predicate: F,
+    ) -> Result<Option<reply::Node>, MessageError> {
+        self.subscribe(&[Subscription::Window])?;
+        self.wait_for_new_window(timeout, predicate)
+    }
+
+    /// Runs `cmd` over `connection` and waits for the window it opens, like `wait_for_window`.
+    /// The listener subscribes before the command is sent, so the window can't appear too
+    /// early to be seen. Returns `Ok(None)` without waiting if i3 refuses the command.
+    ///
+    /// `matcher` should pick out the new window by something it has from the start, such as
+    /// its class; a window some other program opens meanwhile can match too.
+    pub fn exec_and_wait<C: Read + Write, F: FnMut(&reply::Node) -> bool>(
+        &mut self,
+        connection: &mut I3Connection<C>,
+        cmd: &str,
+        matcher: F,
+        timeout: Duration,
+    ) -> Result<Option<reply::Node>, MessageError> {
+        self.subscribe(&[Subscription::Window])?;
+        if !connection.exec(cmd)?.success {
+            return Ok(None);
+        }
+        self.wait_for_new_window(timeout, matcher)
+    }
+
+    /// Iterates over events, holding them back until none has arrived for `quiet` and then
+    /// handing out each burst without the events a later one of it replaces: a change of the
+    /// same kind to the same window, workspace, output or bar, or a newer focus or binding
+    /// mode. Other events are kept in order. A burst that never pauses for `quiet` is handed
+    /// out after ten times `quiet`, which `Debounced::max_wait` changes.
+    pub fn debounce(&mut self, quiet: Duration) -> debounce::Debounced<'_> {
+        debounce::Debounced::debounce(self, quiet)
+    }
+
+    /// Iterates over events, merging title changes of the same window that arrive together
+    /// into the last one. Unlike `debounce`, this doesn't wait for more title changes and
+    /// leaves all other events alone.
+    pub fn coalesce_title_changes(&mut self) -> debounce::Debounced<'_> {
+        debounce::Debounced::coalesce_title_changes(self)
+    }
+
+    /// Waits for a window event about a new window matching `predicate` for at most
+    /// `timeout`.
+    fn wait_for_new_window<F: FnMut(&reply::Node) -> bool>(
+        &mut self,
+        timeout: Duration,
+        mut predicate: F,
+    ) -> Result<Option<reply::Node>, MessageError> {
+        self.wait_for(timeout, |event| match event {
+            event::Event::WindowEvent(info)
+                if info.change == event::inner::WindowChange::New && predicate(&info.container) =>
+            {
+                Some(info.container)
+            }
+            _ => None,
+        })
+    }
+
+    /// Drops events until `pick` returns something for one of them, for at most `timeout`,
+    /// restoring the socket's read timeout afterwards.
+    fn wait_for<T, F: FnMut(event::Event) -> Option<T>>(
+        &mut self,
+        timeout: Duration,
+        mut pick: F,
+    ) -> Result<Option<T>, MessageError> {
+        let previous = self.stream.socket.read_timeout().map_err(receive_failed)?;
+        let deadline = Instant::now() + timeout;
+        let found = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                break Ok(None);
+            }
+            if let Err(e) = self.stream.socket.set_read_timeout(Some(remaining)) {
+                break Err(receive_failed(e));
+            }
+            match self.try_next() {
+                Ok(Some(event)) => {
+                    if let Some(found) = pick(event) {
+                        break Ok(Some(found));
+                    }
+                }
+                Ok(None) => break Ok(None),
+                Err(e) => break Err(e),
+            }
+        };
+        self.stream
+            .socket
+            .set_read_timeout(previous)
+            .map_err(receive_failed)?;
+        found
+    }
+}
+
+impl<S: Read + Write> I3EventListener<S> {
+    /// Wraps an already connected stream, such as one end of a socketpair or a tunnel to
+    /// another machine's i3 socket.
+    pub fn from_stream(stream: S) -> I3EventListener<S> {
+        I3EventListener {
+            stream: BufferedStream::new(stream),
+            unsubscribed: Vec::new(),
+            lossy: false,
+            shut_down: false,
+        }
+    }
+
+    /// Subscribes your connection to certain events.
+    ///
+    /// This can be called again at any time to add more events; events of the earlier
+    /// subscriptions that arrive while waiting for i3's reply are kept. It does wait for the
+    /// reply though, so call it while the listener is in blocking mode.
+    pub fn subscribe(&mut self, events: &[Subscription]) -> Result<reply::Subscribe, MessageError> {
+        self.unsubscribed.retain(|event| !events.contains(event));
+        let json = common::build_subscribe_payload(events);
+        self.stream.send_receive_i3_message(2, &json)
+    }
+
+    /// Stops delivering the given events.
+    ///
+    /// i3 has no way to cancel a subscription, so it keeps sending these events and the
+    /// listener silently drops them. Calling `subscribe` with them again delivers them again.
+    pub fn unsubscribe(&mut self, events: &[Subscription]) {
+        for event in events {
+            if !self.unsubscribed.contains(event) {
+                self.unsubscribed.push(*event);
+            }
+        }
+    }
+
+    /// Sets whether an event that can't be parsed (because a newer i3 changed its format, or
+    /// it has a type this library doesn't know) is delivered as `Event::Malformed` and logged,
+    /// rather than returned as an error. Off by default.
+    ///
+    /// Long running programs usually want this, so that a single odd event doesn't end their
+    /// event loop.
+    pub fn set_lossy(&mut self, lossy: bool) {
+        self.lossy = lossy;
+    }
+
+    /// Sets whether data that doesn't start with the magic string is skipped up to the next
+    /// one, rather than returned as a `Receive` error. Off by default.
+    ///
+    /// This is a best effort for links that can lose or mangle bytes: a payload that happens to
+    /// contain the magic string can still throw the listener off.
+    pub fn set_resynchronize(&mut self, resync: bool) {
+        self.stream.resync = resync;
+    }
+
+    /// Sets how deeply containers may be nested in an event before it is returned as a
+    /// `TreeTooDeep` error instead of being parsed. Only containers in `nodes` and
+    /// `floating_nodes` count. The default is 64.
+    ///
+    /// Parsing recurses for every level, using about 8 KiB of stack per level in an optimized
+    /// build and several times that in a debug build. A higher limit may need a thread with a
+    /// larger stack, while threads with a small stack may want a lower one.
+    pub fn set_max_tree_depth(&mut self, depth: usize) {
+        self.stream.max_tree_depth = depth;
+    }
+
+    /// Sets a hook that sees the subscribe requests and the events of this listener.
+    pub fn set_hook<H: hook::MessageHook + 'static>(&mut self, hook: H) {
+        self.stream.hook.set(hook);
+    }
+
+    /// Waits for the next event and hands it to `inspect` in its borrowed form, parsed straight
+    /// from the listener's buffer without copying its strings. For programs that only look at a
+    /// couple of fields of each event. Unlike the other ways of listening, an event that can't
+    /// be parsed is returned as an error even in lossy mode.
+    pub fn inspect_next<T>(
+        &mut self,
+        inspect: impl FnOnce(event::EventRef<'_>) -> T,
+    ) -> Result<T, MessageError> {
+        let handled = self.receive_message(true, |listener, msgtype, payload| {
+            common::build_event_ref(msgtype, payload.as_bytes(), listener.stream.max_tree_depth)
+                .map(inspect)
+        })?;
+        // a blocking receive only returns without an event once i3 has exited.
+        handled.unwrap_or_else(|| {
+            Err(receive_failed(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "i3 has exited",
+            )))
+        })
+    }
+
+    /// Whether i3 sent a shutdown event announcing that it is exiting. The listener reads no
+    /// more events after that: iterators end, `try_next` returns `Ok(None)` and `inspect_next`
+    /// returns an `UnexpectedEof` error, rather than failing once i3 closes the socket. A
+    /// restart doesn't count, as i3 comes back.
+    pub fn was_shutdown(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Iterate over subscribed events forever, or until i3 exits.
+    pub fn listen(&mut self) -> EventIterator<'_, S> {
+        EventIterator { listener: self }
+    }
+
+    /// Iterate over subscribed events forever, consuming the listener. Unlike `listen`, the
+    /// iterator doesn't borrow anything, so it can be stored in a struct or moved to another
+    /// thread.
+    pub fn into_events(self) -> OwnedEventIterator<S> {
+        OwnedEventIterator { listener: self }
+    }
+
+    /// Subscribes to window events and iterates over them forever. Events of other
+    /// subscriptions the listener already has are skipped, as are malformed events in lossy
+    /// mode.
+    pub fn windows_only(
+        &mut self,
+    ) -> Result<TypedEventIterator<'_, event::WindowEventInfo, S>, MessageError> {
+        self.typed(Subscription::Window, |event| match event {
+            event::Event::WindowEvent(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    /// Subscribes to workspace events and iterates over them forever, like `windows_only`.
+    pub fn workspaces_only(
+        &mut self,
+    ) -> Result<TypedEventIterator<'_, event::WorkspaceEventInfo, S>, MessageError> {
+        self.typed(Subscription::Workspace, |event| match event {
+            event::Event::WorkspaceEvent(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    /// Subscribes to binding events and iterates over them forever, like `windows_only`.
+    pub fn bindings_only(
+        &mut self,
+    ) -> Result<TypedEventIterator<'_, event::BindingEventInfo, S>, MessageError> {
+        self.typed(Subscription::Binding, |event| match event {
+            event::Event::BindingEvent(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    /// Subscribes to output events and iterates over them forever, like `windows_only`.
+    pub fn outputs_only(
+        &mut self,
+    ) -> Result<TypedEventIterator<'_, event::OutputEventInfo, S>, MessageError> {
+        self.typed(Subscription::Output, |event| match event {
+            event::Event::OutputEvent(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    fn typed<T>(
+        &mut self,
+        subscription: Subscription,
+        unpack: fn(event::Event) -> Option<T>,
+    ) -> Result<TypedEventIterator<'_, T, S>, MessageError> {
+        self.subscribe(&[subscription])?;
+        Ok(TypedEventIterator {
+            listener: self,
+            unpack,
+        })
+    }
+
+    /// Iterate over subscribed events forever, keeping the JSON payload of each event next to
+    /// the parsed event.
+    pub fn listen_raw(&mut self) -> RawEventIterator<'_, S> {
+        RawEventIterator { listener: self }
+    }
+
+    /// Returns the next event if a complete one can be read without waiting, and `Ok(None)`
+    /// otherwise. Partially received events are kept until the rest arrives.
+    ///
+    /// This only returns immediately once the listener is in non-blocking mode; in blocking
+    /// mode it waits for the next event like `listen` does.
+    pub fn try_next(&mut self) -> Result<Option<event::Event>, MessageError> {
+        self.receive_event(false)
+    }
+
+    /// Like `try_next`, but keeps the JSON payload next to the parsed event.
+    pub fn try_next_raw(&mut self) -> Result<Option<RawEvent>, MessageError> {
+        self.receive_raw_event(false)
+    }
+
+    fn receive_event(&mut self, block: bool) -> Result<Option<event::Event>, MessageError> {
+        self.receive_message(block, |listener, msgtype, payload| {
+            listener.build_event(msgtype, payload)
+        })?
+        .transpose()
+    }
+
+    fn receive_raw_event(&mut self, block: bool) -> Result<Option<RawEvent>, MessageError> {
+        self.receive_message(block, |listener, msgtype, payload| {
+            Ok(RawEvent {
+                event: listener.build_event(msgtype, payload)?,
+                event_type: msgtype,
+                payload: payload.to_owned(),
+            })
+        })?
+        .transpose()
+    }
+
+    /// Parses an event, turning a parse failure into `Event::Malformed` in lossy mode.
+    fn build_event(&self, msgtype: u32, payload: &str) -> Result<event::Event, MessageError> {
+        common::build_event_lossy(msgtype, payload, self.stream.max_tree_depth, self.lossy)
+    }
+
+    /// Hands the type (with the event bit stripped) and payload of the next event that hasn't
+    /// been unsubscribed from to `handle`. The payload is borrowed straight from the read
+    /// buffer, which keeps its capacity from one event to the next, so only what `handle`
+    /// builds is allocated. Unless `block` is set, a timed out read returns `Ok(None)` instead
+    /// of an error.
+    fn receive_message<T>(
+        &mut self,
+        block: bool,
+        handle: impl FnOnce(&Self, u32, &str) -> T,
+    ) -> Result<Option<T>, MessageError> {
+        if self.shut_down {
+            return Ok(None);
+        }
+        loop {
+            if let Some((msgint, payload)) = self.stream.events.pop_front() {
+                // strip the highest order bit indicating it's an event.
+                let msgtype = msgint & !EVENT_BIT;
+                if !self.is_unsubscribed(msgtype) {
+                    if let Some(hook) = self.stream.hook.get() {
+                        hook.on_event(msgtype, &payload);
+                    }
+                    self.shut_down = is_exit(msgtype, &payload);
+                    return Ok(Some(handle(self, msgtype, &payload)));
+                }
+                continue;
+            }
+            let resync = self.stream.resync;
+            match next_frame(&mut self.stream.read_buffer, resync) {
+                Ok(Some((msgint, end))) => {
+                    let msgtype = msgint & !EVENT_BIT;
+                    if self.is_unsubscribed(msgtype) {
+                        self.stream.read_buffer.drain(..end);
+                        continue;
+                    }
+                    let (handled, exit) = {
+                        let payload = String::from_utf8_lossy(&self.stream.read_buffer[14..end]);
+                        if let Some(hook) = self.stream.hook.get() {
+                            hook.on_event(msgtype, &payload);
+                        }
+                        (handle(self, msgtype, &payload), is_exit(msgtype, &payload))
+                    };
+                    self.shut_down = exit;
+                    self.stream.read_buffer.drain(..end);
+                    return Ok(Some(handled));
+                }
+                Ok(None) => match self.stream.fill_buffer() {
+                    Ok(()) => {}
+                    Err(ref e) if !block && is_timeout(e) => return Ok(None),
+                    Err(e) => return Err(receive_failed(e)),
+                },
+                Err(e) => return Err(receive_failed(e)),
+            }
+        }
+    }
+
+    fn is_unsubscribed(&self, msgtype: u32) -> bool {
+        self.unsubscribed
+            .iter()
+            .any(|event| event.event_type() == msgtype)
+    }
+
+    /// Moves the listener onto a background thread and exposes its events as a
+    /// `futures::Stream`.
+    ///
+    /// The thread stops after the first network error (which is still delivered through the
+    /// stream) or once the stream is dropped and another event arrives.
+    #[cfg(feature = "futures")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "futures")))]
+    pub fn into_stream(mut self) -> EventStream
+    where
+        S: Send + 'static,
+    {
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        std::thread::spawn(move || {
+            for event in self.listen() {
+                let is_receive_error = matches!(event, Err(MessageError::Receive { .. }));
+                if sender.unbounded_send(event).is_err() || is_receive_error {
+                    break;
+                }
+            }
+        });
+        EventStream { receiver }
+    }
+}
+
+/// Stops the thread started by `I3EventListener::into_channel` or `EventHub::start`. Dropping
+/// the handle stops it too, without waiting for it.
+#[derive(Debug)]
+pub struct ShutdownHandle {
+    socket: UnixStream,
+    stopped: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<Result<(), MessageError>>>,
+}
+
+impl ShutdownHandle {
+    /// Stops the thread and waits for it to finish. Returns the error that had already stopped
+    /// the thread before, if there was one.
+    pub fn shutdown(mut self) -> Result<(), MessageError> {
+        self.stop();
+        match self.thread.take().map(|thread| thread.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(receive_failed(io::Error::other(
+                "the listener thread panicked",
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wakes up the thread blocked reading from the socket.
+        let _ = self.socket.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+impl Drop for ShutdownHandle {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            self.stop();
+        }
+    }
+}
+
+impl<S: AsRawFd> AsRawFd for I3EventListener<S> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.socket.as_raw_fd()
+    }
+}
+
+/// Registers the socket of the listener with a `mio::Poll`.
+///
+/// A readiness notification may carry several events, and events can also be buffered by
+/// `subscribe` without the socket becoming readable again, so call `try_next` (in
+/// non-blocking mode) until it returns `Ok(None)` after every notification and once after
+/// subscribing.
+#[cfg(feature = "mio")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "mio")))]
+impl<S: AsRawFd> mio::event::Source for I3EventListener<S> {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+/// A `futures::Stream` of events from an `I3EventListener` running on a background thread.
+///
+/// Created by `I3EventListener::into_stream`.
+#[cfg(feature = "futures")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "futures")))]
+#[derive(Debug)]
+pub struct EventStream {
+    receiver: futures::channel::mpsc::UnboundedReceiver<Result<event::Event, MessageError>>,
+}
+
+#[cfg(feature = "futures")]
+impl futures::Stream for EventStream {
+    type Item = Result<event::Event, MessageError>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::pin::Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+/// An event from a `ResilientEventListener`.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+pub enum ResilientEvent {
+    /// An event sent by i3.
+    Event(event::Event),
+    /// The connection to i3 was lost (usually because i3 restarted in place) and has been
+    /// re-established and resubscribed. Events sent in between are lost, so any state built up
+    /// from earlier events should be refreshed.
+    Reconnected,
+}
+
+/// An event listener that survives i3 restarting in place.
+///
+/// When i3 announces a restart (if subscribed to shutdown events) or the socket is closed, the
+/// listener rediscovers the socket path, reconnects, resubscribes to everything it was
+/// subscribed to before and yields `ResilientEvent::Reconnected`. After i3 announces that it is
+/// exiting, or once reconnecting keeps failing, iteration ends. Settings made through this
+/// listener's setters are kept across reconnects.
+#[derive(Debug)]
+pub struct ResilientEventListener {
+    listener: I3EventListener,
+    subscriptions: Vec<Subscription>,
+    retry: retry::RetryPolicy,
+    needs_reconnect: bool,
+    finished: bool,
+}
+
+impl ResilientEventListener {
+    /// Establishes the IPC connection.
+    pub fn connect() -> Result<ResilientEventListener, EstablishError> {
+        Ok(ResilientEventListener {
+            listener: I3EventListener::connect()?,
+            subscriptions: Vec::new(),
+            retry: retry::RetryPolicy::new(),
+            needs_reconnect: false,
+            finished: false,
+        })
+    }
+
+    /// Subscribes your connection to certain events. The subscriptions are remembered and
+    /// renewed after every reconnect.
+    pub fn subscribe(&mut self, events: &[Subscription]) -> Result<reply::Subscribe, MessageError> {
+        let reply = self.listener.subscribe(events)?;
+        if reply.success {
+            for event in events {
+                if !self.subscriptions.contains(event) {
+                    self.subscriptions.push(*event);
+                }
+            }
+        }
+        Ok(reply)
+    }
+
+    /// Stops delivering the given events, and doesn't renew their subscriptions after a
+    /// reconnect. See `I3EventListener::unsubscribe`.
+    pub fn unsubscribe(&mut self, events: &[Subscription]) {
+        self.subscriptions.retain(|event| !events.contains(event));
+        self.listener.unsubscribe(events);
+    }
+
+    /// Sets how often, and how far apart, reconnecting is attempted before giving up. Defaults
+    /// to 50 attempts 100ms apart. Shorthand for `set_retry_policy` with
+    /// `RetryPolicy::fixed(attempts, delay)`.
+    pub fn set_reconnect_policy(&mut self, attempts: u32, delay: Duration) {
+        self.retry = retry::RetryPolicy::fixed(attempts, delay);
+    }
+
+    /// Sets how reconnecting is attempted, including backoff between the attempts and a hook
+    /// that is called once reconnected and resubscribed.
+    pub fn set_retry_policy(&mut self, policy: retry::RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// Iterate over subscribed events until i3 exits.
+    pub fn listen(&mut self) -> ResilientEventIterator<'_> {
+        ResilientEventIterator { listener: self }
+    }
+
+    /// Sets a read and write timeout. See `I3EventListener::set_timeout`.
+    pub fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.listener.set_timeout(timeout)
+    }
+
+    /// Sets whether events that can't be parsed are delivered as `Event::Malformed`. See
+    /// `I3EventListener::set_lossy`.
+    pub fn set_lossy(&mut self, lossy: bool) {
+        self.listener.set_lossy(lossy);
+    }
+
+    /// Sets whether data that doesn't start with the magic string is skipped. See
+    /// `I3EventListener::set_resynchronize`.
+    pub fn set_resynchronize(&mut self, resync: bool) {
+        self.listener.set_resynchronize(resync);
+    }
+
+    /// Sets how deeply containers may be nested in an event. See
+    /// `I3EventListener::set_max_tree_depth`.
+    pub fn set_max_tree_depth(&mut self, depth: usize) {
+        self.listener.set_max_tree_depth(depth);
+    }
+
+    /// Sets a hook that sees the subscribe requests and the events. See
+    /// `I3EventListener::set_hook`.
+    pub fn set_hook<H: hook::MessageHook + 'static>(&mut self, hook: H) {
+        self.listener.set_hook(hook);
+    }
+
+    /// Connects a new listener with the settings of the current one and resubscribes it. The
+    /// settings (timeout, lossy mode, resynchronizing, tree depth limit and hook) are carried
+    /// over before subscribing, so the subscribe request already uses them.
+    fn reconnect(&mut self) -> Result<(), MessageError> {
+        let subscriptions = &self.subscriptions;
+        let old = &self.listener;
+        let listener = self
+            .retry
+            .retry(|| {
+                let mut listener = I3EventListener::connect().map_err(establish_failed)?;
+                listener.take_settings_from(old).map_err(receive_failed)?;
+                listener.subscribe(subscriptions)?;
+                Ok(listener)
+            })
+            .map_err(gave_up_reconnecting)?;
+        self.listener = listener;
+        Ok(())
+    }
+
+    fn next_event(&mut self) -> Option<Result<ResilientEvent, MessageError>> {
+        if self.finished {
+            return None;
+        }
+        if self.needs_reconnect {
+            self.needs_reconnect = false;
+            return match self.reconnect() {
+                Ok(()) => Some(Ok(ResilientEvent::Reconnected)),
+                Err(e) => {
+                    self.finished = true;
+                    Some(Err(e))
+                }
+            };
+        }
+        match self.listener.listen().next()? {
+            Ok(event) => {
+                if let event::Event::ShutdownEvent(ref info) = event {
+                    match info.change {
+                        event::inner::ShutdownChange::Exit => self.finished = true,
+                        _ => self.needs_reconnect = true,
+                    }
+                }
+                Some(Ok(ResilientEvent::Event(event)))
+            }
+            Err(MessageError::Receive { .. }) => {
+                self.needs_reconnect = true;
+                self.next_event()
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Iterates over events from a `ResilientEventListener`.
+#[derive(Debug)]
+pub struct ResilientEventIterator<'a> {
+    listener: &'a mut ResilientEventListener,
+}
+
+impl<'a> Iterator for ResilientEventIterator<'a> {
+    type Item = Result<ResilientEvent, MessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.listener.next_event()
+    }
+}
+
+/// Turns a failed attempt to reconnect into the error reported if it was the last one.
+fn establish_failed(error: EstablishError) -> MessageError {
+    match error {
+        EstablishError::GetSocketPathError(e) | EstablishError::SocketError(e) => receive_failed(e),
+        EstablishError::ProbeError(e) => e,
+    }
+}
+
+fn gave_up_reconnecting(last_error: Option<MessageError>) -> MessageError {
+    last_error.unwrap_or_else(|| {
+        receive_failed(io::Error::new(
+            io::ErrorKind::NotConnected,
+            "gave up reconnecting to i3",
+        ))
+    })
+}
+
+/// A command connection that reconnects when i3 restarts in place.
+///
+/// Requests go through `run`, which reconnects according to the retry policy and repeats the
+/// request if the connection turns out to be lost. Settings made through `connection`, such as
+/// a timeout, don't carry over to the new connection.
+#[derive(Debug)]
+pub struct ResilientConnection {
+    connection: I3Connection,
+    retry: retry::RetryPolicy,
+}
+
+impl ResilientConnection {
+    /// Establishes the IPC connection.
+    pub fn connect() -> Result<ResilientConnection, EstablishError> {
+        Ok(ResilientConnection {
+            connection: I3Connection::connect()?,
+            retry: retry::RetryPolicy::new(),
+        })
+    }
+
+    /// Sets how reconnecting is attempted. Defaults to `RetryPolicy::new()`.
+    pub fn set_retry_policy(&mut self, policy: retry::RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// The current connection, for requests that shouldn't be repeated.
+    pub fn connection(&mut self) -> &mut I3Connection {
+        &mut self.connection
+    }
+
+    /// Runs `request` on the connection. If sending or receiving fails for any reason but a
+    /// timeout, reconnects and runs it once more. A command i3 received just before the
+    /// connection broke may then run twice, which matters for ones like `exec`.
+    pub fn run<T>(
+        &mut self,
+        mut request: impl FnMut(&mut I3Connection) -> Result<T, MessageError>,
+    ) -> Result<T, MessageError> {
+        match request(&mut self.connection) {
+            Err(MessageError::Send { error: ref e, .. })
+            | Err(MessageError::Receive { error: ref e, .. })
+                if !is_timeout(e) => {}
+            result => return result,
+        }
+        self.connection = self
+            .retry
+            .retry(|| I3Connection::connect().map_err(establish_failed))
+            .map_err(gave_up_reconnecting)?;
+        request(&mut self.connection)
+    }
+
+    /// Runs a command with `run`.
+    pub fn run_command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
+        self.run(|connection| connection.run_command(string))
+    }
+}
+
+/// Configures and establishes an `I3Connection`. Created by `I3Connection::builder`:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use i3ipc::retry::RetryPolicy;
+/// use i3ipc::I3Connection;
+///
+/// let mut connection = I3Connection::builder()
+///     .timeout(Duration::from_secs(1))
+///     .retry(RetryPolicy::fixed(10, Duration::from_millis(200)))
+///     .connect()
+///     .unwrap();
+/// println!("{:?}", connection.capabilities().unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionBuilder {
+    socket_path: Option<String>,
+    timeout: Option<Duration>,
+    retry: Option<retry::RetryPolicy>,
+    prefer_sway: bool,
+    capability_probe: bool,
+}
+
+impl ConnectionBuilder {
+    /// Connects to the given socket instead of looking for one.
+    pub fn socket_path<P: Into<String>>(mut self, path: P) -> ConnectionBuilder {
+        self.socket_path = Some(path.into());
+        self
+    }
+
+    /// Sets a read and write timeout for all requests on the connection. See
+    /// `I3Connection::set_timeout`; `I3Connection::with_timeout` overrides it for single
+    /// requests.
+    pub fn timeout(mut self, timeout: Duration) -> ConnectionBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Keeps trying according to `policy` if the first attempt to connect fails, for programs
+    /// started alongside the window manager. The policy's hook is called if a retry succeeds.
+    pub fn retry(mut self, policy: retry::RetryPolicy) -> ConnectionBuilder {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Looks for sway's socket before i3's, as the `sway` feature does by default.
+    pub fn prefer_sway(mut self) -> ConnectionBuilder {
+        self.prefer_sway = true;
+        self
+    }
+
+    /// Sets whether to ask for the version right after connecting, as `I3Connection::connect`
+    /// does, so that a socket that accepts connections but doesn't answer is noticed at once
+    /// and requests the running i3 doesn't understand are refused with
+    /// `MessageError::Unsupported` rather than left waiting for a reply that never comes. A
+    /// failure is returned as `EstablishError::ProbeError`. On by default; turning it off saves
+    /// a round trip, and such a connection only checks requests after `capabilities` was
+    /// called.
+    pub fn capability_probe(mut self, probe: bool) -> ConnectionBuilder {
+        self.capability_probe = probe;
+        self
+    }
+
+    /// Establishes the connection.
+    pub fn connect(self) -> Result<I3Connection, EstablishError> {
+        match self.connect_once() {
+            Err(first_error) => match self.retry {
+                Some(ref policy) => policy
+                    .retry(|| self.connect_once())
+                    .map_err(|last_error| last_error.unwrap_or(first_error)),
+                None => Err(first_error),
+            },
+            result => result,
+        }
+    }
+
+    fn connect_once(&self) -> Result<I3Connection, EstablishError> {
+        let socket = connect_socket_to(self.socket_path.as_deref(), self.prefer_sway)?;
+        let mut connection = I3Connection::from_stream(socket);
+        if let Some(timeout) = self.timeout {
+            connection
+                .set_timeout(timeout)
+                .map_err(EstablishError::SocketError)?;
+        }
+        if self.capability_probe {
+            connection
+                .capabilities()
+                .map_err(EstablishError::ProbeError)?;
+        }
+        Ok(connection)
+    }
+}
+
+/// Abstraction over an ipc socket to i3. Handles messages/replies.
+#[derive(Debug)]
+pub struct I3Connection<S = UnixStream> {
+    stream: BufferedStream<S>,
+    capabilities: Option<capabilities::Capabilities>,
+}
+
+impl I3Connection<UnixStream> {
+    /// Establishes the IPC connection and asks for the version, so `capabilities` is known.
+    pub fn connect() -> Result<I3Connection, EstablishError> {
+        let mut connection = I3Connection {
+            stream: BufferedStream::new(connect_socket()?),
+            capabilities: None,
+        };
+        connection
+            .capabilities()
+            .map_err(EstablishError::ProbeError)?;
+        Ok(connection)
+    }
+
+    /// Starts configuring a connection, for programs that need more control than `connect`
+    /// gives over where and how it connects.
+    pub fn builder() -> ConnectionBuilder {
+        ConnectionBuilder {
+            socket_path: None,
+            timeout: None,
+            retry: None,
+            prefer_sway: cfg!(feature = "sway"),
+            capability_probe: true,
+        }
+    }
+
+    /// Sets a read and write timeout on the underlying socket, so a hung or restarting i3 can't
+    /// block a request forever; it returns a `Send` or `Receive` error with a `WouldBlock` or
+    /// `TimedOut` kind instead. The connection stays usable afterwards: the late reply to the
+    /// timed out request is discarded when it arrives.
+    pub fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.stream.set_timeout(timeout)
+    }
+
+    /// Runs `requests` with a read and write timeout of `timeout`, then puts back the timeouts
+    /// set before, if any. A request that takes longer returns a `Send` or `Receive` error
+    /// with a `WouldBlock` or `TimedOut` kind and leaves the connection usable, as with
+    /// `set_timeout`.
+    pub fn with_timeout<T>(
+        &mut self,
+        timeout: Duration,
+        requests: impl FnOnce(&mut Self) -> Result<T, MessageError>,
+    ) -> Result<T, MessageError> {
+        let read_timeout = self.stream.socket.read_timeout();
+        let write_timeout = self.stream.socket.write_timeout();
+        let (read_timeout, write_timeout) = match (read_timeout, write_timeout) {
+            (Ok(read), Ok(write)) => (read, write),
+            (Err(e), _) | (_, Err(e)) => return Err(send_failed(e)),
+        };
+        self.set_timeout(timeout).map_err(send_failed)?;
+        let result = requests(self);
+        let socket = &self.stream.socket;
+        socket
+            .set_read_timeout(read_timeout)
+            .and_then(|()| socket.set_write_timeout(write_timeout))
+            .map_err(send_failed)?;
+        result
+    }
+
+    /// Runs a command, giving up after `timeout`. See `with_timeout`.
+    pub fn run_command_timeout(
+        &mut self,
+        string: &str,
+        timeout: Duration,
+    ) -> Result<reply::Command, MessageError> {
+        self.with_timeout(timeout, |connection| connection.run_command(string))
+    }
+}
+
+impl<S: Read + Write> I3Connection<S> {
+    /// Wraps an already connected stream, such as one end of a socketpair or a tunnel to
+    /// another machine's i3 socket.
+    pub fn from_stream(stream: S) -> I3Connection<S> {
+        I3Connection {
+            stream: BufferedStream::new(stream),
+            capabilities: None,
+        }
+    }
+
+    /// Sets whether data that doesn't start with the magic string is skipped up to the next
+    /// one, rather than returned as a `Receive` error. Off by default.
+    ///
+    /// A reply to a different type of message than the one sent is always returned as an
+    /// `UnexpectedReplyType` error; with resynchronizing on, the next request usually gets its
+    /// own reply again.
+    pub fn set_resynchronize(&mut self, resync: bool) {
+        self.stream.resync = resync;
+    }
+
+    /// Sets how deeply containers may be nested in a reply, such as the one to `get_tree`,
+    /// before it is returned as a `TreeTooDeep` error instead of being parsed. Only containers
+    /// in `nodes` and `floating_nodes` count. The default is 64.
+    ///
+    /// Parsing recurses for every level, using about 8 KiB of stack per level in an optimized
+    /// build and several times that in a debug build. A higher limit may need a thread with a
+    /// larger stack, while threads with a small stack may want a lower one.
+    pub fn set_max_tree_depth(&mut self, depth: usize) {
+        self.stream.max_tree_depth = depth;
+    }
+
+    /// Sets a hook that sees every request and reply on this connection.
+    pub fn set_hook<H: hook::MessageHook + 'static>(&mut self, hook: H) {
+        self.stream.hook.set(hook);
+    }
+
+    #[deprecated(since = "0.8.0", note = "Renamed to run_command")]
+    pub fn command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
+        self.run_command(string)
+    }
+
+    /// The payload of the message is a command for i3 (like the commands you can bind to keys
+    /// in the configuration file) and will be executed directly after receiving it.
+    pub fn run_command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
+        self.stream.send_receive_i3_message(0, string)
+    }
+
+    /// Runs several commands with a single message and returns one outcome per command, in the
+    /// same order as `commands`.
+    ///
+    /// The commands are joined with `;`, so each of them should be a single command. i3 stops at
+    /// the first command it can't parse; the commands after it get an unsuccessful outcome saying
+    /// they weren't run.
+    pub fn run_commands<C: Clone + Into<command::Command>>(
+        &mut self,
+        commands: &[C],
+    ) -> Result<Vec<reply::CommandOutcome>, MessageError> {
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+        let joined = commands
+            .iter()
+            .map(|command| command.clone().into().to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        let mut outcomes = self.run_command(&joined)?.outcomes;
+        outcomes.truncate(commands.len());
+        while outcomes.len() < commands.len() {
+            outcomes.push(reply::CommandOutcome {
+                success: false,
+                parse_error: false,
+                error: Some("not run because an earlier command couldn't be parsed".to_owned()),
+                input: None,
+                errorposition: None,
+            });
+        }
+        Ok(outcomes)
+    }
+
+    /// Loads a layout saved with the `layout` module (or `i3-save-tree`) into the focused
+    /// workspace.
+    pub fn append_layout<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<reply::Command, MessageError> {
+        let path = path.as_ref().to_string_lossy();
+        self.run_command(&format!("append_layout {}", command::quote(&path)))
+    }
+
+    /// Gets the current workspaces.
+    pub fn get_workspaces(&mut self) -> Result<reply::Workspaces, MessageError> {
+        self.stream.send_receive_i3_message(1, "")
+    }
+
+    /// Gets the current outputs.
+    pub fn get_outputs(&mut self) -> Result<reply::Outputs, MessageError> {
+        self.stream.send_receive_i3_message(3, "")
+    }
+
+    /// Runs a single command and returns its outcome.
+    fn run_single(
+        &mut self,
+        command: command::Command,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        Ok(self.run_commands(&[command])?.remove(0))
+    }
+
+    /// Switches to the workspace with the given name, creating it if it doesn't exist.
+    pub fn focus_workspace(&mut self, name: &str) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(command::Command::focus().workspace(name))
+    }
+
+    /// Moves the focused container to the workspace with the given name.
+    pub fn move_focused_to_workspace(
+        &mut self,
+        name: &str,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(command::Command::move_container().to_workspace(name))
+    }
+
+    /// Renames the workspace called `old` to `new`.
+    pub fn rename_workspace(
+        &mut self,
+        old: &str,
+        new: &str,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(command::Command::rename_workspace(old, new))
+    }
+
+    /// Moves the workspace with the given name to an output. i3 can only move the focused
+    /// workspace, so the workspace is focused first. The outcome is that of the first command
+    /// that failed, or of the move.
+    pub fn move_workspace_to_output(
+        &mut self,
+        name: &str,
+        output: &str,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        let mut outcomes = self.run_commands(&[
+            command::Command::focus().workspace(name),
+            command::Command::move_workspace_to_output(output),
+        ])?;
+        let failed = outcomes.iter().position(|outcome| !outcome.success);
+        Ok(outcomes.swap_remove(failed.unwrap_or(1)))
+    }
+
+    /// Shows the scratchpad window matching `criteria`, or hides it if it is already shown on
+    /// the focused workspace. With empty criteria, cycles through the scratchpad windows.
+    pub fn scratchpad_show(
+        &mut self,
+        criteria: &criteria::Criteria,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(criteria.apply(command::Command::scratchpad_show()))
+    }
+
+    /// Moves the containers matching `criteria` to the scratchpad. With empty criteria, moves
+    /// the focused container.
+    pub fn move_to_scratchpad(
+        &mut self,
+        criteria: &criteria::Criteria,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(criteria.apply(command::Command::move_container().to_scratchpad()))
+    }
+
+    /// Moves the floating containers matching `criteria` so their top left corner is at
+    /// (x, y). i3 ignores tiling containers, but still reports success; `get_floating_rect`
+    /// tells where a window actually ended up.
+    pub fn move_floating_to(
+        &mut self,
+        criteria: &criteria::Criteria,
+        x: i32,
+        y: i32,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(criteria.apply(command::Command::move_container().to_position(x, y)))
+    }
+
+    /// Resizes the floating containers matching `criteria` to the given size in pixels. i3
+    /// keeps the size within the configured minimum and maximum floating size.
+    pub fn resize_floating(
+        &mut self,
+        criteria: &criteria::Criteria,
+        width: i32,
+        height: i32,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(criteria.apply(command::Command::resize_set(width, height)))
+    }
+
+    /// Centers the floating containers matching `criteria` on their workspace.
+    pub fn center_floating(
+        &mut self,
+        criteria: &criteria::Criteria,
+    ) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(criteria.apply(command::Command::move_container().to_center()))
+    }
+
+    /// Gets the geometry of the floating container holding the container with the given ID,
+    /// or `None` if there is no such container or it isn't floating. For checking the result
+    /// of `move_floating_to`, `resize_floating` and `center_floating`.
+    pub fn get_floating_rect(&mut self, id: i64) -> Result<Option<reply::Rect>, MessageError> {
+        let tree = self.get_tree()?;
+        let rect = tree::TreeIndex::new(&tree)
+            .floating_con_of(id)
+            .map(|node| node.rect);
+        Ok(rect)
+    }
+
+    /// Runs `cmd` with `sh -c`. i3 passes it to the shell unchanged, whatever quotes,
+    /// semicolons or commas it contains.
+    pub fn exec(&mut self, cmd: &str) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(command::Command::exec(cmd))
+    }
+
+    /// Runs `cmd` like `exec`, without startup notification support.
+    pub fn exec_no_startup_id(&mut self, cmd: &str) -> Result<reply::CommandOutcome, MessageError> {
+        self.run_single(command::Command::exec_no_startup_id(cmd))
+    }
+
+    /// Starts queuing requests to send at once. See the `pipeline` module.
+    pub fn pipeline(&mut self) -> pipeline::Pipeline<'_, S> {
+        pipeline::Pipeline::new(self)
+    }
+
+    /// Gets the layout tree. i3 uses a tree as data structure which includes every container.
+    pub fn get_tree(&mut self) -> Result<reply::Node, MessageError> {
+        self.stream.send_receive_i3_message(4, "")
+    }
+
+    /// Gets the focused window, or `None` if the focus is on an empty workspace or a container
+    /// holding several windows.
+    pub fn get_focused_window(&mut self) -> Result<Option<reply::Node>, MessageError> {
+        let tree = self.get_tree()?;
+        Ok(tree
+            .find_focused()
+            .filter(|node| {
+                (node.nodetype == reply::NodeType::Con
+                    || node.nodetype == reply::NodeType::FloatingCon)
+                    && node.nodes.is_empty()
+                    && node.floating_nodes.is_empty()
+            })
+            .cloned())
+    }
+
+    /// Gets the focused workspace.
+    pub fn get_focused_workspace(&mut self) -> Result<Option<reply::Workspace>, MessageError> {
+        let workspaces = self.get_workspaces()?;
+        Ok(workspaces.focused().cloned())
+    }
+
+    /// Gets the workspace with the given name.
+    pub fn get_workspace(&mut self, name: &str) -> Result<Option<reply::Workspace>, MessageError> {
+        let workspaces = self.get_workspaces()?;
+        Ok(workspaces.by_name(name).cloned())
+    }
+
+    /// Gets the workspace with the given number. If several workspaces share it (such as "2"
+    /// and "2: mail"), the first one i3 lists is returned.
+    pub fn get_workspace_number(
+        &mut self,
+        number: i32,
+    ) -> Result<Option<reply::Workspace>, MessageError> {
+        let workspaces = self.get_workspaces()?;
+        Ok(workspaces
+            .workspaces
+            .into_iter()
+            .find(|workspace| workspace.num == Some(number)))
+    }
+
+    /// Gets the output that the focused workspace is on.
+    pub fn get_focused_output(&mut self) -> Result<Option<reply::Output>, MessageError> {
+        let name = match self.get_focused_workspace()? {
+            Some(workspace) => workspace.output,
+            None => return Ok(None),
+        };
+        Ok(self
+            .get_outputs()?
+            .outputs
+            .into_iter()
+            .find(|output| output.name == name))
+    }
+
+    /// Gets a list of marks (identifiers for containers to easily jump to them later).
+    pub fn get_marks(&mut self) -> Result<reply::Marks, MessageError> {
+        self.stream.send_receive_i3_message(5, "")
+    }
+
+    /// Marks the containers matching `criteria` with `mark`, replacing their other marks. As
+    /// marks are unique, the mark is removed from any other container that had it.
+    pub fn set_mark(
+        &mut self,
+        criteria: &criteria::Criteria,
+        mark: &str,
+    ) -> Result<reply::Command, MessageError> {
+        self.run_command(criteria.apply(command::Command::mark(mark)).as_str())
+    }
+
+    /// Removes `mark` from whichever container has it.
+    pub fn unset_mark(&mut self, mark: &str) -> Result<reply::Command, MessageError> {
+        self.run_command(command::Command::unmark(mark).as_str())
+    }
+
+    /// Adds `mark` to the containers matching `criteria`, or removes it from those that
+    /// already have it, keeping their other marks.
+    pub fn toggle_mark(
+        &mut self,
+        criteria: &criteria::Criteria,
+        mark: &str,
+    ) -> Result<reply::Command, MessageError> {
+        self.run_command(criteria.apply(command::Command::mark_toggle(mark)).as_str())
+    }
+
+    /// Gets every mark together with the container that has it, in the order of `get_marks`.
+    /// A mark whose container went away between the two requests is left out.
+    pub fn get_marked_containers(&mut self) -> Result<Vec<(String, reply::Node)>, MessageError> {
+        let marks = self.get_marks()?.marks;
+        let tree = self.get_tree()?;
+        Ok(marks
+            .into_iter()
+            .filter_map(|mark| {
+                let node = tree.find_by_mark(&mark)?.clone();
+                Some((mark, node))
+            })
+            .collect())
+    }
+
+    /// Swaps the containers with the IDs `a` and `b`, usually two windows, so that each takes
+    /// the other's place in the layout. Fails with `MessageError::NoSuchContainer` without
+    /// sending the command if either ID isn't in the current tree.
+    pub fn swap_windows(&mut self, a: i64, b: i64) -> Result<reply::Command, MessageError> {
+        let tree = self.get_tree()?;
+        if let Some(&missing) = [a, b].iter().find(|id| tree.find_by_id(**id).is_none()) {
+            return Err(MessageError::NoSuchContainer(missing));
+        }
+        let command = criteria::Criteria::new()
+            .con_id(a)
+            .apply(command::Command::swap_with_con_id(b));
+        self.run_command(command.as_str())
+    }
+
+    /// Gets the urgent windows together with the name of the workspace each is on.
+    pub fn get_urgent_windows(&mut self) -> Result<Vec<(String, reply::Node)>, MessageError> {
+        let tree = self.get_tree()?;
+        Ok(tree
+            .urgent_windows()
+            .into_iter()
+            .map(|(workspace, window)| {
+                let name = workspace.name.clone().unwrap_or_default();
+                (name, window.clone())
+            })
+            .collect())
+    }
+
+    /// Gets an array with all configured bar IDs.
+    pub fn get_bar_ids(&mut self) -> Result<reply::BarIds, MessageError> {
+        self.stream.send_receive_i3_message(6, "")
+    }
+
+    /// Gets the configuration of the workspace bar with the given ID.
+    pub fn get_bar_config(&mut self, id: &str) -> Result<reply::BarConfig, MessageError> {
+        self.stream.send_receive_i3_message(6, id)
+    }
+
+    /// Gets the version of i3. The reply will include the major, minor, patch and human-readable
+    /// version.
+    pub fn get_version(&mut self) -> Result<reply::Version, MessageError> {
+        let version: reply::Version = self.stream.send_receive_i3_message(7, "")?;
+        self.capabilities = Some(capabilities::Capabilities::from_version(&version));
+        Ok(version)
+    }
+
+    /// Gets what the connected i3 or sway supports, so version specific messages can be
+    /// avoided at runtime. `connect` asks for the version right away, so this usually doesn't
+    /// need another round trip; on a connection made with `from_stream` or without
+    /// `ConnectionBuilder::capability_probe`, the first call asks for it. Once the capabilities
+    /// are known, requests for messages they don't include return `MessageError::Unsupported`
+    /// without being sent, as older i3 versions never answer messages they don't know.
+    pub fn capabilities(&mut self) -> Result<capabilities::Capabilities, MessageError> {
+        match self.capabilities {
+            Some(capabilities) => Ok(capabilities),
+            None => self
+                .get_version()
+                .map(|version| capabilities::Capabilities::from_version(&version)),
+        }
+    }
+
+    /// Gets the list of currently configured binding modes.
+    pub fn get_binding_modes(&mut self) -> Result<reply::BindingModes, MessageError> {
+        self.require(8)?;
+        self.stream.send_receive_i3_message(8, "")
+    }
+
+    /// Returns the last loaded i3 config.
+    pub fn get_config(&mut self) -> Result<reply::Config, MessageError> {
+        self.require(9)?;
+        self.stream.send_receive_i3_message(9, "")
+    }
+
+    /// Sends a tick event with the specified payload to all clients subscribed to tick events.
+    pub fn send_tick(&mut self, payload: &str) -> Result<reply::Tick, MessageError> {
+        self.require(10)?;
+        self.stream.send_receive_i3_message(10, payload)
+    }
+
+    /// Waits until i3 has processed everything sent over this connection before, for scripts
+    /// that need the effects of their commands to be visible to other clients.
+    ///
+    /// Subscribes `listener` to tick events, sends a tick with a payload unique to this call
+    /// and waits for `listener` to receive it. i3 handles messages in order, so once the tick
+    /// arrives the earlier commands are done. Returns `Ok(false)` if it doesn't arrive within
+    /// `timeout` or i3 refuses the tick. Other events `listener` receives meanwhile are
+    /// dropped.
+    pub fn barrier(
+        &mut self,
+        listener: &mut I3EventListener,
+        timeout: Duration,
+    ) -> Result<bool, MessageError> {
+        static BARRIERS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let payload = format!(
+            "i3ipc-barrier-{}-{}",
+            std::process::id(),
+            BARRIERS.fetch_add(1, Ordering::Relaxed)
+        );
+        listener.subscribe(&[Subscription::Tick])?;
+        if !self.send_tick(&payload)?.success {
+            return Ok(false);
+        }
+        let arrived = listener.wait_for(timeout, |event| match event {
+            event::Event::TickEvent(info) if !info.first && info.payload == payload => Some(()),
+            _ => None,
+        })?;
+        Ok(arrived.is_some())
+    }
+
+    /// Sends an i3 sync event to the given X11 window with the given random value, once i3 has
+    /// processed all X11 events that were pending when the request was sent.
+    ///
+    /// Test harnesses and screenshot tools wait for the corresponding `I3_SYNC` client message on
+    /// `window` to know that i3 has caught up.
+    pub fn sync(&mut self, window: u32, rnd: u32) -> Result<reply::Sync, MessageError> {
+        self.require(11)?;
+        let payload = json::json!({ "window": window, "rnd": rnd }).to_string();
+        self.stream.send_receive_i3_message(11, &payload)
+    }
+
+    /// Gets the currently active binding mode.
+    pub fn get_binding_state(&mut self) -> Result<reply::BindingState, MessageError> {
+        self.require(12)?;
+        self.stream.send_receive_i3_message(12, "")
+    }
+
+    /// Gets the input devices known to sway.
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    pub fn get_inputs(&mut self) -> Result<reply::Inputs, MessageError> {
+        self.require(100)?;
+        self.stream.send_receive_i3_message(100, "")
+    }
+
+    /// Gets the seats of sway, with their focus and input devices.
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    pub fn get_seats(&mut self) -> Result<reply::Seats, MessageError> {
+        self.require(101)?;
+        self.stream.send_receive_i3_message(101, "")
+    }
+
+    /// Refuses a message of the given type if the capabilities are known and don't include it.
+    fn require(&self, message_type: u32) -> Result<(), MessageError> {
+        match self.capabilities {
+            Some(ref capabilities) if !capabilities.supports_message(message_type) => {
+                Err(MessageError::Unsupported(message_type))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S: AsRawFd> AsRawFd for I3Connection<S> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.socket.as_raw_fd()
+    }
+}
+
+/// Registers the socket of the connection with a `mio::Poll`, for example to notice that i3
+/// went away while idle. Requests still block until their reply arrives.
+#[cfg(feature = "mio")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "mio")))]
+impl<S: AsRawFd> mio::event::Source for I3Connection<S> {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::autotiling;
+    use crate::capabilities;
+    use crate::command::Command;
+    use crate::common;
+    use crate::criteria::Criteria;
+    use crate::event;
+    use crate::i3bar;
+    use crate::layout::{Layout, Swallows};
+    use crate::reply;
+    use crate::testing::MockI3;
+    use crate::tracker::{
+        FocusHistory, OutputTracker, OutputUpdate, PreviousWorkspace, TreeTracker, WindowFilter,
+        WorkspaceTracker,
+    };
+    use crate::tree::TreeIndex;
+    use crate::I3Connection;
+    use crate::I3EventListener;
+    use crate::MessageError;
+    use crate::Subscription;
+    use std::io::{self, Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    /// The fields sway adds to outputs, which i3's replies lack, for insertion into output
+    /// fixtures in place of `SWAY`.
+    const SWAY_OUTPUT_FIELDS: &str = if cfg!(feature = "sway-1-1") {
+        r#","make":"","model":"","serial":"","dpms":true,"modes":[]"#
+    } else {
+        ""
+    };
+
+    /// A small tree: root > output "eDP-1" > workspace "1" > horizontal split holding a
+    /// firefox (marked "web") and a terminal, plus a floating terminal on the workspace.
+    const TEST_TREE: &str = r##"
+    {
+        "id": 1, "name": "root", "type": "root", "border": "normal",
+        "current_border_width": 0, "layout": "splith", "percent": null,
+        "rect": { "x": 0, "y": 0, "width": 1600, "height": 1200 },
+        "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+        "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+        "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+        "window": null, "urgent": false, "focused": false, "focus": [2],
+        "nodes": [{
+            "id": 2, "name": "eDP-1", "type": "output", "border": "normal",
+            "current_border_width": 0, "layout": "output", "percent": 1.0,
+            "rect": { "x": 0, "y": 0, "width": 1600, "height": 1200 },
+            "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "window": null, "urgent": false, "focused": false, "focus": [3],
+            "nodes": [{
+                "id": 3, "name": "1", "type": "workspace", "border": "normal",
+                "current_border_width": 0, "layout": "splith", "percent": null,
+                "rect": { "x": 0, "y": 0, "width": 1600, "height": 1200 },
+                "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "window": null, "urgent": false, "focused": false, "focus": [4, 7],
+                "nodes": [{
+                    "id": 4, "name": null, "type": "con", "border": "normal",
+                    "current_border_width": 2, "layout": "splith", "percent": 1.0,
+                    "rect": { "x": 0, "y": 0, "width": 1600, "height": 1200 },
+                    "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                    "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                    "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                    "window": null, "urgent": false, "focused": false, "focus": [6, 5],
+                    "nodes": [{
+                        "id": 5, "name": "Mozilla Firefox", "type": "con", "border": "normal",
+                        "current_border_width": 2, "layout": "splith", "percent": 0.5,
+                        "rect": { "x": 0, "y": 0, "width": 800, "height": 1200 },
+                        "window_rect": { "x": 2, "y": 0, "width": 796, "height": 1198 },
+                        "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                        "geometry": { "x": 0, "y": 0, "width": 1280, "height": 720 },
+                        "window": 4194307, "urgent": false, "focused": false,
+                        "window_properties": { "class": "firefox", "instance": "Navigator",
+                                               "title": "Mozilla Firefox" },
+                        "marks": ["web"]
+                    }, {
+                        "id": 6, "name": "~", "type": "con", "border": "normal",
+                        "current_border_width": 2, "layout": "splith", "percent": 0.5,
+                        "rect": { "x": 800, "y": 0, "width": 800, "height": 1200 },
+                        "window_rect": { "x": 2, "y": 0, "width": 796, "height": 1198 },
+                        "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                        "geometry": { "x": 0, "y": 0, "width": 640, "height": 480 },
+                        "window": 6291459, "urgent": false, "focused": true,
+                        "window_properties": { "class": "URxvt", "instance": "urxvt",
+                                               "title": "~" }
+                    }]
+                }],
+                "floating_nodes": [{
+                    "id": 7, "name": null, "type": "floating_con", "border": "normal",
+                    "current_border_width": 2, "layout": "splith", "percent": null,
+                    "rect": { "x": 400, "y": 300, "width": 640, "height": 480 },
+                    "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                    "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                    "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                    "window": null, "urgent": false, "focused": false, "focus": [8],
+                    "nodes": [{
+                        "id": 8, "name": "htop", "type": "con", "border": "normal",
+                        "current_border_width": 2, "layout": "splith", "percent": 1.0,
+                        "rect": { "x": 400, "y": 300, "width": 640, "height": 480 },
+                        "window_rect": { "x": 2, "y": 0, "width": 636, "height": 478 },
+                        "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                        "geometry": { "x": 0, "y": 0, "width": 640, "height": 480 },
+                        "window": 8388611, "urgent": false, "focused": false,
+                        "window_properties": { "class": "URxvt", "instance": "urxvt",
+                                               "title": "htop" }
+                    }]
+                }]
+            }]
+        }]
+    }"##;
+
+    // for the following tests send a request and get the reponse.
+    // response types are specific so often getting them at all indicates success.
+    // can't do much better without mocking an i3 installation.
+
+    #[test]
+    fn connect() {
+        I3Connection::connect().unwrap();
+    }
+
+    #[test]
+    fn run_command_nothing() {
+        let mut connection = I3Connection::connect().unwrap();
+        let result = connection.run_command("").unwrap();
+        assert_eq!(result.outcomes.len(), 0);
+    }
+
+    #[test]
+    fn run_command_single_sucess() {
+        let mut connection = I3Connection::connect().unwrap();
+        let a = connection.run_command("exec /bin/true").unwrap();
+        assert_eq!(a.outcomes.len(), 1);
+        assert!(a.outcomes[0].success);
+    }
+
+    #[test]
+    fn run_command_multiple_success() {
+        let mut connection = I3Connection::connect().unwrap();
+        let result = connection
+            .run_command("exec /bin/true; exec /bin/true")
+            .unwrap();
+        assert_eq!(result.outcomes.len(), 2);
+        assert!(result.outcomes[0].success);
+        assert!(result.outcomes[1].success);
+    }
+
+    #[test]
+    fn run_command_fail() {
+        let mut connection = I3Connection::connect().unwrap();
+        let result = connection.run_command("ThisIsClearlyNotACommand").unwrap();
+        assert_eq!(result.outcomes.len(), 1);
+        assert!(!result.outcomes[0].success);
+    }
+
+    #[test]
+    fn get_workspaces() {
+        I3Connection::connect().unwrap().get_workspaces().unwrap();
+    }
+
+    #[test]
+    fn get_outputs() {
+        I3Connection::connect().unwrap().get_outputs().unwrap();
+    }
+
+    #[test]
+    fn get_tree() {
+        I3Connection::connect().unwrap().get_tree().unwrap();
+    }
+
+    #[test]
+    fn get_marks() {
+        I3Connection::connect().unwrap().get_marks().unwrap();
+    }
+
+    #[test]
+    fn get_bar_ids() {
+        I3Connection::connect().unwrap().get_bar_ids().unwrap();
+    }
+
+    #[test]
+    fn get_bar_ids_and_one_config() {
+        let mut connection = I3Connection::connect().unwrap();
+        let ids = connection.get_bar_ids().unwrap().ids;
+        connection.get_bar_config(&ids[0]).unwrap();
+    }
+
+    #[test]
+    fn get_version() {
+        I3Connection::connect().unwrap().get_version().unwrap();
+    }
+
+    #[test]
+    fn get_binding_modes() {
+        I3Connection::connect()
+            .unwrap()
+            .get_binding_modes()
+            .unwrap();
+    }
+
+    #[test]
+    fn get_config() {
+        I3Connection::connect().unwrap().get_config().unwrap();
+    }
+
+    #[test]
+    fn get_binding_state() {
+        I3Connection::connect()
+            .unwrap()
+            .get_binding_state()
+            .unwrap();
+    }
+
+    #[test]
+    fn send_tick() {
+        let result = I3Connection::connect().unwrap().send_tick("ping").unwrap();
+        assert!(result.success);
+    }
+
+    #[test]
+    fn event_subscribe() {
+        let s = I3EventListener::connect()
+            .unwrap()
+            .subscribe(&[Subscription::Workspace])
+            .unwrap();
+        assert!(s.success);
+    }
+
+    #[test]
+    fn deserialize_command() {
+        let json_str = r##"[{ "success": true }, { "success": false, "error": "oops" }]"##;
+        let command: reply::Command = serde_json::from_str(json_str).unwrap();
+        assert_eq!(command.outcomes.len(), 2);
+        assert!(command.outcomes[0].success);
+        assert_eq!(command.outcomes[1].error, Some("oops".to_owned()));
+    }
+
+    #[test]
+    fn deserialize_tree_unknown_layout() {
+        let json_str = r##"
+        {
+            "id": 1,
+            "name": "root",
+            "type": "root",
+            "border": "normal",
+            "current_border_width": 0,
+            "layout": "some_future_layout",
+            "percent": null,
+            "rect": { "x": 0, "y": 0, "width": 1600, "height": 1200 },
+            "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "window": null,
+            "urgent": false,
+            "focused": false
+        }"##;
+        let node: reply::Node = serde_json::from_str(json_str).unwrap();
+        assert_eq!(node.nodetype, reply::NodeType::Root);
+        assert_eq!(
+            node.layout,
+            reply::NodeLayout::Unknown("some_future_layout".to_owned())
+        );
+        assert_eq!(
+            node.rect,
+            reply::Rect {
+                x: 0,
+                y: 0,
+                width: 1600,
+                height: 1200
+            }
+        );
+        assert!(node.rect.contains(1599, 0) && !node.rect.contains(1600, 0));
+        assert!(node.nodes.is_empty());
+    }
+
+    #[test]
+    fn build_event_invalid_field() {
+        let json_str = r##"{ "change": "new", "container": { "name": "no id here" } }"##;
+        match common::build_event(3, json_str) {
+            Err(MessageError::InvalidField { field, .. }) => assert_eq!(field, "container.id"),
+            other => panic!("expected InvalidField, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn build_event_unknown() {
+        match common::build_event(42, "{}") {
+            Err(MessageError::UnknownEvent(42)) => {}
+            other => panic!("expected UnknownEvent, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_str_workspace() {
+        let json_str = r##"
+        {
+            "change": "focus",
+            "current": {
+                "id": 28489712,
+                "name": "something",
+                "type": "workspace",
+                "border": "normal",
+                "current_border_width": 2,
+                "layout": "splith",
+                "orientation": "none",
+                "percent": 30.0,
+                "rect": { "x": 1600, "y": 0, "width": 1600, "height": 1200 },
+                "window_rect": { "x": 2, "y": 0, "width": 632, "height": 366 },
+                "deco_rect": { "x": 1, "y": 1, "width": 631, "height": 365 },
+                "geometry": { "x": 6, "y": 6, "width": 10, "height": 10 },
+                "window": 1,
+                "urgent": false,
+                "focused": true
+            },
+            "old": null
+        }"##;
+        event::WorkspaceEventInfo::from_str(json_str).unwrap();
+    }
+
+    #[test]
+    fn from_str_output() {
+        let json_str = r##"{ "change": "unspecified" }"##;
+        let info = event::OutputEventInfo::from_str(json_str).unwrap();
+        assert!(info.output.is_none());
+    }
+
+    #[test]
+    fn from_str_output_with_output() {
+        let json_str = r##"{ "change": "unspecified", "output": {
+            "name": "HDMI-1", "active": true, "primary": false, "current_workspace": "3",
+            "rect": { "x": 1920, "y": 0, "width": 2560, "height": 1440 }SWAY } }"##
+            .replace("SWAY", SWAY_OUTPUT_FIELDS);
+        let info = event::OutputEventInfo::from_str(&json_str).unwrap();
+        let output = info.output.unwrap();
+        assert_eq!(output.name, "HDMI-1");
+        assert_eq!(output.current_workspace.as_deref(), Some("3"));
+        assert_eq!(output.rect.width, 2560);
+    }
+
+    #[test]
+    fn from_str_mode() {
+        let json_str = r##"{ "change": "default" }"##;
+        event::ModeEventInfo::from_str(json_str).unwrap();
+    }
+
+    #[test]
+    fn from_str_window() {
+        let json_str = r##"
+        {
+            "change": "new",
+            "container": {
+                "id": 28489712,
+                "name": "something",
+                "type": "workspace",
+                "border": "normal",
+                "current_border_width": 2,
+                "layout": "splith",
+                "orientation": "none",
+                "percent": 30.0,
+                "rect": { "x": 1600, "y": 0, "width": 1600, "height": 1200 },
+                "window_rect": { "x": 2, "y": 0, "width": 632, "height": 366 },
+                "deco_rect": { "x": 1, "y": 1, "width": 631, "height": 365 },
+                "geometry": { "x": 6, "y": 6, "width": 10, "height": 10 },
+                "window": 1,
+                "window_properties": { "class": "Firefox", "instance": "Navigator", "window_role": "browser", "title": "github.com - Mozilla Firefox", "transient_for": null },
+                "urgent": false,
+                "focused": true
+            }
+        }"##;
+        event::WindowEventInfo::from_str(json_str).unwrap();
+    }
+
+    #[cfg(feature = "sway")]
+    #[test]
+    fn from_str_window_sway() {
+        let json_str = r##"
+        {
+            "change": "focus",
+            "container": {
+                "id": 12,
+                "name": "foot",
+                "type": "con",
+                "border": "pixel",
+                "current_border_width": 2,
+                "layout": "none",
+                "orientation": "none",
+                "percent": 1.0,
+                "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 },
+                "window_rect": { "x": 2, "y": 2, "width": 1916, "height": 1076 },
+                "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "geometry": { "x": 0, "y": 0, "width": 800, "height": 600 },
+                "window": null,
+                "urgent": false,
+                "focused": true,
+                "app_id": "foot",
+                "pid": 4242,
+                "shell": "xdg_shell",
+                "visible": true,
+                "inhibit_idle": false,
+                "idle_inhibitors": { "user": "none", "application": "none" }
+            }
+        }"##;
+        let info = event::WindowEventInfo::from_str(json_str).unwrap();
+        assert_eq!(info.container.app_id, Some("foot".to_owned()));
+        assert_eq!(info.container.pid, Some(4242));
+        assert_eq!(info.container.idle_inhibitors.unwrap().user, "none");
+    }
+
+    #[test]
+    fn from_str_barconfig() {
+        let json_str = r##"
+        {
+            "id": "bar-bxuqzf",
+            "mode": "dock",
+            "position": "bottom",
+            "status_command": "i3status",
+            "font": "-misc-fixed-medium-r-normal--13-120-75-75-C-70-iso10646-1",
+            "workspace_buttons": true,
+            "binding_mode_indicator": true,
+            "verbose": false,
+            "colors": {
+                    "background": "#c0c0c0",
+                    "statusline": "#00ff00",
+                    "focused_workspace_text": "#ffffff",
+                    "focused_workspace_bg": "#000000"
+            },
+            "tray_outputs": ["eDP-1"],
+            "tray_padding": 2,
+            "separator_symbol": " | ",
+            "workspace_min_width": 40,
+            "hidden_state": "hide",
+            "modifier": "Mod4",
+            "bindings": [
+                { "input_code": 4, "command": "workspace prev", "release": false },
+                { "input_code": 5, "command": "workspace next", "release": true }
+            ]
+        }"##;
+        let bar_config = event::BarConfigEventInfo::from_str(json_str)
+            .unwrap()
+            .bar_config;
+        assert_eq!(bar_config.tray_outputs, ["eDP-1"]);
+        assert_eq!(bar_config.tray_padding, Some(2));
+        assert_eq!(bar_config.separator_symbol.as_deref(), Some(" | "));
+        assert_eq!(bar_config.workspace_min_width, Some(40));
+        assert_eq!(bar_config.hidden_state.as_deref(), Some("hide"));
+        assert_eq!(bar_config.modifier.as_deref(), Some("Mod4"));
+        assert_eq!(bar_config.bindings[1].command, "workspace next");
+        assert!(bar_config.bindings[1].release);
+        let colors = bar_config.colors;
+        assert_eq!(
+            colors.background,
+            Some(reply::Color {
+                red: 0xc0,
+                green: 0xc0,
+                blue: 0xc0,
+                alpha: 255
+            })
+        );
+        assert_eq!(
+            colors.get(&reply::ColorableBarPart::Statusline),
+            "#00ff00".parse().ok()
+        );
+        assert_eq!(colors.separator, None);
+        let json_str = r##"{ "background": "#c0c0c0", "tray_background": "#00000080" }"##;
+        let colors = serde_json::from_str::<reply::BarColors>(json_str);
+        #[cfg(feature = "deny-unknown-fields")]
+        assert!(colors
+            .unwrap_err()
+            .to_string()
+            .contains("unknown field `tray_background`"));
+        #[cfg(not(feature = "deny-unknown-fields"))]
+        {
+            let colors = colors.unwrap();
+            assert_eq!(colors.other["tray_background"], "#00000080");
+            let translucent: reply::Color = colors.other["tray_background"].parse().unwrap();
+            assert_eq!(translucent.alpha, 0x80);
+        }
+        let translucent: reply::Color = "#00000080".parse().unwrap();
+        assert_eq!(translucent.alpha, 0x80);
+        assert_eq!(translucent.to_string(), "#00000080");
+        assert!("#00ff0".parse::<reply::Color>().is_err());
+        assert!("00ff00".parse::<reply::Color>().is_err());
+    }
+
+    #[test]
+    fn from_str_tick() {
+        let json_str = r##"{ "first": false, "payload": "ping" }"##;
+        let info = event::TickEventInfo::from_str(json_str).unwrap();
+        assert!(!info.first);
+        assert_eq!(info.payload, "ping");
+    }
+
+    #[test]
+    fn from_str_binding_event() {
+        let json_str = r##"
+        {
+            "change": "run",
+            "binding": {
+                "command": "nop",
+                "event_state_mask": [
+                    "shift",
+                    "ctrl"
+                ],
+                "input_code": 0,
+                "symbol": "t",
+                "input_type": "keyboard"
+            }
+        }"##;
+        event::BindingEventInfo::from_str(json_str).unwrap();
+    }
+
+    #[test]
+    fn try_next_partial_event() {
+        let (stream, mut i3) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(stream);
+        listener.set_nonblocking(true).unwrap();
+        assert!(listener.try_next().unwrap().is_none());
+
+        let message = crate::encode_message(0x8000_0002, r#"{"change":"resize"}"#).unwrap();
+        i3.write_all(&message[..10]).unwrap();
+        assert!(listener.try_next().unwrap().is_none());
+        i3.write_all(&message[10..]).unwrap();
+        match listener.try_next().unwrap() {
+            Some(event::Event::ModeEvent(e)) => assert_eq!(e.change, "resize"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(listener.try_next().unwrap().is_none());
+    }
+
+    #[test]
+    fn timeout_discards_late_reply() {
+        let (stream, mut i3) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(stream);
+        connection
+            .set_timeout(std::time::Duration::from_millis(10))
+            .unwrap();
+        match connection.get_marks() {
+            Err(e @ MessageError::Receive { .. }) => {
+                assert_eq!(e.message_type(), Some(5));
+                assert_eq!(
+                    e.to_string(),
+                    "Network error while receiving the reply to message type 5 from i3"
+                );
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        i3.write_all(&crate::encode_message(5, r#"["late"]"#).unwrap())
+            .unwrap();
+        i3.write_all(&crate::encode_message(5, r#"["fresh"]"#).unwrap())
+            .unwrap();
+        assert_eq!(connection.get_marks().unwrap().marks, vec!["fresh"]);
+    }
+
+    #[test]
+    fn subscribe_again_keeps_events() {
+        let (stream, mut i3) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(stream);
+        i3.write_all(&crate::encode_message(0x8000_0002, r#"{"change":"resize"}"#).unwrap())
+            .unwrap();
+        i3.write_all(&crate::encode_message(2, r#"{"success":true}"#).unwrap())
+            .unwrap();
+        assert!(listener.subscribe(&[Subscription::Window]).unwrap().success);
+
+        listener.unsubscribe(&[Subscription::Window]);
+        i3.write_all(&crate::encode_message(0x8000_0003, r#"{"change":"focus"}"#).unwrap())
+            .unwrap();
+        i3.write_all(&crate::encode_message(0x8000_0002, r#"{"change":"default"}"#).unwrap())
+            .unwrap();
+        let modes: Vec<String> = listener
+            .listen()
+            .take(2)
+            .map(|event| match event.unwrap() {
+                event::Event::ModeEvent(e) => e.change,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(modes, vec!["resize", "default"]);
+    }
+
+    #[test]
+    fn command_builder() {
+        assert_eq!(
+            Command::focus().workspace("3: web").as_str(),
+            r#"workspace "3: web""#
+        );
+        assert_eq!(
+            Command::move_container().to_workspace_number(5).as_str(),
+            "move container to workspace number 5"
+        );
+        assert_eq!(
+            Command::exec(r#"notify-send "a\b""#).as_str(),
+            r#"exec "notify-send \"a\\b\"""#
+        );
+    }
+
+    #[test]
+    fn criteria_escaping() {
+        let criteria = Criteria::new().title(r#"say "hi" \o/"#).floating();
+        assert_eq!(
+            criteria.to_string(),
+            r#"[title="say \"hi\" \\o/" floating]"#
+        );
+        assert_eq!(Criteria::new().apply("kill").as_str(), "kill");
+    }
+
+    #[test]
+    fn run_commands_pads_outcomes() {
+        let (stream, mut i3) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(stream);
+        let reply = r#"[{"success":true},{"success":false,"parse_error":true,"error":"Expected one of these tokens","input":"kill; bogus; kill","errorposition":"      ^^^^^"}]"#;
+        i3.write_all(&crate::encode_message(0, reply).unwrap())
+            .unwrap();
+        let outcomes = connection
+            .run_commands(&[Command::kill(), Command::raw("bogus"), Command::kill()])
+            .unwrap();
+        assert_eq!(outcomes.len(), 3);
+        assert!(outcomes[0].success);
+        assert!(outcomes[1].parse_error);
+        assert!(!outcomes[2].success && !outcomes[2].parse_error);
+    }
+
+    #[test]
+    fn node_find() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert_eq!(
+            tree.find_by_id(7).unwrap().nodetype,
+            reply::NodeType::FloatingCon
+        );
+        assert_eq!(tree.find_by_window(6291459).unwrap().id, 6);
+        assert_eq!(tree.find_by_class("URxvt").unwrap().id, 6);
+        assert_eq!(tree.find_by_mark("web").unwrap().id, 5);
+        let htop = tree.find(|n| n.name.as_deref() == Some("htop")).unwrap();
+        assert_eq!(
+            htop.window_property(reply::WindowProperty::Title),
+            Some("htop")
+        );
+        assert!(tree.find_by_id(42).is_none());
+    }
+
+    #[test]
+    fn focused_window() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert_eq!(tree.find_focused().unwrap().id, 6);
+
+        let (stream, mut i3) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(stream);
+        i3.write_all(&crate::encode_message(4, TEST_TREE).unwrap())
+            .unwrap();
+        let window = connection.get_focused_window().unwrap().unwrap();
+        assert_eq!(window.name.as_deref(), Some("~"));
+    }
+
+    #[test]
+    fn tree_index() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let index = TreeIndex::new(&tree);
+        assert_eq!(index.parent_of(5).unwrap().id, 4);
+        assert!(index.parent_of(1).is_none());
+        let ancestors: Vec<i64> = index.ancestors(8).map(|n| n.id).collect();
+        assert_eq!(ancestors, vec![7, 3, 2, 1]);
+        assert_eq!(index.workspace_of(8).unwrap().name.as_deref(), Some("1"));
+        assert_eq!(index.workspace_of(3).unwrap().id, 3);
+        assert_eq!(index.output_of(6).unwrap().name.as_deref(), Some("eDP-1"));
+        assert!(index.output_of(1).is_none());
+        assert_eq!(index.floating_con_of(8).unwrap().id, 7);
+        assert_eq!(index.floating_con_of(7).unwrap().id, 7);
+        assert!(index.floating_con_of(6).is_none());
+    }
+
+    #[test]
+    fn rect_intersection() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let firefox = tree.find_by_id(5).unwrap().rect;
+        let htop = tree.find_by_id(8).unwrap().rect;
+        assert!(firefox.intersects(&htop));
+        assert_eq!(
+            firefox.intersection(&htop),
+            Some(reply::Rect {
+                x: 400,
+                y: 300,
+                width: 400,
+                height: 480
+            })
+        );
+        let terminal = tree.find_by_id(6).unwrap().rect;
+        assert!(!firefox.intersects(&terminal));
+
+        let huge = reply::Rect {
+            x: i32::MAX - 10,
+            y: i32::MAX - 10,
+            width: i32::MAX,
+            height: i32::MAX,
+        };
+        assert!(huge.contains(i32::MAX, i32::MAX));
+        assert!(!huge.contains(0, 0));
+        assert!(huge.intersects(&huge));
+        assert_eq!(huge.intersection(&huge), Some(huge));
+        assert!(!huge.intersects(&firefox));
+        assert_eq!(huge.center(), (i32::MAX, i32::MAX));
+    }
+
+    #[test]
+    fn serialize_tree_round_trip() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let json = serde_json::to_value(&tree).unwrap();
+        assert_eq!(json["nodes"][0]["type"], "output");
+        assert_eq!(json["nodes"][0]["rect"]["width"], 1600);
+        let firefox = &json["nodes"][0]["nodes"][0]["nodes"][0]["nodes"][0];
+        assert_eq!(firefox["window_properties"]["class"], "firefox");
+
+        let again: reply::Node = serde_json::from_value(json).unwrap();
+        let htop = again.find_by_id(8).unwrap();
+        assert_eq!(
+            htop.window_property(reply::WindowProperty::Instance),
+            Some("urxvt")
+        );
+        assert_eq!(htop.rect, tree.find_by_id(8).unwrap().rect);
+    }
+
+    #[test]
+    fn serialize_unknown_enum() {
+        let layout = reply::NodeLayout::Unknown("some_future_layout".to_owned());
+        assert_eq!(
+            serde_json::to_string(&layout).unwrap(),
+            r#""some_future_layout""#
+        );
+        assert_eq!(
+            serde_json::to_string(&reply::NodeBorder::Pixel).unwrap(),
+            r#""pixel""#
+        );
+    }
+
+    #[test]
+    fn layout_from_workspace() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let layout = Layout::from_workspace(tree.find_by_id(3).unwrap());
+        let text = layout.to_string();
+        let containers: Vec<serde_json::Value> = serde_json::Deserializer::from_str(&text)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers[0]["layout"], "splith");
+        let firefox = &containers[0]["nodes"][0];
+        assert_eq!(firefox["swallows"][0]["class"], "^firefox$");
+        assert_eq!(firefox["marks"][0], "web");
+        assert_eq!(containers[1]["type"], "floating_con");
+        assert_eq!(
+            containers[1]["nodes"][0]["swallows"][0]["instance"],
+            "^urxvt$"
+        );
+    }
+
+    #[test]
+    fn i3bar_status_lines() {
+        let header = i3bar::Header {
+            click_events: true,
+            ..i3bar::Header::default()
+        };
+        let mut writer = i3bar::StatusWriter::new(Vec::new(), &header).unwrap();
+        let mut block = i3bar::Block::new("<b>12:00</b>");
+        block.markup = Some(i3bar::Markup::Pango);
+        writer.write_blocks(&[block]).unwrap();
+        writer.write_blocks(&[i3bar::Block::new("12:01")]).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(
+            out,
+            "{\"version\":1,\"click_events\":true}\n[\n\
+             [{\"full_text\":\"<b>12:00</b>\",\"markup\":\"pango\"}]\n\
+             ,[{\"full_text\":\"12:01\"}]\n"
+        );
+    }
+
+    #[test]
+    fn i3bar_click_events() {
+        let input = "[\n{\"name\":\"clock\",\"button\":1,\"x\":1800,\"y\":10}\n\
+                     ,{\"name\":\"vol\",\"instance\":\"master\",\"button\":4,\"modifiers\":[\"Shift\"],\"x\":1700,\"y\":10}\n";
+        let events: Vec<i3bar::ClickEvent> = i3bar::ClickEventReader::new(input.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name.as_deref(), Some("clock"));
+        assert_eq!(events[1].button, 4);
+        assert_eq!(events[1].modifiers, vec!["Shift"]);
+    }
+
+    #[test]
+    fn socket_error_names_source() {
+        let error = crate::socket_error(
+            "/run/user/1000/i3/ipc-socket.42".to_owned(),
+            crate::SocketSource::EnvVar("I3SOCK"),
+            std::io::Error::from(std::io::ErrorKind::ConnectionRefused),
+        );
+        assert_eq!(error.socket_path(), Some("/run/user/1000/i3/ipc-socket.42"));
+        match error {
+            crate::EstablishError::SocketError(e) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::ConnectionRefused);
+                assert_eq!(
+                    e.to_string(),
+                    "couldn't connect to /run/user/1000/i3/ipc-socket.42 (found via $I3SOCK)"
+                );
+                assert!(std::error::Error::source(e.get_ref().unwrap()).is_some());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    /// A stream that replays canned bytes and records what was written to it.
+    struct ScriptedStream {
+        input: std::io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl std::io::Read for ScriptedStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl std::io::Write for ScriptedStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn connection_from_stream() {
+        let stream = ScriptedStream {
+            input: std::io::Cursor::new(crate::encode_message(5, r#"["a","b"]"#).unwrap()),
+            output: Vec::new(),
+        };
+        let mut connection = I3Connection::from_stream(stream);
+        assert_eq!(connection.get_marks().unwrap().marks, vec!["a", "b"]);
+        assert_eq!(
+            connection.stream.socket.output,
+            crate::encode_message(5, "").unwrap()
+        );
+        match connection.get_marks() {
+            Err(MessageError::Receive { error: e, .. }) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn mock_i3() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(0, r#"[{"success":true}]"#);
+        let mut connection = i3.connect().unwrap();
+        assert!(connection.run_command("nop").unwrap().outcomes[0].success);
+
+        let mut listener = i3.connect_listener().unwrap();
+        assert!(listener.subscribe(&[Subscription::Mode]).unwrap().success);
+        i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+        match listener.listen().next().unwrap().unwrap() {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "resize"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            i3.requests(),
+            vec![(0, "nop".to_owned()), (2, r#"[ "mode" ]"#.to_owned())]
+        );
+    }
+
+    #[test]
+    fn deserialize_window_properties() {
+        let json_str = r##"{
+            "class": "Gimp", "instance": "gimp", "title": "Export Image",
+            "window_role": "gimp-file-export", "transient_for": 2097154,
+            "machine": "laptop"
+        }"##;
+        let properties: reply::WindowProperties = serde_json::from_str(json_str).unwrap();
+        assert_eq!(properties.class.as_deref(), Some("Gimp"));
+        assert_eq!(properties.transient_for, Some(2097154));
+        assert_eq!(properties.machine.as_deref(), Some("laptop"));
+        assert_eq!(
+            properties.get(reply::WindowProperty::WindowRole),
+            Some("gimp-file-export")
+        );
+        assert!(properties.other.is_empty());
+
+        let json_str = r##"{ "class": "Gimp", "some_future_property": [1, 2] }"##;
+        let properties = serde_json::from_str::<reply::WindowProperties>(json_str);
+        #[cfg(feature = "deny-unknown-fields")]
+        assert!(properties
+            .unwrap_err()
+            .to_string()
+            .contains("unknown field `some_future_property`"));
+        #[cfg(not(feature = "deny-unknown-fields"))]
+        assert_eq!(
+            properties.unwrap().other["some_future_property"],
+            serde_json::json!([1, 2])
+        );
+    }
+
+    #[cfg(feature = "gaps")]
+    #[test]
+    fn deserialize_gaps() {
+        let mut json: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        json["nodes"][0]["nodes"][0]["gaps"] = serde_json::json!({
+            "inner": 10, "outer": 0, "top": 5, "right": 0, "bottom": 5, "left": 0
+        });
+        let tree: reply::Node = serde_json::from_value(json).unwrap();
+        let gaps = tree.find_by_id(3).unwrap().gaps.unwrap();
+        assert_eq!((gaps.inner, gaps.top, gaps.bottom), (10, 5, 5));
+        assert!(tree.find_by_id(5).unwrap().gaps.is_none());
+    }
+
+    #[cfg(feature = "sway")]
+    #[test]
+    fn deserialize_inputs() {
+        let json_str = r##"[
+            {
+                "identifier": "1:1:AT_Translated_Set_2_keyboard",
+                "name": "AT Translated Set 2 keyboard",
+                "vendor": 1, "product": 1, "type": "keyboard",
+                "xkb_active_layout_name": "English (US)",
+                "xkb_layout_names": ["English (US)", "German"],
+                "xkb_active_layout_index": 0,
+                "libinput": { "send_events": "enabled" }
+            },
+            {
+                "identifier": "1267:12377:ELAN1300:00_04F3:3059_Touchpad",
+                "name": "ELAN1300:00 04F3:3059 Touchpad",
+                "vendor": 1267, "product": 12377, "type": "touchpad_of_the_future",
+                "scroll_factor": 1.0,
+                "libinput": { "tap": "enabled", "accel_speed": 0.25, "scroll_button": 274 }
+            }
+        ]"##;
+        let inputs: reply::Inputs = serde_json::from_str(json_str).unwrap();
+        let keyboard = &inputs.inputs[0];
+        assert_eq!(keyboard.input_type, reply::InputDeviceType::Keyboard);
+        assert_eq!(keyboard.xkb_layout_names.len(), 2);
+        let touchpad = &inputs.inputs[1];
+        assert_eq!(
+            touchpad.input_type,
+            reply::InputDeviceType::Unknown("touchpad_of_the_future".to_owned())
+        );
+        let libinput = touchpad.libinput.as_ref().unwrap();
+        assert_eq!(libinput.accel_speed, Some(0.25));
+        assert_eq!(libinput.scroll_button, Some(274));
+        assert!(libinput.dwt.is_none());
+    }
+
+    #[cfg(feature = "sway")]
+    #[test]
+    fn deserialize_seats() {
+        let json_str = r##"[
+            {
+                "name": "seat0",
+                "capabilities": 3,
+                "focus": 7,
+                "devices": [
+                    {
+                        "identifier": "1:1:AT_Translated_Set_2_keyboard",
+                        "name": "AT Translated Set 2 keyboard",
+                        "vendor": 1, "product": 1, "type": "keyboard"
+                    }
+                ]
+            }
+        ]"##;
+        let seats: reply::Seats = serde_json::from_str(json_str).unwrap();
+        let seat = &seats.seats[0];
+        assert_eq!(seat.name, "seat0");
+        assert_eq!(seat.focus, 7);
+        assert!(seat.has_pointer() && seat.has_keyboard() && !seat.has_touch());
+        assert_eq!(seat.devices[0].input_type, reply::InputDeviceType::Keyboard);
+    }
+
+    #[cfg(feature = "sway")]
+    #[test]
+    fn input_event() {
+        assert_eq!(
+            common::build_subscribe_payload(&[Subscription::Input]),
+            r#"[ "input" ]"#
+        );
+        let json_str = r##"{
+            "change": "xkb_layout",
+            "input": {
+                "identifier": "1:1:AT_Translated_Set_2_keyboard",
+                "name": "AT Translated Set 2 keyboard",
+                "vendor": 1, "product": 1, "type": "keyboard",
+                "xkb_active_layout_name": "German",
+                "xkb_layout_names": ["English (US)", "German"],
+                "xkb_active_layout_index": 1
+            }
+        }"##;
+        match common::build_event(21, json_str).unwrap() {
+            event::Event::InputEvent(info) => {
+                assert_eq!(info.change, event::inner::InputChange::XkbLayout);
+                assert_eq!(info.input.xkb_active_layout_name.unwrap(), "German");
+            }
+            other => panic!("expected an input event, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "sway")]
+    #[test]
+    fn bar_state_update_event() {
+        assert_eq!(
+            common::build_subscribe_payload(&[Subscription::BarStateUpdate]),
+            r#"[ "bar_state_update" ]"#
+        );
+        let json_str = r#"{"id":"bar-0","visible_by_modifier":true}"#;
+        match common::build_event(20, json_str).unwrap() {
+            event::Event::BarStateUpdateEvent(info) => {
+                assert_eq!(info.id, "bar-0");
+                assert!(info.visible_by_modifier);
+            }
+            other => panic!("expected a bar state update event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn capabilities() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(
+            7,
+            r#"{"major":4,"minor":18,"patch":2,"human_readable":"4.18.2",
+                "loaded_config_file_name":"/etc/i3/config"}"#,
+        );
+        let mut connection = i3.connect().unwrap();
+        let capabilities = connection.capabilities().unwrap();
+        assert!(!capabilities.sway);
+        assert!(capabilities.tick && capabilities.sync);
+        assert!(!capabilities.binding_state && !capabilities.inputs);
+        assert!(capabilities.at_least(4, 18, 1) && !capabilities.at_least(4, 19, 0));
+        assert_eq!(connection.capabilities().unwrap(), capabilities);
+        assert_eq!(i3.requests().len(), 1);
+
+        let sway: reply::Version = serde_json::from_str(
+            r#"{"major":1,"minor":8,"patch":1,"human_readable":"1.8.1","variant":"sway",
+                "loaded_config_file_name":"/etc/sway/config"}"#,
+        )
+        .unwrap();
+        let capabilities = capabilities::Capabilities::from(&sway);
+        assert!(capabilities.sway && capabilities.inputs && capabilities.binding_state);
+        assert!(!capabilities.sync && !capabilities.included_configs);
+    }
+
+    #[test]
+    fn clone_and_compare_events() {
+        let json_str = format!(r#"{{"change":"focus","container":{}}}"#, TEST_TREE);
+        let event = common::build_event(3, &json_str).unwrap();
+        let last = event.clone();
+        assert_eq!(event, last);
+        assert_ne!(
+            event,
+            common::build_event(2, r#"{"change":"resize"}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn listen_raw_keeps_payload() {
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(client);
+        let payload = r#"{"change":"resize","pango_markup":true}"#;
+        i3.write_all(&crate::encode_message(0x8000_0002, payload).unwrap())
+            .unwrap();
+        let raw = listener.listen_raw().next().unwrap().unwrap();
+        assert_eq!(raw.payload, payload);
+        match raw.event {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "resize"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lossy_listener_reports_malformed_events() {
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(client);
+        listener.set_lossy(true);
+        for (msgtype, payload) in &[
+            (0x8000_0003, r#"{"change":"focus"}"#),
+            (0x8000_002a, "{}"),
+            (0x8000_0002, r#"{"change":"default"}"#),
+        ] {
+            i3.write_all(&crate::encode_message(*msgtype, payload).unwrap())
+                .unwrap();
+        }
+        let mut events = listener.listen();
+        match events.next().unwrap().unwrap() {
+            event::Event::Malformed {
+                subscription,
+                payload,
+                error,
+            } => {
+                assert_eq!(subscription, Some(Subscription::Window));
+                assert_eq!(payload, r#"{"change":"focus"}"#);
+                assert!(error.contains("container"), "{}", error);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match events.next().unwrap().unwrap() {
+            event::Event::Malformed { subscription, .. } => assert_eq!(subscription, None),
+            other => panic!("unexpected {:?}", other),
+        }
+        match events.next().unwrap().unwrap() {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "default"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn into_channel() {
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let (events, handle) = I3EventListener::from_stream(client).into_channel().unwrap();
+        i3.write_all(&crate::encode_message(0x8000_0002, r#"{"change":"resize"}"#).unwrap())
+            .unwrap();
+        match events.recv().unwrap() {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "resize"),
+            other => panic!("unexpected {:?}", other),
+        }
+        handle.shutdown().unwrap();
+        assert!(events.recv().is_err());
+
+        let (i3, client) = UnixStream::pair().unwrap();
+        let (events, handle) = I3EventListener::from_stream(client).into_channel().unwrap();
+        drop(i3);
+        assert!(events.recv().is_err());
+        match handle.shutdown() {
+            Err(MessageError::Receive { error: e, .. }) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "mio")]
+    #[test]
+    fn mio_source() {
+        use std::os::unix::io::AsRawFd;
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let fd = client.as_raw_fd();
+        let mut listener = I3EventListener::from_stream(client);
+        assert_eq!(listener.as_raw_fd(), fd);
+        listener.set_nonblocking(true).unwrap();
+
+        let mut poll = mio::Poll::new().unwrap();
+        let mut events = mio::Events::with_capacity(4);
+        poll.registry()
+            .register(&mut listener, mio::Token(7), mio::Interest::READABLE)
+            .unwrap();
+        i3.write_all(&crate::encode_message(0x8000_0002, r#"{"change":"resize"}"#).unwrap())
+            .unwrap();
+        poll.poll(&mut events, Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        assert!(events.iter().any(|event| event.token() == mio::Token(7)));
+        assert!(listener.try_next().unwrap().is_some());
+        assert!(listener.try_next().unwrap().is_none());
+        poll.registry().deregister(&mut listener).unwrap();
+    }
+
+    #[test]
+    fn into_events() {
+        struct Watcher {
+            events: crate::OwnedEventIterator,
+        }
+
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut watcher = Watcher {
+            events: I3EventListener::from_stream(client).into_events(),
+        };
+        i3.write_all(&crate::encode_message(0x8000_0002, r#"{"change":"resize"}"#).unwrap())
+            .unwrap();
+        match watcher.events.next().unwrap().unwrap() {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "resize"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        watcher.events.listener().unsubscribe(&[Subscription::Mode]);
+        i3.write_all(&crate::encode_message(0x8000_0002, r#"{"change":"default"}"#).unwrap())
+            .unwrap();
+        i3.write_all(&crate::encode_message(0x8000_0001, r#"{"change":"unspecified"}"#).unwrap())
+            .unwrap();
+        match watcher.events.next().unwrap().unwrap() {
+            event::Event::OutputEvent(_) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tree_tracker() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(4, TEST_TREE);
+        let mut tracker = TreeTracker::new(i3.connect().unwrap()).unwrap();
+        assert_eq!(tracker.snapshot().find_focused().unwrap().id, 6);
+
+        let mut firefox = tracker.snapshot().find_by_id(5).unwrap().clone();
+        firefox.name = Some("Rust - Mozilla Firefox".to_owned());
+        tracker
+            .handle_event(&event::Event::WindowEvent(event::WindowEventInfo {
+                change: event::inner::WindowChange::Title,
+                container: firefox.clone(),
+            }))
+            .unwrap();
+        let tree = tracker.snapshot();
+        assert_eq!(tree.find_by_id(5).unwrap().name, firefox.name);
+        assert_eq!(tree.find_focused().unwrap().id, 6);
+
+        firefox.focused = true;
+        tracker
+            .handle_event(&event::Event::WindowEvent(event::WindowEventInfo {
+                change: event::inner::WindowChange::Focus,
+                container: firefox,
+            }))
+            .unwrap();
+        let tree = tracker.snapshot();
+        assert_eq!(tree.find_focused().unwrap().id, 5);
+        assert!(!tree.find_by_id(6).unwrap().focused);
+        assert_eq!(TreeIndex::new(tree).parent_of(5).unwrap().focus[0], 5);
+        assert_eq!(i3.requests().len(), 1);
+
+        let container = tracker.snapshot().find_by_id(8).unwrap().clone();
+        tracker
+            .handle_event(&event::Event::WindowEvent(event::WindowEventInfo {
+                change: event::inner::WindowChange::Close,
+                container,
+            }))
+            .unwrap();
+        assert_eq!(i3.requests().len(), 2);
+        assert_eq!(tracker.snapshot().find_focused().unwrap().id, 6);
+    }
+
+    #[test]
+    fn workspace_tracker() {
+        let i3 = MockI3::start().unwrap();
+        let rect = r#"{"x":0,"y":0,"width":1920,"height":1080}"#;
+        i3.set_reply(
+            1,
+            &format!(
+                r#"[{{"num":1,"name":"1","visible":true,"focused":true,"urgent":false,
+                     "rect":{r},"output":"eDP-1"}},
+                    {{"num":2,"name":"2","visible":false,"focused":false,"urgent":false,
+                     "rect":{r},"output":"eDP-1"}},
+                    {{"num":3,"name":"3","visible":true,"focused":false,"urgent":false,
+                     "rect":{r},"output":"HDMI-1"}}]"#,
+                r = rect
+            ),
+        );
+        let mut tracker = WorkspaceTracker::new(i3.connect().unwrap()).unwrap();
+        assert_eq!(tracker.focused().unwrap().name, "1");
+
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let mut workspace = tree.find_by_id(3).unwrap().clone();
+        workspace.name = Some("2".to_owned());
+        workspace.urgent = true;
+        for change in [
+            event::inner::WorkspaceChange::Focus,
+            event::inner::WorkspaceChange::Urgent,
+        ] {
+            tracker
+                .handle_event(&event::Event::WorkspaceEvent(event::WorkspaceEventInfo {
+                    change,
+                    current: Some(workspace.clone()),
+                    old: None,
+                }))
+                .unwrap();
+        }
+        assert_eq!(tracker.focused().unwrap().name, "2");
+        assert!(tracker.get("2").unwrap().visible && tracker.get("2").unwrap().urgent);
+        assert!(!tracker.get("1").unwrap().visible);
+        assert!(tracker.get("3").unwrap().visible);
+        assert_eq!(i3.requests().len(), 1);
+
+        tracker.set_reconcile_interval(std::time::Duration::from_secs(0));
+        tracker
+            .handle_event(&event::Event::WorkspaceEvent(event::WorkspaceEventInfo {
+                change: event::inner::WorkspaceChange::Focus,
+                current: Some(workspace),
+                old: None,
+            }))
+            .unwrap();
+        assert_eq!(i3.requests().len(), 2);
+        assert_eq!(tracker.focused().unwrap().name, "1");
+    }
+
+    #[test]
+    fn render_tree() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert_eq!(
+            tree.find_by_id(3).unwrap().render_tree(),
+            concat!(
+                "workspace splith \"1\" #3\n",
+                "  con splith #4\n",
+                "    con splith \"Mozilla Firefox\" #5\n",
+                "    con splith \"~\" #6 *\n",
+                "  floating_con splith #7 floating\n",
+                "    con splith \"htop\" #8\n",
+            )
+        );
+        assert_eq!(reply::NodeType::FloatingCon.as_str(), "floating_con");
+    }
+
+    #[test]
+    fn tree_diff() {
+        use crate::reply::TreeChange;
+        let old: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert!(reply::diff(&old, &old).is_empty());
+
+        let mut new = old.clone();
+        let split = &mut new.nodes[0].nodes[0].nodes[0];
+        let mut terminal = split.nodes.pop().unwrap();
+        split.nodes[0].name = Some("Rust - Mozilla Firefox".to_owned());
+        split.nodes[0].focused = true;
+        terminal.focused = false;
+        new.nodes[0].nodes[0].floating_nodes[0].nodes.clear();
+        new.nodes[0].nodes[0].nodes.push(terminal);
+
+        assert_eq!(
+            reply::diff(&old, &new),
+            vec![
+                TreeChange::Renamed {
+                    id: 5,
+                    old_name: Some("Mozilla Firefox".to_owned()),
+                    new_name: Some("Rust - Mozilla Firefox".to_owned()),
+                },
+                TreeChange::Moved {
+                    id: 6,
+                    old_parent: Some(4),
+                    new_parent: Some(3),
+                },
+                TreeChange::Removed {
+                    id: 8,
+                    parent: Some(7),
+                },
+                TreeChange::Refocused {
+                    old: Some(6),
+                    new: Some(5),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn record_and_replay() {
+        use crate::record::{Recorder, Replay};
+
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(client);
+        i3.write_all(&crate::encode_message(0x8000_0002, r#"{"change":"resize"}"#).unwrap())
+            .unwrap();
+        i3.write_all(&crate::encode_message(0x8000_0001, r#"{"change":"unspecified"}"#).unwrap())
+            .unwrap();
+        let mut recorder = Recorder::new(Vec::new());
+        for event in listener.listen_raw().take(2) {
+            recorder.record(&event.unwrap()).unwrap();
+        }
+        let recording = recorder.into_inner();
+        let first_line = std::str::from_utf8(&recording)
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap();
+        assert!(
+            first_line.contains(r#""subscription":"mode""#),
+            "{}",
+            first_line
+        );
+
+        let mut replay = Replay::new(&recording[..]);
+        replay.set_speed(f64::INFINITY);
+        let events = replay.collect::<Result<Vec<_>, _>>().unwrap();
+        match events[..] {
+            [event::Event::ModeEvent(ref mode), event::Event::OutputEvent(_)] => {
+                assert_eq!(mode.change, "resize")
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_carry_context() {
+        let long_title = "x".repeat(1000);
+        let json_str = format!(
+            r#"{{"change":"focus","container":{{"name":"{}"}}}}"#,
+            long_title
+        );
+        let error = common::build_event(3, &json_str).unwrap_err();
+        assert_eq!(error.message_type(), Some(0x8000_0003));
+        assert_eq!(
+            error.to_string(),
+            "Got an event of type 3 from i3 with a missing or invalid field `container.id`"
+        );
+        match error {
+            MessageError::InvalidField { ref payload, .. } => {
+                assert!(payload.len() < 300 && payload.ends_with("..."))
+            }
+            ref other => panic!("expected InvalidField, got {:?}", other),
+        }
+        assert!(std::error::Error::source(&error).is_some());
+
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(client);
+        i3.write_all(&crate::encode_message(5, "[not json").unwrap())
+            .unwrap();
+        match connection.get_marks() {
+            Err(MessageError::JsonCouldntParse {
+                message_type,
+                ref payload,
+                ..
+            }) => {
+                assert_eq!(message_type, 5);
+                assert_eq!(payload, "[not json");
+            }
+            other => panic!("expected JsonCouldntParse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unexpected_reply_type() {
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(client);
+        i3.write_all(&crate::encode_message(4, "{}").unwrap())
+            .unwrap();
+        match connection.get_marks() {
+            Err(MessageError::UnexpectedReplyType { expected, received }) => {
+                assert_eq!((expected, received), (5, 4))
+            }
+            other => panic!("expected UnexpectedReplyType, got {:?}", other),
+        }
+        i3.write_all(&crate::encode_message(5, r#"["a"]"#).unwrap())
+            .unwrap();
+        assert_eq!(connection.get_marks().unwrap().marks, vec!["a".to_owned()]);
+    }
+
+    #[test]
+    fn resynchronize() {
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(client);
+        let mut bytes = b"garbage i3-i".to_vec();
+        bytes.extend(crate::encode_message(5, r#"["a"]"#).unwrap());
+        i3.write_all(&bytes).unwrap();
+        match connection.get_marks() {
+            Err(MessageError::Receive { .. }) => {}
+            other => panic!("expected a Receive error, got {:?}", other),
+        }
+
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(client);
+        connection.set_resynchronize(true);
+        i3.write_all(&bytes).unwrap();
+        assert_eq!(connection.get_marks().unwrap().marks, vec!["a".to_owned()]);
+
+        let mut buffer = b"xxi3-".to_vec();
+        assert_eq!(crate::take_message(&mut buffer, true).unwrap(), None);
+        assert_eq!(buffer, b"i3-");
+    }
+
+    /// A transport that hands over at most `chunk` bytes per read or write and fails every
+    /// other call with `Interrupted`, like a loaded system delivering a signal mid-message.
+    struct Dribble {
+        input: io::Cursor<Vec<u8>>,
+        output: Arc<Mutex<Vec<u8>>>,
+        chunk: usize,
+        interrupt: bool,
+    }
+
+    impl Dribble {
+        fn new(input: Vec<u8>, chunk: usize) -> (Dribble, Arc<Mutex<Vec<u8>>>) {
+            let output = Arc::new(Mutex::new(Vec::new()));
+            let dribble = Dribble {
+                input: io::Cursor::new(input),
+                output: output.clone(),
+                chunk,
+                interrupt: false,
+            };
+            (dribble, output)
+        }
+
+        fn interrupted(&mut self) -> bool {
+            self.interrupt = !self.interrupt;
+            self.interrupt
+        }
+    }
+
+    impl Read for Dribble {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.interrupted() {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let len = buf.len().min(self.chunk);
+            self.input.read(&mut buf[..len])
+        }
+    }
+
+    impl Write for Dribble {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.interrupted() {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let len = buf.len().min(self.chunk);
+            self.output.lock().unwrap().extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    impl futures::io::AsyncRead for Dribble {
+        fn poll_read(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<io::Result<usize>> {
+            std::task::Poll::Ready(self.get_mut().read(buf))
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    impl futures::io::AsyncWrite for Dribble {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<io::Result<usize>> {
+            std::task::Poll::Ready(self.get_mut().write(buf))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn dribbled_messages() {
+        for &chunk in &[1, 7, 4096] {
+            let (stream, output) =
+                Dribble::new(crate::encode_message(4, TEST_TREE).unwrap(), chunk);
+            let mut connection = I3Connection::from_stream(stream);
+            assert_eq!(connection.get_tree().unwrap().id, 1);
+            assert_eq!(
+                *output.lock().unwrap(),
+                crate::encode_message(4, "").unwrap()
+            );
+        }
+
+        let mut input = crate::encode_message(0x8000_0005, r#"{"change":"mode"}"#).unwrap();
+        input.truncate(input.len() - 3);
+        let (stream, _) = Dribble::new(input, 3);
+        let mut listener = I3EventListener::from_stream(stream);
+        match listener.listen().next() {
+            Some(Err(MessageError::Receive { error: ref e, .. })) => {
+                assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof)
+            }
+            other => panic!("expected an UnexpectedEof error, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn dribbled_messages_async() {
+        use std::future::Future;
+        use std::task::{Context, Poll};
+
+        let (stream, output) = Dribble::new(crate::encode_message(4, TEST_TREE).unwrap(), 5);
+        let mut connection = crate::transport::AsyncI3Connection::new(stream);
+        let mut future = Box::pin(connection.get_tree());
+        let waker = futures::task::noop_waker();
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(tree) => assert_eq!(tree.unwrap().id, 1),
+            Poll::Pending => panic!("the transport never blocks"),
+        }
+        drop(future);
+        assert_eq!(
+            *output.lock().unwrap(),
+            crate::encode_message(4, "").unwrap()
+        );
+    }
+
+    #[test]
+    fn tree_too_deep() {
+        let deep = format!("{}{{}}{}", r#"{"nodes":["#.repeat(100), "]}".repeat(100));
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(client);
+        i3.write_all(&crate::encode_message(4, &deep).unwrap())
+            .unwrap();
+        match connection.get_tree() {
+            Err(MessageError::TreeTooDeep {
+                message_type,
+                depth,
+                limit,
+            }) => assert_eq!((message_type, depth, limit), (4, 101, 64)),
+            other => panic!("expected TreeTooDeep, got {:?}", other),
+        }
+
+        connection.set_max_tree_depth(4);
+        i3.write_all(&crate::encode_message(4, TEST_TREE).unwrap())
+            .unwrap();
+        match connection.get_tree() {
+            Err(MessageError::TreeTooDeep { depth, limit, .. }) => {
+                assert_eq!((depth, limit), (5, 4))
+            }
+            other => panic!("expected TreeTooDeep, got {:?}", other),
+        }
+        connection.set_max_tree_depth(5);
+        i3.write_all(&crate::encode_message(4, TEST_TREE).unwrap())
+            .unwrap();
+        assert_eq!(connection.get_tree().unwrap().id, 1);
+
+        // brackets in strings and nesting outside `nodes` don't count
+        let json_str = r#"{"change":"[[[[[[[[[[\"]]]]","nodes":"[[","nested":[[{}]]}"#;
+        let mut listener = I3EventListener::from_stream(Dribble::new(Vec::new(), 1).0);
+        listener.set_max_tree_depth(1);
+        assert!(listener.build_event(2, json_str).is_ok());
+        let tree: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        let workspace = tree["nodes"][0]["nodes"][0].to_string();
+        let event = format!(r#"{{"change":"focus","current":{}}}"#, workspace);
+        listener.set_max_tree_depth(2);
+        match listener.build_event(0, &event) {
+            Err(MessageError::TreeTooDeep { depth, limit, .. }) => {
+                assert_eq!((depth, limit), (3, 2))
+            }
+            other => panic!("expected TreeTooDeep, got {:?}", other),
+        }
+        listener.set_max_tree_depth(3);
+        assert!(listener.build_event(0, &event).is_ok());
+    }
+
+    #[test]
+    fn tree_depth_above_default() {
+        let tree: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        let leaf = tree["nodes"][0]["nodes"][0]["nodes"][0]["nodes"][1].clone();
+        let mut deep = leaf.clone();
+        for id in 0..99 {
+            let mut parent = leaf.clone();
+            parent["id"] = (1000 + id).into();
+            parent["nodes"] = serde_json::json!([deep]);
+            deep = parent;
+        }
+        let deep = deep.to_string();
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(client);
+        i3.write_all(&crate::encode_message(4, &deep).unwrap())
+            .unwrap();
+        assert!(matches!(
+            connection.get_tree(),
+            Err(MessageError::TreeTooDeep { depth: 100, .. })
+        ));
+        connection.set_max_tree_depth(100);
+        i3.write_all(&crate::encode_message(4, &deep).unwrap())
+            .unwrap();
+        // a debug build needs more than the default stack of a test thread for this.
+        let depth = std::thread::Builder::new()
+            .stack_size(16 << 20)
+            .spawn(move || {
+                let mut node = connection.get_tree().unwrap();
+                let mut depth = 1;
+                while let Some(child) = node.nodes.pop() {
+                    node = child;
+                    depth += 1;
+                }
+                depth
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(depth, 100);
+
+        // JSON nested much deeper than the containers still hits the parser's own limit.
+        let mut container = leaf;
+        container["swallows"] = serde_json::json!([{ "class": "deep" }]);
+        let event = serde_json::json!({ "change": "new", "container": container })
+            .to_string()
+            .replace(
+                r#""deep""#,
+                &format!("{}{}", "[".repeat(1000), "]".repeat(1000)),
+            );
+        let mut listener = I3EventListener::from_stream(Dribble::new(Vec::new(), 1).0);
+        listener.set_max_tree_depth(100);
+        assert!(matches!(
+            listener.build_event(3, &event),
+            Err(MessageError::JsonCouldntParse { .. })
+        ));
+    }
+
+    #[test]
+    fn batched_events_share_the_read_buffer() {
+        let mut input = Vec::new();
+        for &(event_type, payload) in &[
+            (2, r#"{"change":"resize"}"#),
+            (3, "never parsed"),
+            (2, r#"{"change":"default"}"#),
+        ] {
+            input.extend(crate::encode_message(0x8000_0000 | event_type, payload).unwrap());
+        }
+        let (stream, _) = Dribble::new(input, 4096);
+        let mut listener = I3EventListener::from_stream(stream);
+        listener.unsubscribe(&[Subscription::Window]);
+        let changes: Vec<_> = listener
+            .listen()
+            .take(2)
+            .map(|event| match event.unwrap() {
+                event::Event::ModeEvent(info) => info.change,
+                other => panic!("expected a mode event, got {:?}", other),
+            })
             .collect();
-        Ok(reply::Outputs { outputs })
+        assert_eq!(changes, ["resize", "default"]);
+        assert!(listener.stream.read_buffer.is_empty());
+        assert!(listener.stream.read_buffer.capacity() > 0);
     }
 
-    /// Gets the layout tree. i3 uses a tree as data structure which includes every container.
-    pub fn get_tree(&mut self) -> Result<reply::Node, MessageError> {
-        let val: json::Value = self.stream.send_receive_i3_message(4, "")?;
-        Ok(common::build_tree(&val))
+    #[test]
+    fn parse_borrowed() {
+        use std::borrow::Cow;
+
+        let json_str = r#"{"change":"title","container":{"id":6,"type":"con","name":"say \"hi\"",
+            "window":77,"window_properties":{"class":"URxvt","title":"~"},"marks":["term"],
+            "urgent":false,"focused":true}}"#;
+        let message = crate::encode_message(0x8000_0003, json_str).unwrap();
+        match event::Event::parse_borrowed(&message).unwrap() {
+            event::EventRef::WindowEvent(info) => {
+                assert_eq!(info.change, event::inner::WindowChange::Title);
+                let container = info.container;
+                assert_eq!(container.id, 6);
+                assert!(container.focused);
+                assert!(
+                    matches!(container.name, Some(Cow::Owned(ref name)) if name == "say \"hi\"")
+                );
+                let properties = container.window_properties.unwrap();
+                assert!(matches!(properties.class, Some(Cow::Borrowed("URxvt"))));
+                assert!(matches!(container.marks[..], [Cow::Borrowed("term")]));
+            }
+            other => panic!("expected a window event, got {:?}", other),
+        }
+
+        let reply = crate::encode_message(3, json_str).unwrap();
+        match event::Event::parse_borrowed(&reply) {
+            Err(MessageError::Receive { error: ref e, .. }) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData)
+            }
+            other => panic!("expected a Receive error, got {:?}", other),
+        }
+        match event::Event::parse_borrowed(&message[..message.len() - 1]) {
+            Err(MessageError::Receive { error: ref e, .. }) => {
+                assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof)
+            }
+            other => panic!("expected a Receive error, got {:?}", other),
+        }
+
+        let (mut i3, client) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(client);
+        i3.write_all(&crate::encode_message(0x8000_0002, r#"{"change":"resize"}"#).unwrap())
+            .unwrap();
+        let change = listener
+            .inspect_next(|event| match event {
+                event::EventRef::ModeEvent(info) => info.change.into_owned(),
+                other => panic!("expected a mode event, got {:?}", other),
+            })
+            .unwrap();
+        assert_eq!(change, "resize");
     }
 
-    /// Gets a list of marks (identifiers for containers to easily jump to them later).
-    pub fn get_marks(&mut self) -> Result<reply::Marks, MessageError> {
-        let marks: Vec<String> = self.stream.send_receive_i3_message(5, "")?;
-        Ok(reply::Marks { marks })
+    #[test]
+    fn deserialize_workspaces() {
+        let json_str = r#"[
+            {"num":1,"name":"1","visible":false,"focused":false,"urgent":false,
+             "rect":{"x":0,"y":0,"width":1920,"height":1080},"output":"eDP-1"},
+            {"num":-1,"name":"mail","visible":true,"focused":true,"urgent":false,
+             "rect":{"x":0,"y":0,"width":1920,"height":1080},"output":"eDP-1"},
+            {"num":3,"name":"3","visible":true,"focused":false,"urgent":false,
+             "rect":{"x":1920,"y":0,"width":2560,"height":1440},"output":"HDMI-1"}
+        ]"#;
+        let workspaces: reply::Workspaces = serde_json::from_str(json_str).unwrap();
+        assert_eq!(workspaces.workspaces[0].num, Some(1));
+        let focused = workspaces.focused().unwrap();
+        assert_eq!((focused.name.as_str(), focused.num), ("mail", None));
+        let names: Vec<_> = workspaces
+            .on_output("eDP-1")
+            .iter()
+            .map(|workspace| workspace.name.as_str())
+            .collect();
+        assert_eq!(names, ["1", "mail"]);
+        assert_eq!(workspaces.by_name("3").unwrap().rect.width, 2560);
+        assert!(workspaces.by_name("4").is_none());
+        let json = serde_json::to_value(&workspaces).unwrap();
+        assert_eq!(json[1]["num"], -1);
     }
 
-    /// Gets an array with all configured bar IDs.
-    pub fn get_bar_ids(&mut self) -> Result<reply::BarIds, MessageError> {
-        let ids: Vec<String> = self.stream.send_receive_i3_message(6, "")?;
-        Ok(reply::BarIds { ids })
+    #[test]
+    fn mark_helpers() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(0, r#"[{"success":true}]"#);
+        i3.set_reply(4, TEST_TREE);
+        i3.set_reply(5, r#"["web","gone"]"#);
+        let mut connection = i3.connect().unwrap();
+        let terminal = Criteria::new().class("URxvt");
+        connection.set_mark(&terminal, "term").unwrap();
+        connection.toggle_mark(&terminal, "t").unwrap();
+        connection.unset_mark("term").unwrap();
+        let commands: Vec<_> = i3
+            .requests()
+            .into_iter()
+            .filter(|&(message_type, _)| message_type == 0)
+            .map(|(_, payload)| payload)
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                r#"[class="URxvt"] mark "term""#,
+                r#"[class="URxvt"] mark --add --toggle "t""#,
+                r#"unmark "term""#,
+            ]
+        );
+
+        let marked = connection.get_marked_containers().unwrap();
+        assert_eq!(marked.len(), 1);
+        assert_eq!((marked[0].0.as_str(), marked[0].1.id), ("web", 5));
+    }
+
+    #[test]
+    fn get_focused_output() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(
+            1,
+            r#"[{"num":1,"name":"1","visible":true,"focused":false,"urgent":false,
+                 "rect":{"x":0,"y":0,"width":1920,"height":1080},"output":"eDP-1"},
+                {"num":2,"name":"2","visible":true,"focused":true,"urgent":false,
+                 "rect":{"x":1920,"y":0,"width":2560,"height":1440},"output":"HDMI-1"}]"#,
+        );
+        i3.set_reply(
+            3,
+            &r#"[{"name":"eDP-1","active":true,"primary":true,"current_workspace":"1",
+                  "rect":{"x":0,"y":0,"width":1920,"height":1080}SWAY},
+                 {"name":"HDMI-1","active":true,"primary":false,"current_workspace":"2",
+                  "rect":{"x":1920,"y":0,"width":2560,"height":1440}SWAY}]"#
+                .replace("SWAY", SWAY_OUTPUT_FIELDS),
+        );
+        let mut connection = i3.connect().unwrap();
+        let output = connection.get_focused_output().unwrap().unwrap();
+        assert_eq!(output.name, "HDMI-1");
+        assert_eq!(output.rect.x, 1920);
+    }
+
+    #[test]
+    fn get_workspace_lookups() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(
+            1,
+            r#"[{"num":2,"name":"2: mail","visible":true,"focused":true,"urgent":false,
+                 "rect":{"x":0,"y":0,"width":1920,"height":1080},"output":"eDP-1"},
+                {"num":-1,"name":"notes","visible":false,"focused":false,"urgent":false,
+                 "rect":{"x":0,"y":0,"width":1920,"height":1080},"output":"eDP-1"}]"#,
+        );
+        let mut connection = i3.connect().unwrap();
+        assert_eq!(
+            connection.get_workspace("notes").unwrap().unwrap().num,
+            None
+        );
+        assert!(connection.get_workspace("2").unwrap().is_none());
+        assert_eq!(
+            connection.get_workspace_number(2).unwrap().unwrap().name,
+            "2: mail"
+        );
+        assert!(connection.get_workspace_number(-1).unwrap().is_none());
+    }
+
+    #[test]
+    fn workspace_command_helpers() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(0, r#"[{"success":true}]"#);
+        let mut connection = i3.connect().unwrap();
+        assert!(connection.focus_workspace(r#"2: "mail""#).unwrap().success);
+        assert!(connection.move_focused_to_workspace("3").unwrap().success);
+        assert!(connection.rename_workspace("3", "3: web").unwrap().success);
+        // the canned reply only has an outcome for the first command.
+        let outcome = connection
+            .move_workspace_to_output("3: web", "HDMI-1")
+            .unwrap();
+        assert!(!outcome.success);
+        let commands: Vec<_> = i3
+            .requests()
+            .into_iter()
+            .map(|(_, payload)| payload)
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                r#"workspace "2: \"mail\"""#,
+                r#"move container to workspace "3""#,
+                r#"rename workspace "3" to "3: web""#,
+                r#"workspace "3: web"; move workspace to output "HDMI-1""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn scratchpad() {
+        let mut tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert!(tree.find_scratchpad().is_none());
+        assert!(!tree.is_in_scratchpad(8));
+        tree.nodes[0].nodes[0].name = Some("__i3_scratch".to_owned());
+        assert_eq!(tree.find_scratchpad().unwrap().id, 3);
+        assert!(tree.is_in_scratchpad(8));
+        assert!(!tree.is_in_scratchpad(3));
+        assert!(!tree.nodes[0].is_scratchpad());
+
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(0, r#"[{"success":true}]"#);
+        let mut connection = i3.connect().unwrap();
+        let terminal = Criteria::new().instance("dropdown");
+        assert!(connection.move_to_scratchpad(&terminal).unwrap().success);
+        assert!(connection.scratchpad_show(&terminal).unwrap().success);
+        connection.scratchpad_show(&Criteria::new()).unwrap();
+        let commands: Vec<_> = i3
+            .requests()
+            .into_iter()
+            .map(|(_, payload)| payload)
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                r#"[instance="dropdown"] move scratchpad"#,
+                r#"[instance="dropdown"] scratchpad show"#,
+                "scratchpad show",
+            ]
+        );
+    }
+
+    #[test]
+    fn exec_quoting() {
+        let mock = MockI3::start().unwrap();
+        mock.set_reply(0, r#"[{"success":true}]"#);
+        let mut connection = mock.connect().unwrap();
+        let cmd = r#"notify-send "a; b, c" 'it\'s' && echo \"done\""#;
+        assert!(connection.exec(cmd).unwrap().success);
+        assert!(
+            connection
+                .exec_no_startup_id("echo ]; kill")
+                .unwrap()
+                .success
+        );
+        let requests = mock.requests();
+        assert_eq!(
+            requests[0],
+            (
+                0,
+                r#"exec "notify-send \"a; b, c\" 'it\\'s' && echo \\\"done\\\"""#.to_owned()
+            )
+        );
+        assert_eq!(
+            requests[1],
+            (0, r#"exec --no-startup-id "echo ]; kill""#.to_owned())
+        );
+    }
+
+    #[test]
+    fn wait_for_window() {
+        let tree: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        let split = &tree["nodes"][0]["nodes"][0]["nodes"][0];
+        let firefox = format!(r#"{{"change":"new","container":{}}}"#, split["nodes"][0]);
+        let urxvt = format!(r#"{{"change":"new","container":{}}}"#, split["nodes"][1]);
+        let focus = format!(r#"{{"change":"focus","container":{}}}"#, split["nodes"][1]);
+
+        let i3 = MockI3::start().unwrap();
+        let mut listener = i3.connect_listener().unwrap();
+        listener.subscribe(&[Subscription::Window]).unwrap();
+        for payload in &[&firefox, &focus, &urxvt] {
+            i3.push_event(3, payload).unwrap();
+        }
+        let window = listener
+            .wait_for_window(std::time::Duration::from_secs(5), |node| node.id == 6)
+            .unwrap()
+            .unwrap();
+        assert_eq!(window.name.as_deref(), Some("~"));
+        assert_eq!(i3.requests().iter().filter(|r| r.0 == 2).count(), 2);
+
+        i3.push_event(3, &firefox).unwrap();
+        let started = std::time::Instant::now();
+        let window = listener
+            .wait_for_window(std::time::Duration::from_millis(50), |node| node.id == 6)
+            .unwrap();
+        assert!(window.is_none());
+        assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+        assert_eq!(listener.stream.socket.read_timeout().unwrap(), None);
+    }
+
+    #[test]
+    fn exec_and_wait() {
+        let tree: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        let urxvt = format!(
+            r#"{{"change":"new","container":{}}}"#,
+            tree["nodes"][0]["nodes"][0]["nodes"][0]["nodes"][1]
+        );
+
+        let i3 = Arc::new(MockI3::start().unwrap());
+        i3.set_reply(0, r#"[{"success":true}]"#);
+        let mut connection = i3.connect().unwrap();
+        let mut listener = i3.connect_listener().unwrap();
+        let pusher = {
+            let i3 = i3.clone();
+            std::thread::spawn(move || {
+                // open the window only once the command has been sent.
+                while !i3.requests().iter().any(|r| r.0 == 0) {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                i3.push_event(3, &urxvt).unwrap();
+            })
+        };
+        let window = listener
+            .exec_and_wait(
+                &mut connection,
+                "urxvt",
+                |node| node.id == 6,
+                std::time::Duration::from_secs(5),
+            )
+            .unwrap()
+            .unwrap();
+        pusher.join().unwrap();
+        assert_eq!(window.id, 6);
+        let requests = i3.requests();
+        assert_eq!(requests[0].0, 2);
+        assert_eq!(requests[1], (0, r#"exec "urxvt""#.to_owned()));
+
+        i3.set_reply(0, r#"[{"success":false,"error":"nope"}]"#);
+        let window = listener
+            .exec_and_wait(
+                &mut connection,
+                "urxvt",
+                |_| true,
+                std::time::Duration::from_secs(5),
+            )
+            .unwrap();
+        assert!(window.is_none());
+    }
+
+    #[test]
+    fn floating_geometry() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(0, r#"[{"success":true}]"#);
+        i3.set_reply(4, TEST_TREE);
+        let mut connection = i3.connect().unwrap();
+        let htop = Criteria::new().con_id(8);
+        assert!(
+            connection
+                .move_floating_to(&htop, 400, -20)
+                .unwrap()
+                .success
+        );
+        assert!(connection.resize_floating(&htop, 640, 480).unwrap().success);
+        assert!(
+            connection
+                .center_floating(&Criteria::new())
+                .unwrap()
+                .success
+        );
+        let commands: Vec<String> = i3.requests().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![
+                r#"[con_id="8"] move position 400 px -20 px"#,
+                r#"[con_id="8"] resize set 640 px 480 px"#,
+                "move position center",
+            ]
+        );
+        assert_eq!(
+            connection.get_floating_rect(8).unwrap(),
+            Some(reply::Rect {
+                x: 400,
+                y: 300,
+                width: 640,
+                height: 480
+            })
+        );
+        assert_eq!(connection.get_floating_rect(6).unwrap(), None);
+    }
+
+    #[test]
+    fn fullscreen_mode() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert_eq!(tree.fullscreen_mode, reply::FullscreenMode::None);
+        assert!(tree.find(|node| node.is_fullscreen()).is_none());
+
+        let json = TEST_TREE.replace(r#""id": 6,"#, r#""id": 6, "fullscreen_mode": 2,"#);
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        let fullscreen = tree.find(|node| node.is_fullscreen()).unwrap();
+        assert_eq!(fullscreen.id, 6);
+        assert_eq!(fullscreen.fullscreen_mode, reply::FullscreenMode::Global);
+
+        let mode: reply::FullscreenMode = serde_json::from_str("1").unwrap();
+        assert_eq!(mode, reply::FullscreenMode::Output);
+        let mode: reply::FullscreenMode = serde_json::from_str("7").unwrap();
+        assert_eq!(mode, reply::FullscreenMode::Unknown(7));
+        assert_eq!(serde_json::to_string(&mode).unwrap(), "7");
+    }
+
+    #[test]
+    fn floating_and_sticky() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let htop = tree.find_by_id(8).unwrap();
+        assert_eq!(htop.floating, None);
+        assert!(!htop.sticky);
+
+        let json = TEST_TREE
+            .replace(r#""id": 6,"#, r#""id": 6, "floating": "auto_off","#)
+            .replace(
+                r#""id": 8,"#,
+                r#""id": 8, "floating": "user_on", "sticky": true,"#,
+            );
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        let urxvt = tree.find_by_id(6).unwrap();
+        assert_eq!(urxvt.floating, Some(reply::FloatingState::AutoOff));
+        assert!(!urxvt.sticky);
+        let htop = tree.find_by_id(8).unwrap();
+        assert!(htop.floating.as_ref().unwrap().is_floating());
+        assert!(htop.sticky);
+    }
+
+    #[test]
+    fn window_type() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert_eq!(tree.find_by_id(6).unwrap().window_type, None);
+
+        let json = TEST_TREE
+            .replace(r#""id": 6,"#, r#""id": 6, "window_type": "dialog","#)
+            .replace(r#""id": 8,"#, r#""id": 8, "window_type": "dropdown_menu","#);
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        let dialog = tree
+            .find(|node| node.window_type == Some(reply::WindowType::Dialog))
+            .unwrap();
+        assert_eq!(dialog.id, 6);
+        let menu = tree.find_by_id(8).unwrap().window_type.as_ref().unwrap();
+        assert_eq!(*menu, reply::WindowType::DropdownMenu);
+        assert_eq!(menu.as_str(), "dropdown_menu");
+    }
+
+    #[test]
+    fn focused_leaf() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert_eq!(tree.focused_leaf().id, 6);
+        let workspace = tree.find_by_id(3).unwrap();
+        let mut unfocused = workspace.clone();
+        unfocused.focus = vec![7, 4];
+        assert_eq!(unfocused.focused_leaf().id, 8);
+        assert_eq!(tree.find_by_id(5).unwrap().focused_leaf().id, 5);
+    }
+
+    #[test]
+    fn list_replies() {
+        let marks: reply::Marks = serde_json::from_str(r#"["web","term"]"#).unwrap();
+        assert_eq!(marks.len(), 2);
+        assert!(!marks.is_empty());
+        assert_eq!(marks.iter().next().map(String::as_str), Some("web"));
+        let mut seen = Vec::new();
+        for mark in &marks {
+            seen.push(mark.as_str());
+        }
+        assert_eq!(seen, vec!["web", "term"]);
+        let owned: Vec<String> = marks.into_iter().collect();
+        assert_eq!(owned, vec!["web", "term"]);
+
+        let outputs: reply::Outputs = serde_json::from_str("[]").unwrap();
+        assert!(outputs.is_empty());
+        assert_eq!(outputs.iter().count(), 0);
+
+        let workspaces: reply::Workspaces = serde_json::from_str(
+            r#"[{"num":1,"name":"1","visible":true,"focused":true,"urgent":false,
+                "rect":{"x":0,"y":0,"width":10,"height":10},"output":"eDP-1"}]"#,
+        )
+        .unwrap();
+        let names: Vec<&str> = workspaces.iter().map(|w| w.name.as_str()).collect();
+        assert_eq!(names, vec!["1"]);
+        assert_eq!(workspaces.into_iter().next().unwrap().num, Some(1));
+    }
+
+    #[test]
+    fn event_kind() {
+        use std::collections::HashMap;
+
+        let mode = common::build_event(2, r#"{"change":"resize"}"#).unwrap();
+        assert_eq!(mode.kind(), event::EventKind::Mode);
+        assert_eq!(mode.subscription(), Some(Subscription::Mode));
+        let malformed = event::Event::Malformed {
+            subscription: Some(Subscription::Window),
+            payload: "{".to_owned(),
+            error: "EOF".to_owned(),
+        };
+        assert_eq!(malformed.kind(), event::EventKind::Malformed);
+        assert_eq!(malformed.kind().subscription(), None);
+        assert_eq!(malformed.subscription(), Some(Subscription::Window));
+
+        let mut counts = HashMap::new();
+        for event in &[&mode, &malformed, &mode] {
+            *counts.entry(event.kind()).or_insert(0) += 1;
+        }
+        assert_eq!(counts[&event::EventKind::Mode], 2);
+        for subscription in &[Subscription::Workspace, Subscription::BarConfig] {
+            let kind = event::EventKind::from(*subscription);
+            assert_eq!(kind.subscription(), Some(*subscription));
+        }
+    }
+
+    #[test]
+    fn typed_listeners() {
+        let i3 = MockI3::start().unwrap();
+        let mut listener = i3.connect_listener().unwrap();
+        listener.subscribe(&[Subscription::Mode]).unwrap();
+        let mut windows = listener.windows_only().unwrap();
+        i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+        i3.push_event(3, r#"{"change":"focus","container":null}"#)
+            .unwrap();
+        let tree: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        let urxvt = &tree["nodes"][0]["nodes"][0]["nodes"][0]["nodes"][1];
+        i3.push_event(3, &format!(r#"{{"change":"title","container":{}}}"#, urxvt))
+            .unwrap();
+        // the mode event is skipped, the broken window event isn't.
+        assert!(windows.next().unwrap().is_err());
+        let info = windows.next().unwrap().unwrap();
+        assert_eq!(info.change, event::inner::WindowChange::Title);
+        assert_eq!(info.container.id, 6);
+
+        let mut workspaces = listener.workspaces_only().unwrap();
+        i3.push_event(0, r#"{"change":"empty","current":null,"old":null}"#)
+            .unwrap();
+        let info = workspaces.next().unwrap().unwrap();
+        assert_eq!(info.change, event::inner::WorkspaceChange::Empty);
+    }
+
+    #[test]
+    fn event_hub() {
+        use crate::hub::EventHub;
+
+        let i3 = MockI3::start().unwrap();
+        let mut hub = EventHub::new(i3.connect_listener().unwrap());
+        let modes = hub.subscribe(&[Subscription::Mode]);
+        let both = hub.subscribe(&[Subscription::Mode, Subscription::Workspace]);
+        let handle = hub.start().unwrap();
+        let subscribe = i3.requests();
+        assert_eq!(
+            subscribe,
+            vec![(2, r#"[ "mode", "workspace" ]"#.to_owned())]
+        );
+
+        i3.push_event(0, r#"{"change":"empty","current":null,"old":null}"#)
+            .unwrap();
+        i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        match modes.recv_timeout(timeout).unwrap() {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "resize"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            both.recv_timeout(timeout).unwrap().kind(),
+            event::EventKind::Workspace
+        );
+        assert_eq!(
+            both.recv_timeout(timeout).unwrap().kind(),
+            event::EventKind::Mode
+        );
+
+        drop(modes);
+        i3.push_event(2, r#"{"change":"default"}"#).unwrap();
+        assert_eq!(
+            both.recv_timeout(timeout).unwrap().kind(),
+            event::EventKind::Mode
+        );
+        handle.shutdown().unwrap();
+        assert!(both.recv().is_err());
+    }
+
+    #[test]
+    fn retry_policy() {
+        use crate::retry::RetryPolicy;
+        use std::time::Duration;
+
+        let policy = RetryPolicy::new()
+            .initial_delay(Duration::from_millis(100))
+            .backoff(2.0)
+            .max_delay(Duration::from_millis(500));
+        let delays: Vec<u128> = (0..5).map(|n| policy.base_delay(n).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.base_delay(u32::MAX), Duration::from_millis(500));
+
+        let jittery = policy.jitter(0.5);
+        for _ in 0..20 {
+            let delay = jittery.delay(0);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+        }
+
+        let reconnected = Arc::new(Mutex::new(Vec::new()));
+        let policy = {
+            let reconnected = reconnected.clone();
+            RetryPolicy::fixed(5, Duration::from_millis(0))
+                .on_reconnect(move |attempts| reconnected.lock().unwrap().push(attempts))
+        };
+        let mut calls = 0;
+        let result: Result<u32, Option<&str>> = policy.retry(|| {
+            calls += 1;
+            if calls < 3 {
+                Err("refused")
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(3));
+        assert_eq!(*reconnected.lock().unwrap(), vec![3]);
+
+        let result: Result<(), Option<&str>> = policy.max_attempts(2).retry(|| Err("refused"));
+        assert_eq!(result, Err(Some("refused")));
+        let result: Result<(), Option<&str>> =
+            RetryPolicy::fixed(0, Duration::from_millis(0)).retry(|| Ok(()));
+        assert_eq!(result, Err(None));
+        assert_eq!(*reconnected.lock().unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn connection_builder() {
+        use crate::retry::RetryPolicy;
+        use crate::EstablishError;
+        use std::error::Error;
+        use std::time::Duration;
+
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(
+            7,
+            r#"{"major":4,"minor":22,"patch":0,"human_readable":"4.22",
+                "loaded_config_file_name":"/c"}"#,
+        );
+        let path = i3.socket_path().to_str().unwrap().to_owned();
+        let mut connection = I3Connection::builder()
+            .socket_path(path.clone())
+            .timeout(Duration::from_secs(5))
+            .capability_probe(true)
+            .connect()
+            .unwrap();
+        assert_eq!(i3.requests(), vec![(7, String::new())]);
+        assert!(connection.capabilities().unwrap().tick);
+        assert_eq!(i3.requests().len(), 1);
+
+        i3.set_reply(7, "{}");
+        match I3Connection::builder()
+            .socket_path(path)
+            .capability_probe(true)
+            .connect()
+        {
+            Err(EstablishError::ProbeError(MessageError::InvalidField { .. })) => {}
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+
+        let missing = std::env::temp_dir().join("i3ipc-no-such-socket");
+        let retried = Arc::new(Mutex::new(0));
+        let policy = {
+            let retried = retried.clone();
+            RetryPolicy::fixed(2, Duration::from_millis(0))
+                .on_reconnect(move |_| *retried.lock().unwrap() += 1)
+        };
+        let error = I3Connection::builder()
+            .socket_path(missing.to_str().unwrap())
+            .retry(policy)
+            .connect()
+            .unwrap_err();
+        assert_eq!(error.socket_path(), missing.to_str());
+        assert!(error
+            .source()
+            .unwrap()
+            .to_string()
+            .contains("ConnectionBuilder::socket_path"));
+        assert_eq!(*retried.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn pipelined_requests() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(1, "[]");
+        i3.set_reply(4, TEST_TREE);
+        i3.set_reply(5, r#"["web"]"#);
+        let mut connection = i3.connect().unwrap();
+        let mut pipeline = connection.pipeline();
+        let tree = pipeline.get_tree();
+        let workspaces = pipeline.get_workspaces();
+        let marks = pipeline.get_marks();
+        let outputs = pipeline.get_outputs();
+        assert_eq!(pipeline.len(), 4);
+        let replies = pipeline.send().unwrap();
+        assert_eq!(replies.get(tree).unwrap().focused_leaf().id, 6);
+        assert!(replies.get(workspaces).unwrap().is_empty());
+        assert_eq!(replies.get(marks).unwrap().marks, vec!["web"]);
+        // the mock has no reply for outputs, so it answers with an error object.
+        assert!(replies.get(outputs).is_err());
+        let types: Vec<u32> = i3.requests().iter().map(|r| r.0).collect();
+        assert_eq!(types, vec![4, 1, 5, 3]);
+        assert!(connection.pipeline().send().is_ok());
+        assert!(connection.get_marks().is_ok());
+    }
+
+    #[test]
+    fn pipeline_timeout_drops_owed_replies() {
+        let (client, mut i3) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(client);
+        connection
+            .set_timeout(std::time::Duration::from_millis(10))
+            .unwrap();
+        let mut pipeline = connection.pipeline();
+        pipeline.get_marks();
+        pipeline.get_bar_ids();
+        pipeline.get_marks();
+        // only the first reply arrives in time.
+        i3.write_all(&crate::encode_message(5, "[]").unwrap())
+            .unwrap();
+        assert!(matches!(pipeline.send(), Err(MessageError::Receive { .. })));
+        i3.write_all(&crate::encode_message(6, "[]").unwrap())
+            .unwrap();
+        i3.write_all(&crate::encode_message(5, r#"["late"]"#).unwrap())
+            .unwrap();
+        i3.write_all(&crate::encode_message(5, r#"["fresh"]"#).unwrap())
+            .unwrap();
+        assert_eq!(connection.get_marks().unwrap().marks, vec!["fresh"]);
+    }
+
+    #[test]
+    fn per_request_timeout() {
+        use std::time::Duration;
+
+        let (client, mut i3) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(client);
+        match connection.run_command_timeout("nop", Duration::from_millis(10)) {
+            Err(MessageError::Receive { error: ref e, .. }) => assert!(crate::is_timeout(e)),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(connection.stream.socket.read_timeout().unwrap(), None);
+        assert_eq!(connection.stream.socket.write_timeout().unwrap(), None);
+
+        connection.set_timeout(Duration::from_secs(7)).unwrap();
+        i3.write_all(&crate::encode_message(0, r#"[{"success":false}]"#).unwrap())
+            .unwrap();
+        i3.write_all(&crate::encode_message(0, r#"[{"success":true}]"#).unwrap())
+            .unwrap();
+        let reply = connection
+            .run_command_timeout("nop", Duration::from_secs(5))
+            .unwrap();
+        assert!(reply.outcomes[0].success);
+        assert_eq!(
+            connection.stream.socket.read_timeout().unwrap(),
+            Some(Duration::from_secs(7))
+        );
+    }
+
+    #[derive(Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl crate::hook::MessageHook for Recorder {
+        fn before_send(&self, message_type: u32, payload: &str) {
+            let call = format!("send {} {}", message_type, payload);
+            self.0.lock().unwrap().push(call);
+        }
+
+        fn after_receive(&self, message_type: u32, payload: &str, _: std::time::Duration) {
+            let call = format!("receive {} {}", message_type, payload.len());
+            self.0.lock().unwrap().push(call);
+        }
+
+        fn on_error(&self, message_type: u32, _: &MessageError, _: std::time::Duration) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("error {}", message_type));
+        }
+
+        fn on_event(&self, event_type: u32, payload: &str) {
+            let call = format!("event {} {}", event_type, payload);
+            self.0.lock().unwrap().push(call);
+        }
     }
 
-    /// Gets the configuration of the workspace bar with the given ID.
-    pub fn get_bar_config(&mut self, id: &str) -> Result<reply::BarConfig, MessageError> {
-        let ids: json::Value = self.stream.send_receive_i3_message(6, id)?;
-        Ok(common::build_bar_config(&ids))
+    #[test]
+    fn message_hook() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(5, r#"["web"]"#);
+        i3.set_reply(1, "nope");
+        let mut connection = i3.connect().unwrap();
+        let recorder = Recorder::default();
+        let calls = recorder.0.clone();
+        connection.set_hook(recorder);
+        connection.get_marks().unwrap();
+        assert!(connection.get_workspaces().is_err());
+        let mut pipeline = connection.pipeline();
+        pipeline.get_marks();
+        pipeline.run_command("nop");
+        pipeline.send().unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "send 5 ",
+                "receive 5 7",
+                "send 1 ",
+                "receive 1 4",
+                "error 1",
+                "send 5 ",
+                "send 0 nop",
+                "receive 5 7",
+                "receive 0 43",
+            ]
+        );
+
+        let mut listener = i3.connect_listener().unwrap();
+        let recorder = Recorder::default();
+        let calls = recorder.0.clone();
+        listener.set_hook(recorder);
+        listener.subscribe(&[Subscription::Mode]).unwrap();
+        i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+        listener.listen().next().unwrap().unwrap();
+        let calls = calls.lock().unwrap();
+        assert!(calls[0].starts_with("send 2 ") && calls[0].contains("mode"));
+        assert_eq!(calls.last().unwrap(), r#"event 2 {"change":"resize"}"#);
     }
 
-    /// Gets the version of i3. The reply will include the major, minor, patch and human-readable
-    /// version.
-    pub fn get_version(&mut self) -> Result<reply::Version, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(7, "")?;
-        Ok(reply::Version {
-            major: j.get("major").unwrap().as_i64().unwrap() as i32,
-            minor: j.get("minor").unwrap().as_i64().unwrap() as i32,
-            patch: j.get("patch").unwrap().as_i64().unwrap() as i32,
-            human_readable: j
-                .get("human_readable")
-                .unwrap()
-                .as_str()
-                .unwrap()
-                .to_owned(),
-            loaded_config_file_name: j
-                .get("loaded_config_file_name")
-                .unwrap()
-                .as_str()
-                .unwrap()
-                .to_owned(),
-        })
+    #[test]
+    #[cfg(feature = "deny-unknown-fields")]
+    fn deny_unknown_fields() {
+        let output = r#"{"name":"eDP-1","active":true,"primary":true,"current_workspace":"1",
+                         "rect":{"x":0,"y":0,"width":1920,"height":1080}SWAY}"#
+            .replace("SWAY", SWAY_OUTPUT_FIELDS);
+        assert!(serde_json::from_str::<reply::Output>(&output).is_ok());
+        let extra = output.replace(r#""active""#, r#""hdr":true,"active""#);
+        let error = serde_json::from_str::<reply::Output>(&extra).unwrap_err();
+        assert!(error.to_string().contains("unknown field `hdr`"));
+
+        let properties = r#"{"class":"URxvt","instance":"urxvt"}"#;
+        assert!(serde_json::from_str::<reply::WindowProperties>(properties).is_ok());
+        let extra = properties.replace(r#""class""#, r#""wm_icon":"x","class""#);
+        let error = serde_json::from_str::<reply::WindowProperties>(&extra).unwrap_err();
+        assert!(error.to_string().contains("unknown field `wm_icon`"));
     }
 
-    /// Gets the list of currently configured binding modes.
-    #[cfg(feature = "i3-4-13")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-13")))]
-    pub fn get_binding_modes(&mut self) -> Result<reply::BindingModes, MessageError> {
-        let modes: Vec<String> = self.stream.send_receive_i3_message(8, "")?;
-        Ok(reply::BindingModes { modes })
+    /// Replies as i3 4.20.1 sends them for a config with an included file and a bar that sets
+    /// every option, which parse in strict mode as well.
+    #[test]
+    fn real_i3_4_20_replies() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(
+            7,
+            r#"{"major":4,"minor":20,"patch":1,"human_readable":"4.20.1 (2021-11-03)",
+                "loaded_config_file_name":"/home/user/.config/i3/config",
+                "included_config_file_names":["/home/user/.config/i3/bar.conf"]}"#,
+        );
+        i3.set_reply(
+            6,
+            r##"{"id":"bar-0","tray_outputs":["primary"],"tray_padding":2,"mode":"dock",
+                 "hidden_state":"hide","modifier":"Mod4",
+                 "bindings":[{"input_code":4,"command":"workspace prev","release":false}],
+                 "position":"bottom","i3bar_command":"/usr/bin/i3bar",
+                 "status_command":"i3status","workspace_command":"i3-workspaces",
+                 "font":"pango:monospace 8","bar_height":24,"separator_symbol":" | ",
+                 "workspace_buttons":true,"workspace_min_width":40,
+                 "strip_workspace_numbers":true,"strip_workspace_name":false,
+                 "binding_mode_indicator":true,"verbose":false,
+                 "colors":{"background":"#000000","statusline":"#ffffff",
+                           "separator":"#666666","focused_workspace_border":"#4c7899",
+                           "focused_workspace_bg":"#285577",
+                           "focused_workspace_text":"#ffffff",
+                           "binding_mode_border":"#2f343a","binding_mode_bg":"#900000",
+                           "binding_mode_text":"#ffffff"}}"##,
+        );
+        let mut connection = I3Connection::builder()
+            .socket_path(i3.socket_path().to_str().unwrap())
+            .connect()
+            .unwrap();
+        let version = connection.get_version().unwrap();
+        assert_eq!(
+            version.included_config_file_names,
+            ["/home/user/.config/i3/bar.conf"]
+        );
+        assert!(connection.capabilities().unwrap().at_least(4, 20, 0));
+
+        let bar = connection.get_bar_config("bar-0").unwrap();
+        assert_eq!(bar.i3bar_command.as_deref(), Some("/usr/bin/i3bar"));
+        assert_eq!(bar.workspace_command.as_deref(), Some("i3-workspaces"));
+        assert_eq!(bar.bar_height, Some(24));
+        assert!(bar.strip_workspace_numbers && !bar.strip_workspace_name);
     }
 
-    /// Returns the last loaded i3 config.
-    #[cfg(feature = "i3-4-14")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
-    pub fn get_config(&mut self) -> Result<reply::Config, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(9, "")?;
-        let cfg = j.get("config").unwrap().as_str().unwrap();
-        Ok(reply::Config {
-            config: cfg.to_owned(),
-        })
+    #[test]
+    fn barrier() {
+        let i3 = Arc::new(MockI3::start().unwrap());
+        i3.set_reply(10, r#"{"success":true}"#);
+        let mut connection = i3.connect().unwrap();
+        let mut listener = i3.connect_listener().unwrap();
+        let ticker = {
+            let i3 = i3.clone();
+            std::thread::spawn(move || {
+                let payload = loop {
+                    if let Some(tick) = i3.requests().into_iter().find(|r| r.0 == 10) {
+                        break tick.1;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                };
+                // an older tick with another payload doesn't count.
+                i3.push_event(7, r#"{"first":false,"payload":"other"}"#)
+                    .unwrap();
+                let tick = serde_json::json!({ "first": false, "payload": payload });
+                i3.push_event(7, &tick.to_string()).unwrap();
+                payload
+            })
+        };
+        let timeout = std::time::Duration::from_secs(5);
+        assert!(connection.barrier(&mut listener, timeout).unwrap());
+        let first = ticker.join().unwrap();
+        assert!(first.starts_with("i3ipc-barrier-"));
+
+        // nobody sends the tick this time.
+        let timeout = std::time::Duration::from_millis(20);
+        assert!(!connection.barrier(&mut listener, timeout).unwrap());
+        let ticks: Vec<String> = i3
+            .requests()
+            .into_iter()
+            .filter(|r| r.0 == 10)
+            .map(|r| r.1)
+            .collect();
+        assert_eq!(ticks.len(), 2);
+        assert_ne!(ticks[0], ticks[1]);
+
+        i3.set_reply(10, r#"{"success":false}"#);
+        assert!(!connection.barrier(&mut listener, timeout).unwrap());
     }
-}
 
-#[cfg(test)]
-mod test {
-    use event;
-    use std::str::FromStr;
-    use I3Connection;
-    use I3EventListener;
-    use Subscription;
+    #[test]
+    fn debounce() {
+        let tree: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        let split = &tree["nodes"][0]["nodes"][0]["nodes"][0];
+        let window = |change: &str, index: usize, name: &str| {
+            let mut container = split["nodes"][index].clone();
+            container["name"] = name.into();
+            serde_json::json!({ "change": change, "container": container }).to_string()
+        };
+        let summary = |event: event::Event| match event {
+            event::Event::WindowEvent(info) => format!(
+                "{:?} {} {}",
+                info.change,
+                info.container.id,
+                info.container.name.unwrap()
+            ),
+            event::Event::ModeEvent(info) => format!("mode {}", info.change),
+            other => panic!("unexpected {:?}", other),
+        };
 
-    // for the following tests send a request and get the reponse.
-    // response types are specific so often getting them at all indicates success.
-    // can't do much better without mocking an i3 installation.
+        let i3 = MockI3::start().unwrap();
+        let mut listener = i3.connect_listener().unwrap();
+        listener
+            .subscribe(&[Subscription::Window, Subscription::Mode])
+            .unwrap();
+        i3.push_event(3, &window("title", 1, "a")).unwrap();
+        i3.push_event(3, &window("title", 0, "web")).unwrap();
+        i3.push_event(3, &window("title", 1, "b")).unwrap();
+        i3.push_event(3, &window("focus", 0, "web")).unwrap();
+        i3.push_event(3, &window("focus", 1, "b")).unwrap();
+        i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+        i3.push_event(2, r#"{"change":"default"}"#).unwrap();
+        let events: Vec<String> = listener
+            .debounce(std::time::Duration::from_millis(20))
+            .take(4)
+            .map(|event| summary(event.unwrap()))
+            .collect();
+        assert_eq!(
+            events,
+            vec!["Title 5 web", "Title 6 b", "Focus 6 b", "mode default"]
+        );
+        assert_eq!(listener.stream.socket.read_timeout().unwrap(), None);
+
+        i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+        i3.push_event(3, &window("title", 1, "a")).unwrap();
+        i3.push_event(3, &window("title", 1, "b")).unwrap();
+        i3.push_event(3, &window("title", 0, "web")).unwrap();
+        i3.push_event(3, &window("title", 1, "c")).unwrap();
+        i3.push_event(3, &window("focus", 1, "c")).unwrap();
+        i3.push_event(3, &window("focus", 0, "web")).unwrap();
+        let events: Vec<String> = listener
+            .coalesce_title_changes()
+            .take(5)
+            .map(|event| summary(event.unwrap()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "mode resize",
+                "Title 5 web",
+                "Title 6 c",
+                "Focus 6 c",
+                "Focus 5 web"
+            ]
+        );
+        assert_eq!(listener.stream.socket.read_timeout().unwrap(), None);
+    }
 
     #[test]
-    fn connect() {
-        I3Connection::connect().unwrap();
+    fn local_criteria() {
+        let json = TEST_TREE.replace(r#""id": 8,"#, r#""id": 8, "floating": "user_on","#);
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        let firefox = tree.find_by_id(5).unwrap();
+        let urxvt = tree.find_by_id(6).unwrap();
+        let htop = tree.find_by_id(8).unwrap();
+        let matching = |criteria: Criteria| {
+            [firefox, urxvt, htop]
+                .iter()
+                .filter(|node| criteria.matches(node))
+                .map(|node| node.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matching(Criteria::new()), vec![5, 6, 8]);
+        assert_eq!(matching(Criteria::new().class("^URxvt$")), vec![6, 8]);
+        assert_eq!(matching(Criteria::new().class("(?i)firefox")), vec![5]);
+        assert_eq!(matching(Criteria::new().class("fire|xvt")), vec![5, 6, 8]);
+        assert_eq!(matching(Criteria::new().class("[Ff]ire(fox)?$")), vec![5]);
+        assert_eq!(matching(Criteria::new().class("Fire(")), Vec::<i64>::new());
+        assert_eq!(matching(Criteria::new().con_id(6)), vec![6]);
+        assert_eq!(matching(Criteria::new().floating()), vec![8]);
+        assert_eq!(matching(Criteria::new().tiling()), vec![5, 6]);
+        assert_eq!(matching(Criteria::new().tiling().title("^~$")), vec![6]);
+        assert_eq!(matching(Criteria::new().workspace("1")), Vec::<i64>::new());
+
+        for (pattern, text, expected) in [
+            ("a{2,3}b", "caaab", true),
+            ("^a{2}$", "aaa", false),
+            (r"\d+\.\d", "v1.2", true),
+            (r"[^\s]x", " x", false),
+            ("x{y", "x{y", true),
+            ("(a|b)*c", "zababc", true),
+            ("^$", "", true),
+            (r"\bword", "word", false),
+            ("(a*)*b", "aab", true),
+            ("()*x|(a?){3}y", "aay", true),
+            ("^(ab|a)(bc|c)$", "abc", true),
+            ("(?i)^A[X-Z]{1,2}", "ayz", true),
+        ] {
+            let matched = crate::pattern::Pattern::new(pattern).is_some_and(|p| p.is_match(text));
+            assert_eq!(matched, expected, "{} on {}", pattern, text);
+        }
     }
 
     #[test]
-    fn run_command_nothing() {
-        let mut connection = I3Connection::connect().unwrap();
-        let result = connection.run_command("").unwrap();
-        assert_eq!(result.outcomes.len(), 0);
+    fn pattern_long_and_exponential() {
+        use crate::pattern::Pattern;
+
+        let long = "a".repeat(50_000);
+        assert!(Pattern::new("^a*$").unwrap().is_match(&long));
+        assert!(!Pattern::new("^a*b$").unwrap().is_match(&long));
+        assert!(Pattern::new("a{3}$").unwrap().is_match(&long));
+
+        // backtracking would try 2^n ways of matching these before failing.
+        let start = std::time::Instant::now();
+        let text = "a".repeat(5_000);
+        assert!(!Pattern::new("(a|a)*b").unwrap().is_match(&text));
+        assert!(!Pattern::new("^(a*)*b").unwrap().is_match(&text));
+        assert!(!Pattern::new("(a|aa)+$b").unwrap().is_match(&text));
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+        // patterns that would compile to huge programs don't match at all.
+        assert!(Pattern::new("(a{1000}){1000}").is_none());
+        assert!(Pattern::new("(){1000000000000}x").unwrap().is_match("x"));
+        assert!(Pattern::new(&format!("{}a{}", "(".repeat(100), ")".repeat(100))).is_none());
     }
 
     #[test]
-    fn run_command_single_sucess() {
-        let mut connection = I3Connection::connect().unwrap();
-        let a = connection.run_command("exec /bin/true").unwrap();
-        assert_eq!(a.outcomes.len(), 1);
-        assert!(a.outcomes[0].success);
+    fn filter_matching() {
+        let tree: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        let split = &tree["nodes"][0]["nodes"][0]["nodes"][0];
+        let i3 = MockI3::start().unwrap();
+        let mut listener = i3.connect_listener().unwrap();
+        listener
+            .subscribe(&[Subscription::Window, Subscription::Mode])
+            .unwrap();
+        for index in [0, 1] {
+            let event =
+                serde_json::json!({ "change": "title", "container": split["nodes"][index] });
+            i3.push_event(3, &event.to_string()).unwrap();
+        }
+        i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+        let mut events = listener
+            .listen()
+            .filter_matching(Criteria::new().class("URxvt"));
+        match events.next().unwrap().unwrap() {
+            event::Event::WindowEvent(info) => assert_eq!(info.container.id, 6),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            events.next().unwrap().unwrap(),
+            event::Event::ModeEvent(_)
+        ));
     }
 
     #[test]
-    fn run_command_multiple_success() {
-        let mut connection = I3Connection::connect().unwrap();
-        let result = connection
-            .run_command("exec /bin/true; exec /bin/true")
+    fn workspace_move() {
+        let i3 = MockI3::start().unwrap();
+        let workspaces = |output: &str| {
+            format!(
+                r#"[{{"num":1,"name":"1","visible":true,"focused":true,"urgent":false,
+                     "rect":{{"x":0,"y":0,"width":1920,"height":1080}},"output":"{}"}}]"#,
+                output
+            )
+        };
+        i3.set_reply(1, &workspaces("eDP-1"));
+        let mut tracker = WorkspaceTracker::new(i3.connect().unwrap()).unwrap();
+        assert!(tracker.last_move().is_none());
+
+        let tree: serde_json::Value = serde_json::from_str(TEST_TREE).unwrap();
+        let mut workspace = tree["nodes"][0]["nodes"][0].clone();
+        workspace["output"] = "HDMI-1".into();
+        let payload = serde_json::json!({ "change": "move", "current": workspace, "old": null });
+        let info = event::WorkspaceEventInfo::from_str(&payload.to_string()).unwrap();
+        assert_eq!(info.change, event::inner::WorkspaceChange::Move);
+        assert_eq!(info.new_output(), Some("HDMI-1"));
+        assert_eq!(info.old_output(), None);
+
+        i3.set_reply(1, &workspaces("HDMI-1"));
+        tracker
+            .handle_event(&event::Event::WorkspaceEvent(info))
             .unwrap();
-        assert_eq!(result.outcomes.len(), 2);
-        assert!(result.outcomes[0].success);
-        assert!(result.outcomes[1].success);
+        let moved = tracker.last_move().unwrap();
+        assert_eq!(moved.workspace, "1");
+        assert_eq!(moved.from.as_deref(), Some("eDP-1"));
+        assert_eq!(moved.to.as_deref(), Some("HDMI-1"));
     }
 
     #[test]
-    fn run_command_fail() {
-        let mut connection = I3Connection::connect().unwrap();
-        let result = connection.run_command("ThisIsClearlyNotACommand").unwrap();
-        assert_eq!(result.outcomes.len(), 1);
-        assert!(!result.outcomes[0].success);
+    fn listener_ends_after_exit() {
+        let i3 = MockI3::start().unwrap();
+        let mut listener = i3.connect_listener().unwrap();
+        listener
+            .subscribe(&[Subscription::Shutdown, Subscription::Mode])
+            .unwrap();
+        i3.push_event(6, r#"{"change":"restart"}"#).unwrap();
+        i3.push_event(6, r#"{"change":"exit"}"#).unwrap();
+        i3.push_event(2, r#"{"change":"default"}"#).unwrap();
+        let changes: Vec<event::inner::ShutdownChange> = listener
+            .listen()
+            .map(|event| match event.unwrap() {
+                event::Event::ShutdownEvent(info) => info.change,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                event::inner::ShutdownChange::Restart,
+                event::inner::ShutdownChange::Exit
+            ]
+        );
+        assert!(listener.was_shutdown());
+        assert!(listener.try_next().unwrap().is_none());
+        match listener.inspect_next(|_| ()) {
+            Err(MessageError::Receive { error: e, .. }) => {
+                assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
     }
 
     #[test]
-    fn get_workspaces() {
-        I3Connection::connect().unwrap().get_workspaces().unwrap();
+    fn criteria_matcher() {
+        let json = TEST_TREE
+            .replace(r#""id": 8,"#, r#""id": 8, "floating": "user_on","#)
+            .replace(r#""id": 4,"#, r#""id": 4, "marks": ["main"],"#);
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        let ids = |criteria: Criteria| {
+            criteria
+                .matcher()
+                .find_all(&tree)
+                .iter()
+                .map(|node| node.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(Criteria::new()), vec![5, 6, 8]);
+        assert_eq!(ids(Criteria::new().class("URxvt")), vec![6, 8]);
+        assert_eq!(ids(Criteria::new().class("URxvt").floating()), vec![8]);
+        assert_eq!(ids(Criteria::new().workspace("^1$").tiling()), vec![5, 6]);
+        assert_eq!(ids(Criteria::new().workspace("^2$")), Vec::<i64>::new());
+        // marks and container ids match split containers too.
+        assert_eq!(ids(Criteria::new().con_mark("^main$")), vec![4]);
+        assert_eq!(ids(Criteria::new().con_id(7)), vec![7]);
+
+        let matcher = Criteria::new().instance("^urxvt$").workspace("1").matcher();
+        let urxvt = tree.find_by_id(6).unwrap();
+        assert!(matcher.matches_on(urxvt, "1"));
+        assert!(!matcher.matches_on(urxvt, "2"));
+        assert!(!matcher.matches(urxvt));
+        assert!(Criteria::new().instance("^urxvt$").matcher().matches(urxvt));
     }
 
     #[test]
-    fn get_outputs() {
-        I3Connection::connect().unwrap().get_outputs().unwrap();
+    fn swallow_criteria() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let mut firefox = tree.find_by_id(5).unwrap().clone();
+        let properties = firefox.window_properties.as_mut().unwrap();
+        properties.title = Some("github.com - Mozilla Firefox".to_owned());
+        let swallows = Swallows::for_window(&firefox);
+        assert_eq!(
+            swallows.recommended,
+            vec![
+                ("class", "^firefox$".to_owned()),
+                ("instance", "^Navigator$".to_owned())
+            ]
+        );
+        assert_eq!(
+            swallows.to_string(),
+            r#"{
+    // "title": "^github\\.com - Mozilla Firefox$",
+    "class": "^firefox$",
+    "instance": "^Navigator$"
+}"#
+        );
+        assert_eq!(
+            swallows.to_json(),
+            serde_json::json!({ "class": "^firefox$", "instance": "^Navigator$" })
+        );
+
+        let properties = firefox.window_properties.as_mut().unwrap();
+        properties.class = None;
+        properties.instance = None;
+        let swallows = Swallows::for_window(&firefox);
+        assert_eq!(swallows.recommended[0].0, "title");
+        assert!(swallows.alternatives.is_empty());
     }
 
     #[test]
-    fn get_tree() {
-        I3Connection::connect().unwrap().get_tree().unwrap();
+    fn tree_to_dot() {
+        let json = TEST_TREE.replace(r#""name": "~""#, r#""name": "say \"hi\"""#);
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            tree.find_by_id(3).unwrap().to_dot(),
+            concat!(
+                "digraph tree {\n",
+                "    node [shape=box];\n",
+                "    \"3\" [label=\"workspace splith\\n1\\n#3\"];\n",
+                "    \"3\" -> \"4\" [color=red, penwidth=2];\n",
+                "    \"3\" -> \"7\" [style=dashed];\n",
+                "    \"4\" [label=\"con splith\\n#4\"];\n",
+                "    \"4\" -> \"5\";\n",
+                "    \"4\" -> \"6\" [color=red, penwidth=2];\n",
+                "    \"5\" [label=\"con splith\\nMozilla Firefox\\n#5\"];\n",
+                "    \"6\" [label=\"con splith\\nsay \\\"hi\\\"\\n#6\", style=filled];\n",
+                "    \"7\" [label=\"floating_con splith\\n#7\"];\n",
+                "    \"7\" -> \"8\" [color=red, penwidth=2];\n",
+                "    \"8\" [label=\"con splith\\nhtop\\n#8\"];\n",
+                "}\n",
+            )
+        );
     }
 
     #[test]
-    fn get_marks() {
-        I3Connection::connect().unwrap().get_marks().unwrap();
+    fn flatten_tree() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let flat: Vec<(usize, Vec<i64>, i64)> = tree
+            .flatten()
+            .map(|(depth, path, node)| (depth, path, node.id))
+            .collect();
+        assert_eq!(
+            flat,
+            vec![
+                (0, vec![1], 1),
+                (1, vec![1, 2], 2),
+                (2, vec![1, 2, 3], 3),
+                (3, vec![1, 2, 3, 4], 4),
+                (4, vec![1, 2, 3, 4, 5], 5),
+                (4, vec![1, 2, 3, 4, 6], 6),
+                (3, vec![1, 2, 3, 7], 7),
+                (4, vec![1, 2, 3, 7, 8], 8),
+            ]
+        );
+        let workspace = tree.find_by_id(3).unwrap();
+        assert_eq!(workspace.flatten().next().unwrap().0, 0);
+
+        let mut jsonl = Vec::new();
+        tree.write_windows_jsonl(&mut jsonl).unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(jsonl)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let ids: Vec<_> = lines
+            .iter()
+            .map(|line| line["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(ids, vec![5, 6, 8]);
+        assert_eq!(lines[0]["class"], "firefox");
+        assert_eq!(lines[0]["instance"], "Navigator");
+        assert_eq!(lines[0]["workspace"], "1");
+        assert_eq!(lines[1]["focused"], true);
+        assert_eq!(lines[2]["depth"], 4);
+        assert_eq!(lines[2]["path"], serde_json::json!([1, 2, 3, 7, 8]));
     }
 
     #[test]
-    fn get_bar_ids() {
-        I3Connection::connect().unwrap().get_bar_ids().unwrap();
+    fn windows_per_workspace() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let ids: Vec<i64> = tree
+            .windows_on_workspace("1")
+            .iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(ids, vec![5, 6, 8]);
+        assert!(tree.windows_on_workspace("2").is_empty());
+        assert_eq!(tree.workspace_of_window(8388611).unwrap().id, 3);
+        assert!(tree.workspace_of_window(1).is_none());
+        assert_eq!(tree.window_counts_per_workspace(), vec![("1", 3)]);
     }
 
     #[test]
-    fn get_bar_ids_and_one_config() {
-        let mut connection = I3Connection::connect().unwrap();
-        let ids = connection.get_bar_ids().unwrap().ids;
-        connection.get_bar_config(&ids[0]).unwrap();
+    fn urgent_windows() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        assert!(tree.urgent_windows().is_empty());
+
+        let json = TEST_TREE.replace(
+            r#""window": 8388611, "urgent": false"#,
+            r#""window": 8388611, "urgent": true"#,
+        );
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        let urgent: Vec<(i64, i64)> = tree
+            .urgent_windows()
+            .iter()
+            .map(|(workspace, window)| (workspace.id, window.id))
+            .collect();
+        assert_eq!(urgent, vec![(3, 8)]);
+
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(4, &json);
+        let mut connection = i3.connect().unwrap();
+        let urgent = connection.get_urgent_windows().unwrap();
+        assert_eq!(urgent.len(), 1);
+        assert_eq!(urgent[0].0, "1");
+        assert_eq!(urgent[0].1.id, 8);
     }
 
     #[test]
-    fn get_version() {
-        I3Connection::connect().unwrap().get_version().unwrap();
+    fn autotiling() {
+        let tall = reply::Rect {
+            x: 0,
+            y: 0,
+            width: 800,
+            height: 1200,
+        };
+        assert_eq!(autotiling::split_for(&tall).as_str(), "split v");
+        let wide = reply::Rect {
+            width: 1200,
+            height: 800,
+            ..tall
+        };
+        assert_eq!(autotiling::split_for(&wide).as_str(), "split h");
+
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let split = autotiling::recommended_split(&tree).unwrap();
+        assert_eq!(split.as_str(), "split v");
+
+        let json = TEST_TREE.replace(
+            r#""x": 800, "y": 0, "width": 800, "height": 1200"#,
+            r#""x": 800, "y": 0, "width": 800, "height": 600"#,
+        );
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        assert!(autotiling::recommended_split(&tree).is_none());
+
+        let json = TEST_TREE.replace(
+            r#""current_border_width": 2, "layout": "splith", "percent": 1.0,"#,
+            r#""current_border_width": 2, "layout": "tabbed", "percent": 1.0,"#,
+        );
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        assert!(autotiling::recommended_split(&tree).is_none());
+
+        let json = TEST_TREE
+            .replace(r#""focused": true"#, r#""focused": false"#)
+            .replace(
+                r#""window": 8388611, "urgent": false, "focused": false"#,
+                r#""window": 8388611, "urgent": false, "focused": true"#,
+            )
+            .replace(r#""focus": [4, 7]"#, r#""focus": [7, 4]"#);
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        assert_eq!(tree.find_focused().unwrap().id, 8);
+        assert!(autotiling::recommended_split(&tree).is_none());
     }
 
-    #[cfg(feature = "i3-4-13")]
     #[test]
-    fn get_binding_modes() {
-        I3Connection::connect()
-            .unwrap()
-            .get_binding_modes()
+    fn output_tracker() {
+        let output = |name: &str, active: bool, x: i32| {
+            format!(
+                r#"{{"name":"{}","active":{},"primary":false,"current_workspace":null,
+                     "rect":{{"x":{},"y":0,"width":1920,"height":1080}}SWAY}}"#,
+                name, active, x
+            )
+            .replace("SWAY", SWAY_OUTPUT_FIELDS)
+        };
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(
+            3,
+            &format!(
+                "[{},{}]",
+                output("eDP-1", true, 0),
+                output("DP-1", true, 1920)
+            ),
+        );
+        let mut tracker = OutputTracker::new(i3.connect().unwrap()).unwrap();
+        assert_eq!(tracker.outputs().len(), 2);
+
+        let event = event::Event::OutputEvent(
+            event::OutputEventInfo::from_str(r#"{"change":"unspecified"}"#).unwrap(),
+        );
+        assert!(tracker.handle_event(&event).unwrap().is_empty());
+
+        i3.set_reply(
+            3,
+            &format!(
+                "[{},{}]",
+                output("eDP-1", false, 0),
+                output("HDMI-1", true, 1920)
+            ),
+        );
+        let updates = tracker.handle_event(&event).unwrap();
+        assert_eq!(updates.len(), 3);
+        match &updates[0] {
+            OutputUpdate::Changed { old, new } => {
+                assert_eq!(old.name, "eDP-1");
+                assert!(old.active && !new.active);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match &updates[1] {
+            OutputUpdate::Added(output) => assert_eq!(output.name, "HDMI-1"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match &updates[2] {
+            OutputUpdate::Removed(output) => assert_eq!(output.name, "DP-1"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(tracker.get("DP-1").is_none());
+
+        let requests = i3.requests().len();
+        let mode = event::ModeEventInfo::from_str(r#"{ "change": "default" }"#).unwrap();
+        let updates = tracker
+            .handle_event(&event::Event::ModeEvent(mode))
             .unwrap();
+        assert!(updates.is_empty());
+        assert_eq!(i3.requests().len(), requests);
     }
 
-    #[cfg(feature = "i3-4-14")]
     #[test]
-    fn get_config() {
-        I3Connection::connect().unwrap().get_config().unwrap();
+    fn previous_workspace() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(
+            1,
+            r#"[{"num":1,"name":"1","visible":true,"focused":true,"urgent":false,
+                 "rect":{"x":0,"y":0,"width":1920,"height":1080},"output":"eDP-1"}]"#,
+        );
+        i3.set_reply(0, r#"[{"success":true}]"#);
+        let mut tracker = PreviousWorkspace::new(i3.connect().unwrap()).unwrap();
+        assert_eq!(tracker.current(), Some("1"));
+        assert!(tracker.previous().is_none());
+        assert!(tracker.switch_back().unwrap().is_none());
+
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let workspace = |id: i64, name: &str| {
+            let mut workspace = tree.find_by_id(3).unwrap().clone();
+            workspace.id = id;
+            workspace.name = Some(name.to_owned());
+            workspace
+        };
+        let event = |change, current| {
+            event::Event::WorkspaceEvent(event::WorkspaceEventInfo {
+                change,
+                current: Some(current),
+                old: None,
+            })
+        };
+        use event::inner::WorkspaceChange;
+        tracker.handle_event(&event(WorkspaceChange::Focus, workspace(20, "2")));
+        tracker.handle_event(&event(WorkspaceChange::Focus, workspace(30, "3")));
+        tracker.handle_event(&event(WorkspaceChange::Focus, workspace(10, "1")));
+        assert_eq!(tracker.history().collect::<Vec<_>>(), vec!["1", "3", "2"]);
+        assert_eq!(tracker.previous(), Some("3"));
+        assert_eq!(tracker.nth_previous(2), Some("2"));
+        assert!(tracker.nth_previous(3).is_none());
+
+        tracker.handle_event(&event(WorkspaceChange::Rename, workspace(30, "3: web")));
+        tracker.handle_event(&event(WorkspaceChange::Urgent, workspace(20, "2")));
+        assert_eq!(
+            tracker.history().collect::<Vec<_>>(),
+            vec!["1", "3: web", "2"]
+        );
+
+        assert!(tracker.switch_back().unwrap().unwrap().outcomes[0].success);
+        assert!(tracker.switch_back_by(2).unwrap().is_some());
+        let requests = i3.requests();
+        assert_eq!(
+            requests[requests.len() - 2],
+            (0, r#"workspace "3: web""#.to_owned())
+        );
+        assert_eq!(
+            requests[requests.len() - 1],
+            (0, r#"workspace "2""#.to_owned())
+        );
     }
 
     #[test]
-    fn event_subscribe() {
-        let s = I3EventListener::connect()
-            .unwrap()
-            .subscribe(&[Subscription::Workspace])
-            .unwrap();
-        assert_eq!(s.success, true);
+    fn window_cycling() {
+        let tree: reply::Node = serde_json::from_str(TEST_TREE).unwrap();
+        let ids = |windows: Vec<&reply::Node>| windows.iter().map(|w| w.id).collect::<Vec<_>>();
+        assert_eq!(ids(tree.windows_by_focus()), vec![6, 5, 8]);
+
+        let mut history = FocusHistory::new();
+        let all = WindowFilter::All;
+        assert_eq!(
+            history.next_window(&tree, &all).unwrap().as_str(),
+            r#"[con_id="5"] focus"#
+        );
+        assert_eq!(
+            history.prev_window(&tree, &all).unwrap().as_str(),
+            r#"[con_id="8"] focus"#
+        );
+
+        let window_event = |change, id| {
+            let mut container = tree.find_by_id(id).unwrap().clone();
+            container.focused = change == event::inner::WindowChange::Focus;
+            event::Event::WindowEvent(event::WindowEventInfo { change, container })
+        };
+        history.handle_event(&window_event(event::inner::WindowChange::Focus, 8));
+        history.handle_event(&window_event(event::inner::WindowChange::Focus, 6));
+        assert_eq!(history.history(), &[6, 8]);
+        assert_eq!(ids(history.windows(&tree, &all)), vec![6, 8, 5]);
+        assert_eq!(
+            history.next_window(&tree, &all).unwrap().as_str(),
+            r#"[con_id="8"] focus"#
+        );
+        assert_eq!(
+            history.prev_window(&tree, &all).unwrap().as_str(),
+            r#"[con_id="5"] focus"#
+        );
+        let current = WindowFilter::CurrentWorkspace;
+        assert_eq!(ids(history.windows(&tree, &current)), vec![6, 8, 5]);
+
+        let firefox = WindowFilter::Class("firefox".to_owned());
+        assert_eq!(
+            history.next_window(&tree, &firefox).unwrap().as_str(),
+            r#"[con_id="5"] focus"#
+        );
+        let urxvt = WindowFilter::Class("URxvt".to_owned());
+        assert_eq!(
+            history.prev_window(&tree, &urxvt).unwrap().as_str(),
+            r#"[con_id="8"] focus"#
+        );
+        let none = WindowFilter::Class("xterm".to_owned());
+        assert!(history.next_window(&tree, &none).is_none());
+
+        history.handle_event(&window_event(event::inner::WindowChange::Close, 8));
+        assert_eq!(history.history(), &[6]);
     }
 
     #[test]
-    fn from_str_workspace() {
-        let json_str = r##"
-        {
-            "change": "focus",
-            "current": {
-                "id": 28489712,
-                "name": "something",
-                "type": "workspace",
-                "border": "normal",
-                "current_border_width": 2,
-                "layout": "splith",
-                "orientation": "none",
-                "percent": 30.0,
-                "rect": { "x": 1600, "y": 0, "width": 1600, "height": 1200 },
-                "window_rect": { "x": 2, "y": 0, "width": 632, "height": 366 },
-                "deco_rect": { "x": 1, "y": 1, "width": 631, "height": 365 },
-                "geometry": { "x": 6, "y": 6, "width": 10, "height": 10 },
-                "window": 1,
-                "urgent": false,
-                "focused": true
-            },
-            "old": null
-        }"##;
-        event::WorkspaceEventInfo::from_str(json_str).unwrap();
+    fn swap_windows() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(4, TEST_TREE);
+        i3.set_reply(0, r#"[{"success":true}]"#);
+        let mut connection = i3.connect().unwrap();
+        assert!(connection.swap_windows(5, 8).unwrap().outcomes[0].success);
+        assert_eq!(
+            i3.requests().last().unwrap(),
+            &(0, r#"[con_id="5"] swap container with con_id 8"#.to_owned())
+        );
+
+        let requests = i3.requests().len();
+        match connection.swap_windows(5, 42) {
+            Err(MessageError::NoSuchContainer(42)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(i3.requests().len(), requests + 1);
+        assert_eq!(i3.requests().last().unwrap().0, 4);
     }
 
     #[test]
-    fn from_str_output() {
-        let json_str = r##"{ "change": "unspecified" }"##;
-        event::OutputEventInfo::from_str(json_str).unwrap();
+    fn from_str_tree_with_i3_fields() {
+        let json = TEST_TREE.replace(
+            r#""id": 3, "name": "1", "type": "workspace","#,
+            r#""id": 3, "name": "1", "type": "workspace", "num": 1, "orientation": "horizontal",
+               "scratchpad_state": "none", "workspace_layout": "default",
+               "last_split_layout": "splith", "window_icon_padding": -1, "title_format": null,
+               "swallows": [{ "class": "^URxvt$", "dock": 2 }],"#,
+        );
+        let tree: reply::Node = serde_json::from_str(&json).unwrap();
+        let workspace = tree.find_by_id(3).unwrap();
+        assert_eq!(workspace.num, Some(1));
+        assert_eq!(workspace.orientation.as_deref(), Some("horizontal"));
+        assert_eq!(workspace.scratchpad_state.as_deref(), Some("none"));
+        assert_eq!(workspace.workspace_layout.as_deref(), Some("default"));
+        assert_eq!(workspace.last_split_layout.as_deref(), Some("splith"));
+        assert_eq!(workspace.window_icon_padding, Some(-1));
+        assert_eq!(workspace.swallows[0]["class"], "^URxvt$");
+        assert!(tree.find_by_id(4).unwrap().swallows.is_empty());
+        assert_eq!(tree.find_by_id(4).unwrap().num, None);
+        let json_tree = serde_json::to_value(&tree).unwrap();
+        assert_eq!(json_tree["nodes"][0]["nodes"][0]["num"], 1);
+        assert!(json_tree["nodes"][0]["nodes"][0]["nodes"][0]
+            .get("num")
+            .is_none());
+
+        let named: reply::Node =
+            serde_json::from_str(&json.replace(r#""num": 1"#, r#""num": -1"#)).unwrap();
+        assert_eq!(named.find_by_id(3).unwrap().num, None);
     }
 
     #[test]
-    fn from_str_mode() {
-        let json_str = r##"{ "change": "default" }"##;
-        event::ModeEventInfo::from_str(json_str).unwrap();
+    fn reconnected_listener_keeps_settings() {
+        struct Counter(Arc<Mutex<usize>>);
+        impl crate::hook::MessageHook for Counter {
+            fn before_send(&self, _: u32, _: &str) {
+                *self.0.lock().unwrap() += 1;
+            }
+        }
+        let i3 = MockI3::start().unwrap();
+        let mut old = i3.connect_listener().unwrap();
+        let sent = Arc::new(Mutex::new(0));
+        old.set_timeout(std::time::Duration::from_millis(250))
+            .unwrap();
+        old.set_lossy(true);
+        old.set_resynchronize(true);
+        old.set_max_tree_depth(7);
+        old.set_hook(Counter(sent.clone()));
+
+        let mut new = i3.connect_listener().unwrap();
+        new.take_settings_from(&old).unwrap();
+        // The kernel rounds timeouts, so compare with what the old socket reports.
+        assert!(new.stream.socket.read_timeout().unwrap().is_some());
+        assert_eq!(
+            new.stream.socket.read_timeout().unwrap(),
+            old.stream.socket.read_timeout().unwrap()
+        );
+        assert_eq!(
+            new.stream.socket.write_timeout().unwrap(),
+            old.stream.socket.write_timeout().unwrap()
+        );
+        assert!(new.lossy && new.stream.resync);
+        assert_eq!(new.stream.max_tree_depth, 7);
+        new.subscribe(&[Subscription::Mode]).unwrap();
+        assert_eq!(*sent.lock().unwrap(), 1);
     }
 
     #[test]
-    fn from_str_window() {
-        let json_str = r##"
-        {
-            "change": "new",
-            "container": {
-                "id": 28489712,
-                "name": "something",
-                "type": "workspace",
-                "border": "normal",
-                "current_border_width": 2,
-                "layout": "splith",
-                "orientation": "none",
-                "percent": 30.0,
-                "rect": { "x": 1600, "y": 0, "width": 1600, "height": 1200 },
-                "window_rect": { "x": 2, "y": 0, "width": 632, "height": 366 },
-                "deco_rect": { "x": 1, "y": 1, "width": 631, "height": 365 },
-                "geometry": { "x": 6, "y": 6, "width": 10, "height": 10 },
-                "window": 1,
-                "window_properties": { "class": "Firefox", "instance": "Navigator", "window_role": "browser", "title": "github.com - Mozilla Firefox", "transient_for": null },
-                "urgent": false,
-                "focused": true
-            }
-        }"##;
-        event::WindowEventInfo::from_str(json_str).unwrap();
+    fn send_error_names_message_type() {
+        let (stream, i3) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(stream);
+        drop(i3);
+        match connection.get_outputs() {
+            Err(e @ MessageError::Send { .. }) => assert_eq!(e.message_type(), Some(3)),
+            other => panic!("unexpected {:?}", other),
+        }
     }
 
     #[test]
-    fn from_str_barconfig() {
-        let json_str = r##"
-        {
-            "id": "bar-bxuqzf",
-            "mode": "dock",
-            "position": "bottom",
-            "status_command": "i3status",
-            "font": "-misc-fixed-medium-r-normal--13-120-75-75-C-70-iso10646-1",
-            "workspace_buttons": true,
-            "binding_mode_indicator": true,
-            "verbose": false,
-            "colors": {
-                    "background": "#c0c0c0",
-                    "statusline": "#00ff00",
-                    "focused_workspace_text": "#ffffff",
-                    "focused_workspace_bg": "#000000"
+    fn debounce_steady_traffic() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::{Duration, Instant};
+
+        let i3 = MockI3::start().unwrap();
+        let mut listener = i3.connect_listener().unwrap();
+        listener.subscribe(&[Subscription::Mode]).unwrap();
+        let stop = AtomicBool::new(false);
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            // stops on its own, so that a listener that holds the burst back fails the test
+            // instead of hanging it.
+            scope.spawn(|| {
+                while !stop.load(Ordering::SeqCst) && start.elapsed() < Duration::from_secs(3) {
+                    i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+                    std::thread::sleep(Duration::from_millis(2));
+                }
+            });
+            let event = listener
+                .debounce(Duration::from_millis(50))
+                .max_wait(Duration::from_millis(200))
+                .next()
+                .unwrap();
+            let elapsed = start.elapsed();
+            stop.store(true, Ordering::SeqCst);
+            match event.unwrap() {
+                event::Event::ModeEvent(info) => assert_eq!(info.change, "resize"),
+                other => panic!("unexpected {:?}", other),
             }
-        }"##;
-        event::BarConfigEventInfo::from_str(json_str).unwrap();
+            assert!(
+                elapsed < Duration::from_secs(2),
+                "held back for {:?}",
+                elapsed
+            );
+        });
+        assert_eq!(listener.stream.socket.read_timeout().unwrap(), None);
     }
 
     #[test]
-    fn from_str_binding_event() {
-        let json_str = r##"
-        {
-            "change": "run",
-            "binding": {
-                "command": "nop",
-                "event_state_mask": [
-                    "shift",
-                    "ctrl"
-                ],
-                "input_code": 0,
-                "symbol": "t",
-                "input_type": "keyboard"
-            }
-        }"##;
-        event::BindingEventInfo::from_str(json_str).unwrap();
+    fn unsupported_messages_not_sent() {
+        let i3 = MockI3::start().unwrap();
+        i3.set_reply(
+            7,
+            r#"{"major":4,"minor":12,"patch":0,"human_readable":"4.12",
+                "loaded_config_file_name":"/etc/i3/config"}"#,
+        );
+        let mut connection = I3Connection::builder()
+            .socket_path(i3.socket_path().to_str().unwrap())
+            .connect()
+            .unwrap();
+        match connection.get_config() {
+            Err(MessageError::Unsupported(9)) => {}
+            other => panic!("expected Unsupported(9), got {:?}", other),
+        }
+        assert!(connection.send_tick("").is_err());
+        let mut pipeline = connection.pipeline();
+        pipeline.get_workspaces();
+        pipeline.get_binding_state();
+        match pipeline.send() {
+            Err(MessageError::Unsupported(12)) => {}
+            other => panic!("expected Unsupported(12), got {:?}", other),
+        }
+        assert_eq!(
+            MessageError::Unsupported(9).to_string(),
+            "The running i3 doesn't support message type 9"
+        );
+        assert!(i3
+            .requests()
+            .iter()
+            .all(|(message_type, _)| *message_type == 7));
+        assert!(connection.get_workspaces().is_err());
+        assert_eq!(i3.requests().last().unwrap().0, 1);
     }
 }
@@ -0,0 +1,488 @@
+//! The part of PCRE that criteria values use in practice, for matching criteria locally.
+//!
+//! Supported are literal text, `.`, `^`, `$`, character classes like `[a-z]` and `[^0-9]`,
+//! the escapes `\d`, `\w`, `\s` (and their negations), groups with `|`, the quantifiers `*`,
+//! `+`, `?` and `{n,m}`, and a leading `(?i)` for matching without regard to case. Like i3,
+//! a pattern matches anywhere in the text unless anchored. A pattern using anything else
+//! doesn't match at all, and neither does one nested or repeated so much that it compiles to
+//! more than `MAX_PROGRAM` instructions.
+//!
+//! Patterns are compiled to a program that is run for all ways of matching at once, by moving
+//! through the text a character at a time, so matching takes time linear in the length of the
+//! text and doesn't recurse, however the pattern is written.
+
+/// A compiled pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Pattern {
+    program: Vec<Inst>,
+    ignore_case: bool,
+}
+
+/// How many instructions a pattern may compile to, which also bounds how many ways of matching
+/// are followed at once.
+const MAX_PROGRAM: usize = 10_000;
+
+/// How deeply groups may be nested in a pattern, as the parser recurses for every group.
+const MAX_NESTING: usize = 32;
+
+/// An instruction of a compiled pattern.
+#[derive(Debug, Clone, PartialEq)]
+enum Inst {
+    /// Consumes a character that the node (`Any`, `Char` or `Class`) accepts.
+    Single(Node),
+    /// Continues only at the start of the text.
+    Start,
+    /// Continues only at the end of the text.
+    End,
+    /// Continues at both instructions.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Piece {
+    node: Node,
+    min: usize,
+    max: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Start,
+    End,
+    Any,
+    Char(char),
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    Group(Vec<Vec<Piece>>),
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+const SPACE: &[(char, char)] = &[(' ', ' '), ('\t', '\r')];
+
+impl Pattern {
+    /// Compiles `pattern`, or returns `None` if it uses unsupported or invalid syntax.
+    pub(crate) fn new(pattern: &str) -> Option<Pattern> {
+        let (pattern, ignore_case) = match pattern.strip_prefix("(?i)") {
+            Some(rest) => (rest, true),
+            None => (pattern, false),
+        };
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut parser = Parser {
+            chars,
+            pos: 0,
+            nesting: 0,
+        };
+        let alternatives = parser.alternatives()?;
+        if parser.pos != parser.chars.len() {
+            return None;
+        }
+        let mut compiler = Compiler {
+            program: Vec::new(),
+        };
+        compiler.alternatives(&alternatives)?;
+        compiler.push(Inst::Match)?;
+        Some(Pattern {
+            program: compiler.program,
+            ignore_case,
+        })
+    }
+
+    /// Whether the pattern matches somewhere in `text`.
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut stack = Vec::new();
+        for pos in 0..=text.len() {
+            // a match may start anywhere, so a new way of matching starts at every position.
+            if self.follow(&mut current, &mut stack, 0, pos, text.len()) {
+                return true;
+            }
+            let c = match text.get(pos) {
+                Some(&c) => c,
+                None => break,
+            };
+            next.clear();
+            for &pc in &current.pcs {
+                if let Inst::Single(ref node) = self.program[pc] {
+                    if self.single(node, c)
+                        && self.follow(&mut next, &mut stack, pc + 1, pos + 1, text.len())
+                    {
+                        return true;
+                    }
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+        false
+    }
+
+    /// Adds `pc` to `threads` along with every instruction reachable from it without
+    /// consuming a character at `pos`. Returns whether that reaches `Match`.
+    fn follow(
+        &self,
+        threads: &mut Threads,
+        stack: &mut Vec<usize>,
+        pc: usize,
+        pos: usize,
+        len: usize,
+    ) -> bool {
+        stack.clear();
+        stack.push(pc);
+        while let Some(pc) = stack.pop() {
+            if !threads.insert(pc) {
+                continue;
+            }
+            match self.program[pc] {
+                Inst::Single(_) => {}
+                Inst::Start => {
+                    if pos == 0 {
+                        stack.push(pc + 1);
+                    }
+                }
+                Inst::End => {
+                    if pos == len {
+                        stack.push(pc + 1);
+                    }
+                }
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+                Inst::Jump(to) => stack.push(to),
+                Inst::Match => return true,
+            }
+        }
+        false
+    }
+
+    fn single(&self, node: &Node, c: char) -> bool {
+        match node {
+            Node::Any => c != '\n',
+            Node::Char(expected) => {
+                c == *expected || (self.ignore_case && lowercase(c) == lowercase(*expected))
+            }
+            Node::Class { negated, ranges } => {
+                let within = |c: char| ranges.iter().any(|&(low, high)| low <= c && c <= high);
+                let found = within(c)
+                    || (self.ignore_case && (within(lowercase(c)) || within(uppercase(c))));
+                found != *negated
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The instructions reached at one position of the text, each at most once.
+struct Threads {
+    pcs: Vec<usize>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            pcs: Vec::with_capacity(len),
+            seen: vec![false; len],
+        }
+    }
+
+    /// Adds `pc`, or returns false if it was already there.
+    fn insert(&mut self, pc: usize) -> bool {
+        if self.seen[pc] {
+            return false;
+        }
+        self.seen[pc] = true;
+        self.pcs.push(pc);
+        true
+    }
+
+    fn clear(&mut self) {
+        for &pc in &self.pcs {
+            self.seen[pc] = false;
+        }
+        self.pcs.clear();
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    nesting: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn alternatives(&mut self) -> Option<Vec<Vec<Piece>>> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Some(alternatives)
+    }
+
+    fn sequence(&mut self) -> Option<Vec<Piece>> {
+        let mut pieces = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let node = self.node()?;
+            let (min, max) = self.quantifier()?;
+            if (min, max) != (1, Some(1)) {
+                if let Node::Start | Node::End = node {
+                    return None;
+                }
+            }
+            pieces.push(Piece { node, min, max });
+        }
+        Some(pieces)
+    }
+
+    fn node(&mut self) -> Option<Node> {
+        Some(match self.next()? {
+            '^' => Node::Start,
+            '$' => Node::End,
+            '.' => Node::Any,
+            '(' => {
+                if self.nesting == MAX_NESTING {
+                    return None;
+                }
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                    if self.next()? != ':' {
+                        return None;
+                    }
+                }
+                self.nesting += 1;
+                let alternatives = self.alternatives()?;
+                self.nesting -= 1;
+                if self.next()? != ')' {
+                    return None;
+                }
+                Node::Group(alternatives)
+            }
+            '[' => self.class()?,
+            '\\' => match self.escape()? {
+                Ok(c) => Node::Char(c),
+                Err(class) => class,
+            },
+            '*' | '+' | '?' | ')' => return None,
+            c => Node::Char(c),
+        })
+    }
+
+    /// An escaped character, or the class it stands for.
+    fn escape(&mut self) -> Option<Result<char, Node>> {
+        let c = self.next()?;
+        let class = |negated, ranges: &[(char, char)]| {
+            Err(Node::Class {
+                negated,
+                ranges: ranges.to_vec(),
+            })
+        };
+        Some(match c {
+            'd' => class(false, DIGIT),
+            'D' => class(true, DIGIT),
+            'w' => class(false, WORD),
+            'W' => class(true, WORD),
+            's' => class(false, SPACE),
+            'S' => class(true, SPACE),
+            't' => Ok('\t'),
+            'n' => Ok('\n'),
+            c if c.is_ascii_alphanumeric() => return None,
+            c => Ok(c),
+        })
+    }
+
+    fn class(&mut self) -> Option<Node> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next()?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = if c == '\\' {
+                match self.escape()? {
+                    Ok(c) => c,
+                    Err(Node::Class {
+                        negated: false,
+                        ranges: more,
+                    }) => {
+                        ranges.extend(more);
+                        continue;
+                    }
+                    Err(_) => return None,
+                }
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                self.pos += 1;
+                let high = match self.next()? {
+                    '\\' => self.escape()?.ok()?,
+                    c => c,
+                };
+                if high < low {
+                    return None;
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+        Some(Node::Class { negated, ranges })
+    }
+
+    /// Parses a quantifier, if any, as its minimum and maximum count.
+    fn quantifier(&mut self) -> Option<(usize, Option<usize>)> {
+        let bounds = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.braces() {
+                Some(bounds) => return Some(self.lazy(bounds)),
+                // PCRE takes a brace that doesn't start a quantifier literally.
+                None => return Some((1, Some(1))),
+            },
+            _ => return Some((1, Some(1))),
+        };
+        self.pos += 1;
+        Some(self.lazy(bounds))
+    }
+
+    /// Skips the `?` that makes a quantifier lazy, which makes no difference for whether a
+    /// pattern matches.
+    fn lazy(&mut self, bounds: (usize, Option<usize>)) -> (usize, Option<usize>) {
+        if self.peek() == Some('?') {
+            self.pos += 1;
+        }
+        bounds
+    }
+
+    fn braces(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest: String = self.chars[self.pos + 1..].iter().collect();
+        let end = rest.find('}')?;
+        let inner = &rest[..end];
+        let number = |s: &str| {
+            if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+                s.parse::<usize>().ok()
+            } else {
+                None
+            }
+        };
+        let bounds = match inner.split_once(',') {
+            None => {
+                let n = number(inner)?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (number(min)?, None),
+            Some((min, max)) => (number(min)?, Some(number(max)?)),
+        };
+        if bounds.1.is_some_and(|max| max < bounds.0) {
+            return None;
+        }
+        self.pos += 1 + inner.chars().count() + 1;
+        Some(bounds)
+    }
+}
+
+/// Compiles parsed pieces into instructions. A piece repeated `{n,m}` times is written out
+/// `m` times, the ones past `n` optional.
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    /// Appends `inst` and returns its index, or `None` once the program is too long.
+    fn push(&mut self, inst: Inst) -> Option<usize> {
+        if self.program.len() == MAX_PROGRAM {
+            return None;
+        }
+        self.program.push(inst);
+        Some(self.program.len() - 1)
+    }
+
+    fn alternatives(&mut self, alternatives: &[Vec<Piece>]) -> Option<()> {
+        let mut jumps = Vec::new();
+        let (last, rest) = alternatives.split_last()?;
+        for pieces in rest {
+            let split = self.push(Inst::Split(0, 0))?;
+            self.sequence(pieces)?;
+            jumps.push(self.push(Inst::Jump(0))?);
+            self.program[split] = Inst::Split(split + 1, self.program.len());
+        }
+        self.sequence(last)?;
+        for jump in jumps {
+            self.program[jump] = Inst::Jump(self.program.len());
+        }
+        Some(())
+    }
+
+    fn sequence(&mut self, pieces: &[Piece]) -> Option<()> {
+        for piece in pieces {
+            for _ in 0..piece.min {
+                let start = self.program.len();
+                self.node(&piece.node)?;
+                // an empty group matches the same however often it's repeated.
+                if self.program.len() == start {
+                    break;
+                }
+            }
+            match piece.max {
+                None => {
+                    let split = self.push(Inst::Split(0, 0))?;
+                    self.node(&piece.node)?;
+                    self.push(Inst::Jump(split))?;
+                    self.program[split] = Inst::Split(split + 1, self.program.len());
+                }
+                Some(max) => {
+                    for _ in piece.min..max {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.node(&piece.node)?;
+                        self.program[split] = Inst::Split(split + 1, self.program.len());
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn node(&mut self, node: &Node) -> Option<()> {
+        match node {
+            Node::Start => self.push(Inst::Start).map(drop),
+            Node::End => self.push(Inst::End).map(drop),
+            Node::Group(alternatives) => self.alternatives(alternatives),
+            single => self.push(Inst::Single(single.clone())).map(drop),
+        }
+    }
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn uppercase(c: char) -> char {
+    c.to_uppercase().next().unwrap_or(c)
+}
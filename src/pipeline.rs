@@ -0,0 +1,180 @@
+//! Sending several requests at once and reading all replies afterwards.
+//!
+//! Each request on an `I3Connection` waits for its reply before the next one is sent. A
+//! program that asks for a lot at startup can instead queue the requests in a `Pipeline`,
+//! which sends them together and then reads the replies in order, paying the round trip to
+//! i3 only once:
+//!
+//! ```no_run
+//! use i3ipc::I3Connection;
+//!
+//! let mut connection = I3Connection::connect().unwrap();
+//! let mut pipeline = connection.pipeline();
+//! let tree = pipeline.get_tree();
+//! let workspaces = pipeline.get_workspaces();
+//! let outputs = pipeline.get_outputs();
+//! let replies = pipeline.send().unwrap();
+//! println!("{:?}", replies.get(tree).unwrap().name);
+//! println!("{} workspaces", replies.get(workspaces).unwrap().len());
+//! println!("{} outputs", replies.get(outputs).unwrap().len());
+//! ```
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use crate::{common, reply, telemetry, I3Connection, MessageError};
+
+/// Stands for the reply to a request queued in a `Pipeline`, to be taken from its `Replies`.
+pub struct Slot<T> {
+    index: usize,
+    message_type: u32,
+    reply: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Slot<T> {
+    fn clone(&self) -> Slot<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Slot<T> {}
+
+impl<T> fmt::Debug for Slot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Slot")
+            .field("index", &self.index)
+            .field("message_type", &self.message_type)
+            .finish()
+    }
+}
+
+/// Requests queued to be sent together. Created by `I3Connection::pipeline`.
+#[derive(Debug)]
+pub struct Pipeline<'a, S> {
+    connection: &'a mut I3Connection<S>,
+    requests: Vec<(u32, String)>,
+}
+
+impl<'a, S: Read + Write> Pipeline<'a, S> {
+    pub(crate) fn new(connection: &'a mut I3Connection<S>) -> Pipeline<'a, S> {
+        Pipeline {
+            connection,
+            requests: Vec::new(),
+        }
+    }
+
+    fn push<T>(&mut self, message_type: u32, payload: &str) -> Slot<T> {
+        self.requests.push((message_type, payload.to_owned()));
+        Slot {
+            index: self.requests.len() - 1,
+            message_type,
+            reply: PhantomData,
+        }
+    }
+
+    /// Queues a command. See `I3Connection::run_command`.
+    pub fn run_command(&mut self, string: &str) -> Slot<reply::Command> {
+        self.push(0, string)
+    }
+
+    /// Queues a request for the workspaces.
+    pub fn get_workspaces(&mut self) -> Slot<reply::Workspaces> {
+        self.push(1, "")
+    }
+
+    /// Queues a request for the outputs.
+    pub fn get_outputs(&mut self) -> Slot<reply::Outputs> {
+        self.push(3, "")
+    }
+
+    /// Queues a request for the layout tree.
+    pub fn get_tree(&mut self) -> Slot<reply::Node> {
+        self.push(4, "")
+    }
+
+    /// Queues a request for the marks.
+    pub fn get_marks(&mut self) -> Slot<reply::Marks> {
+        self.push(5, "")
+    }
+
+    /// Queues a request for the IDs of the bars.
+    pub fn get_bar_ids(&mut self) -> Slot<reply::BarIds> {
+        self.push(6, "")
+    }
+
+    /// Queues a request for the configuration of the bar with the given ID.
+    pub fn get_bar_config(&mut self, id: &str) -> Slot<reply::BarConfig> {
+        self.push(6, id)
+    }
+
+    /// Queues a request for the version. Unlike `I3Connection::get_version`, the reply isn't
+    /// used to fill in the connection's capabilities.
+    pub fn get_version(&mut self) -> Slot<reply::Version> {
+        self.push(7, "")
+    }
+
+    /// Queues a request for the binding modes.
+    pub fn get_binding_modes(&mut self) -> Slot<reply::BindingModes> {
+        self.push(8, "")
+    }
+
+    /// Queues a request for the loaded config.
+    pub fn get_config(&mut self) -> Slot<reply::Config> {
+        self.push(9, "")
+    }
+
+    /// Queues a request for the active binding mode.
+    pub fn get_binding_state(&mut self) -> Slot<reply::BindingState> {
+        self.push(12, "")
+    }
+
+    /// The number of queued requests.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Whether no requests are queued.
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Sends all queued requests and reads their replies. A network error or a reply to an
+    /// unexpected message type fails the whole pipeline; replies that don't parse only fail
+    /// `Replies::get` for their own slot.
+    pub fn send(self) -> Result<Replies, MessageError> {
+        for (message_type, _) in &self.requests {
+            self.connection.require(*message_type)?;
+        }
+        let stream = &mut self.connection.stream;
+        Ok(Replies {
+            payloads: stream.send_receive_many(&self.requests)?,
+            max_tree_depth: stream.max_tree_depth,
+        })
+    }
+}
+
+/// The unparsed replies to the requests of a `Pipeline`.
+#[derive(Debug)]
+pub struct Replies {
+    payloads: Vec<String>,
+    max_tree_depth: usize,
+}
+
+impl Replies {
+    /// Parses the reply to the request `slot` stands for.
+    ///
+    /// # Panics
+    ///
+    /// If `slot` belongs to a different pipeline with more requests than this one.
+    pub fn get<T: DeserializeOwned>(&self, slot: Slot<T>) -> Result<T, MessageError> {
+        common::parse(
+            slot.message_type,
+            &self.payloads[slot.index],
+            self.max_tree_depth,
+        )
+        .inspect_err(telemetry::parse_failed)
+    }
+}
@@ -0,0 +1,158 @@
+//! Recording events to a file and replaying them later.
+//!
+//! A `Recorder` writes the events a listener receives as JSON lines, one `RecordedEvent` per
+//! line. A `Replay` reads such a file and parses the events again the same way a listener
+//! does, so a problem a user ran into can be reproduced without their i3:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::BufReader;
+//! use i3ipc::record::{Recorder, Replay};
+//! use i3ipc::{I3EventListener, Subscription};
+//!
+//! // on the user's machine
+//! let mut recorder = Recorder::new(File::create("events.jsonl").unwrap());
+//! let mut listener = I3EventListener::connect().unwrap();
+//! listener.subscribe(&[Subscription::Window]).unwrap();
+//! for event in listener.listen_raw().take(100) {
+//!     recorder.record(&event.unwrap()).unwrap();
+//! }
+//!
+//! // later, at ten times the original speed
+//! let mut replay = Replay::new(BufReader::new(File::open("events.jsonl").unwrap()));
+//! replay.set_speed(10.0);
+//! for event in replay {
+//!     println!("{:?}", event.unwrap());
+//! }
+//! ```
+
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+
+use crate::{common, event, MessageError, RawEvent, Subscription};
+
+/// One line of a recording.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RecordedEvent {
+    /// When the event was received, in milliseconds since the recording started.
+    pub time_ms: u64,
+    /// The type of the event as sent by i3, without the highest bit that marks events.
+    pub event_type: u32,
+    /// The subscription the event belongs to, if it is one this library knows. Only for
+    /// reading the recording; replaying goes by `event_type`.
+    pub subscription: Option<Subscription>,
+    /// The payload exactly as i3 sent it.
+    pub payload: String,
+}
+
+/// Writes events to a recording.
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    writer: W,
+    started: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Starts a recording. Event times are counted from now.
+    pub fn new(writer: W) -> Recorder<W> {
+        Recorder {
+            writer,
+            started: Instant::now(),
+        }
+    }
+
+    /// Writes an event received from `I3EventListener::listen_raw` and flushes it, so the
+    /// recording is complete up to the last event even if the program is killed.
+    pub fn record(&mut self, event: &RawEvent) -> io::Result<()> {
+        self.record_payload(event.event_type, &event.payload)
+    }
+
+    /// Writes an event given as its type and payload, such as one that failed to parse.
+    pub fn record_payload(&mut self, event_type: u32, payload: &str) -> io::Result<()> {
+        let recorded = RecordedEvent {
+            time_ms: self.started.elapsed().as_millis() as u64,
+            event_type,
+            subscription: Subscription::from_event_type(event_type),
+            payload: payload.to_owned(),
+        };
+        json::to_writer(&mut self.writer, &recorded)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    /// Gives back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Replays a recording as an iterator of events.
+///
+/// Each event is returned at the time it was originally received, relative to the first call
+/// to `next`, divided by the speed. A line that can't be read is returned as a `Receive`
+/// error with the `InvalidData` kind, an event that can't be parsed as the error a listener
+/// would have returned.
+#[derive(Debug)]
+pub struct Replay<R: BufRead> {
+    reader: R,
+    speed: f64,
+    started: Option<Instant>,
+}
+
+impl<R: BufRead> Replay<R> {
+    /// Replays the recording read from `reader` at its original speed.
+    pub fn new(reader: R) -> Replay<R> {
+        Replay {
+            reader,
+            speed: 1.0,
+            started: None,
+        }
+    }
+
+    /// Sets how many times faster than the original the events are replayed. An infinite speed
+    /// replays them without waiting.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    /// Reads the next recorded event without waiting or parsing it.
+    pub fn next_recorded(&mut self) -> Option<Result<RecordedEvent, MessageError>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => {
+                    return Some(json::from_str(&line).map_err(|e| {
+                        crate::receive_failed(io::Error::new(io::ErrorKind::InvalidData, e))
+                    }))
+                }
+                Err(e) => return Some(Err(crate::receive_failed(e))),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Replay<R> {
+    type Item = Result<event::Event, MessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let recorded = match self.next_recorded()? {
+            Ok(recorded) => recorded,
+            Err(e) => return Some(Err(e)),
+        };
+        if self.speed.is_finite() && self.speed > 0.0 {
+            let due = Duration::from_millis(recorded.time_ms).div_f64(self.speed);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        Some(common::build_event(recorded.event_type, &recorded.payload))
+    }
+}
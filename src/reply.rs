@@ -1,29 +1,157 @@
 //! Abstractions for the replies passed back from i3.
 
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use std::collections::{HashMap, HashSet};
+
+/// Implements `iter`, `len`, `is_empty` and `IntoIterator` for a reply that wraps a single
+/// list, so it can be used like a collection.
+macro_rules! list_reply {
+    ($name:ident { $field:ident: $item:ty }) => {
+        impl $name {
+            /// Iterates over the entries of the list.
+            pub fn iter(&self) -> std::slice::Iter<'_, $item> {
+                self.$field.iter()
+            }
+
+            /// The number of entries in the list.
+            pub fn len(&self) -> usize {
+                self.$field.len()
+            }
+
+            /// Whether the list is empty.
+            pub fn is_empty(&self) -> bool {
+                self.$field.is_empty()
+            }
+        }
+
+        impl IntoIterator for $name {
+            type Item = $item;
+            type IntoIter = std::vec::IntoIter<$item>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.$field.into_iter()
+            }
+        }
+
+        impl<'a> IntoIterator for &'a $name {
+            type Item = &'a $item;
+            type IntoIter = std::slice::Iter<'a, $item>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.$field.iter()
+            }
+        }
+    };
+}
 
 /// The outcome of a single command.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 pub struct CommandOutcome {
     /// Whether the command was successful.
     pub success: bool,
+    /// Whether the command couldn't be parsed. i3 stops at the first command it can't parse.
+    #[serde(default)]
+    pub parse_error: bool,
     /// A human-readable error message.
     pub error: Option<String>,
+    /// For parse errors, the complete input i3 tried to parse.
+    pub input: Option<String>,
+    /// For parse errors, a string marking the position of the error in `input` with `^`.
+    pub errorposition: Option<String>,
 }
 
 /// The reply to the `command` request.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct Command {
     /// A list of `CommandOutcome` structs; one for each command that was parsed.
     pub outcomes: Vec<CommandOutcome>,
 }
 
+/// A rectangle in pixels, as used for the geometry of workspaces, outputs and containers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    /// The x coordinate just right of the rectangle, computed without overflowing.
+    fn right(&self) -> i64 {
+        i64::from(self.x) + i64::from(self.width)
+    }
+
+    /// The y coordinate just below the rectangle, computed without overflowing.
+    fn bottom(&self) -> i64 {
+        i64::from(self.y) + i64::from(self.height)
+    }
+
+    /// Whether the point (x, y) lies inside the rectangle.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && i64::from(x) < self.right() && y >= self.y && i64::from(y) < self.bottom()
+    }
+
+    /// Whether the two rectangles overlap in at least one pixel.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        i64::from(self.x) < other.right()
+            && i64::from(other.x) < self.right()
+            && i64::from(self.y) < other.bottom()
+            && i64::from(other.y) < self.bottom()
+    }
+
+    /// The overlapping part of the two rectangles, if any.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        // the overlap is no wider or taller than either rectangle, so it fits in an i32.
+        Some(Rect {
+            x,
+            y,
+            width: (self.right().min(other.right()) - i64::from(x)) as i32,
+            height: (self.bottom().min(other.bottom()) - i64::from(y)) as i32,
+        })
+    }
+
+    /// The center of the rectangle as (x, y), rounded towards the top left.
+    pub fn center(&self) -> (i32, i32) {
+        (
+            self.x.saturating_add(self.width / 2),
+            self.y.saturating_add(self.height / 2),
+        )
+    }
+
+    /// The area in pixels.
+    pub fn area(&self) -> i64 {
+        i64::from(self.width) * i64::from(self.height)
+    }
+}
+
+impl From<Rect> for (i32, i32, i32, i32) {
+    fn from(rect: Rect) -> (i32, i32, i32, i32) {
+        (rect.x, rect.y, rect.width, rect.height)
+    }
+}
+
 /// A single workspace.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 pub struct Workspace {
     /// The logical number of the workspace. Corresponds to the command to switch to this
-    /// workspace. For named workspaces, this will be -1.
-    pub num: i32,
+    /// workspace. `None` for named workspaces, which i3 gives the number -1.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_workspace_num",
+        serialize_with = "serialize_workspace_num"
+    )]
+    pub num: Option<i32>,
     /// The name of this workspace (by default num+1), as changed by the user.
     pub name: String,
     /// Whether this workspace is currently visible on an output (multiple workspaces can be
@@ -34,22 +162,63 @@ pub struct Workspace {
     pub focused: bool,
     /// Whether a window on this workspace has the "urgent" flag set.
     pub urgent: bool,
-    /// The rectangle of this workspace (equals the rect of the output it is on), consists of
-    /// x, y, width, height.
-    pub rect: (i32, i32, i32, i32),
+    /// The rectangle of this workspace (equals the rect of the output it is on).
+    pub rect: Rect,
     /// The video output this workspace is on (LVDS1, VGA1, …).
     pub output: String,
 }
 
+fn deserialize_workspace_num<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i32>, D::Error> {
+    let num = i32::deserialize(deserializer)?;
+    Ok(if num < 0 { None } else { Some(num) })
+}
+
+fn serialize_workspace_num<S: serde::Serializer>(
+    num: &Option<i32>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i32(num.unwrap_or(-1))
+}
+
 /// The reply to the `get_workspaces` request.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct Workspaces {
     /// A list of workspaces.
     pub workspaces: Vec<Workspace>,
 }
 
+list_reply!(Workspaces {
+    workspaces: Workspace
+});
+
+impl Workspaces {
+    /// The focused workspace.
+    pub fn focused(&self) -> Option<&Workspace> {
+        self.workspaces.iter().find(|workspace| workspace.focused)
+    }
+
+    /// The workspaces on the output with the given name, in the order i3 reported them.
+    pub fn on_output(&self, output: &str) -> Vec<&Workspace> {
+        self.workspaces
+            .iter()
+            .filter(|workspace| workspace.output == output)
+            .collect()
+    }
+
+    /// The workspace with the given name.
+    pub fn by_name(&self, name: &str) -> Option<&Workspace> {
+        self.workspaces
+            .iter()
+            .find(|workspace| workspace.name == name)
+    }
+}
+
 /// The reply to the `subscribe` request.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 pub struct Subscribe {
     /// Indicates whether the subscription was successful (the default) or whether a JSON
     /// parse error occurred.
@@ -57,7 +226,8 @@ pub struct Subscribe {
 }
 
 #[cfg(feature = "sway-1-1")]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 /// A mode for sway
 pub struct Mode {
     pub width: i32,
@@ -66,7 +236,8 @@ pub struct Mode {
 }
 
 /// A single output (display)
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 pub struct Output {
     /// The name of this output (as seen in xrandr).
     pub name: String,
@@ -104,28 +275,89 @@ pub struct Output {
     #[cfg(feature = "sway-1-1")]
     /// current mode for the output
     pub current_mode: Option<Mode>,
-    /// The rectangle of this output (equals the rect of the output it is on), consists of
-    /// x, y, width, height.
-    pub rect: (i32, i32, i32, i32),
+    /// The rectangle of this output (equals the rect of the output it is on).
+    pub rect: Rect,
 }
 
 /// The reply to the `get_outputs` request.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct Outputs {
     /// A list of outputs (displays)
     pub outputs: Vec<Output>,
 }
 
-#[derive(Eq, PartialEq, Debug, Hash, Clone)]
+list_reply!(Outputs { outputs: Output });
+
+/// Names the string properties in `WindowProperties`, for looking them up with
+/// `WindowProperties::get`.
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum WindowProperty {
     Title,
     Instance,
     Class,
     WindowRole,
+    /// Not a string; `get` always returns `None` for it, use `WindowProperties::transient_for`.
     TransientFor,
+    Machine,
+}
+
+/// The X11 properties of a window.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct WindowProperties {
+    /// The window title (_NET_WM_NAME).
+    pub title: Option<String>,
+    /// The first part of WM_CLASS.
+    pub instance: Option<String>,
+    /// The second part of WM_CLASS.
+    pub class: Option<String>,
+    /// WM_WINDOW_ROLE.
+    pub window_role: Option<String>,
+    /// The X11 window ID of the window this one is a dialog or similar for (WM_TRANSIENT_FOR).
+    pub transient_for: Option<u32>,
+    /// The host the client runs on (WM_CLIENT_MACHINE).
+    pub machine: Option<String>,
+    /// Properties this library doesn't know about yet, as i3 sent them.
+    #[serde(flatten)]
+    #[cfg_attr(
+        feature = "deny-unknown-fields",
+        serde(deserialize_with = "deny_other_fields")
+    )]
+    pub other: HashMap<String, json::Value>,
+}
+
+/// Rejects the fields a struct would otherwise collect in a map of unknown fields.
+#[cfg(feature = "deny-unknown-fields")]
+fn deny_other_fields<'de, D, V>(deserializer: D) -> Result<HashMap<String, V>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    let other = HashMap::<String, V>::deserialize(deserializer)?;
+    match other.keys().next() {
+        Some(field) => Err(serde::de::Error::unknown_field(field, &[])),
+        None => Ok(other),
+    }
+}
+
+impl WindowProperties {
+    /// Gets one of the string properties.
+    pub fn get(&self, property: WindowProperty) -> Option<&str> {
+        match property {
+            WindowProperty::Title => self.title.as_deref(),
+            WindowProperty::Instance => self.instance.as_deref(),
+            WindowProperty::Class => self.class.as_deref(),
+            WindowProperty::WindowRole => self.window_role.as_deref(),
+            WindowProperty::TransientFor => None,
+            WindowProperty::Machine => self.machine.as_deref(),
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
 pub enum NodeType {
     Root,
     Output,
@@ -133,20 +365,37 @@ pub enum NodeType {
     FloatingCon,
     Workspace,
     DockArea,
-    /// A NodeType we don't support yet.
-    Unknown,
+    /// A NodeType we don't support yet, holding the raw string i3 sent.
+    Unknown(String),
 }
 
+serde_enum_str!(NodeType {
+    "root" => Root,
+    "output" => Output,
+    "con" => Con,
+    "floating_con" => FloatingCon,
+    "workspace" => Workspace,
+    "dockarea" => DockArea,
+});
+
 #[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
 pub enum NodeBorder {
     Normal,
     None,
     Pixel,
-    /// A NodeBorder we don't support yet.
-    Unknown,
+    /// A NodeBorder we don't support yet, holding the raw string i3 sent.
+    Unknown(String),
 }
 
+serde_enum_str!(NodeBorder {
+    "normal" => Normal,
+    "none" => None,
+    "pixel" => Pixel,
+});
+
 #[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
 pub enum NodeLayout {
     SplitH,
     SplitV,
@@ -154,22 +403,140 @@ pub enum NodeLayout {
     Tabbed,
     DockArea,
     Output,
-    /// A NodeLayout we don't support yet.
-    Unknown,
+    /// A NodeLayout we don't support yet, holding the raw string i3 sent.
+    Unknown(String),
+}
+
+serde_enum_str!(NodeLayout {
+    "splith" => SplitH,
+    "splitv" => SplitV,
+    "stacked" => Stacked,
+    "tabbed" => Tabbed,
+    "dockarea" => DockArea,
+    "output" => Output,
+});
+
+/// The _NET_WM_WINDOW_TYPE of a window, which tells dialogs, menus and the like apart from
+/// normal windows.
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub enum WindowType {
+    Normal,
+    Dialog,
+    Utility,
+    Toolbar,
+    Splash,
+    Menu,
+    DropdownMenu,
+    PopupMenu,
+    Tooltip,
+    Notification,
+    Dock,
+    /// A WindowType we don't support yet, holding the raw string i3 sent. i3 sends "unknown"
+    /// for windows of a type it doesn't know either.
+    Unknown(String),
+}
+
+serde_enum_str!(WindowType {
+    "normal" => Normal,
+    "dialog" => Dialog,
+    "utility" => Utility,
+    "toolbar" => Toolbar,
+    "splash" => Splash,
+    "menu" => Menu,
+    "dropdown_menu" => DropdownMenu,
+    "popup_menu" => PopupMenu,
+    "tooltip" => Tooltip,
+    "notification" => Notification,
+    "dock" => Dock,
+});
+
+/// Whether a container is floating, and whether i3 or the user decided so.
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub enum FloatingState {
+    /// i3 made the container floating, for example because it is a dialog.
+    AutoOn,
+    /// The container is tiling because i3 decided so.
+    AutoOff,
+    /// The user made the container floating.
+    UserOn,
+    /// The user made the container tiling.
+    UserOff,
+    /// A FloatingState we don't support yet, holding the raw string i3 sent.
+    Unknown(String),
+}
+
+serde_enum_str!(FloatingState {
+    "auto_on" => AutoOn,
+    "auto_off" => AutoOff,
+    "user_on" => UserOn,
+    "user_off" => UserOff,
+});
+
+impl FloatingState {
+    /// Whether the container is floating, whoever decided so.
+    pub fn is_floating(&self) -> bool {
+        matches!(*self, FloatingState::AutoOn | FloatingState::UserOn)
+    }
+}
+
+/// Whether and how a container is fullscreen. i3 sends it as a number.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub enum FullscreenMode {
+    /// The container isn't fullscreen.
+    #[default]
+    None,
+    /// The container fills its output.
+    Output,
+    /// The container spans all outputs.
+    Global,
+    /// A FullscreenMode we don't support yet, holding the raw number i3 sent.
+    Unknown(i64),
+}
+
+impl<'de> Deserialize<'de> for FullscreenMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match i64::deserialize(deserializer)? {
+            0 => FullscreenMode::None,
+            1 => FullscreenMode::Output,
+            2 => FullscreenMode::Global,
+            other => {
+                crate::telemetry::unknown_value("FullscreenMode", &other.to_string());
+                FullscreenMode::Unknown(other)
+            }
+        })
+    }
+}
+
+impl Serialize for FullscreenMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(match *self {
+            FullscreenMode::None => 0,
+            FullscreenMode::Output => 1,
+            FullscreenMode::Global => 2,
+            FullscreenMode::Unknown(other) => other,
+        })
+    }
 }
 
 /// The reply to the `get_tree` request.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 pub struct Node {
     /// List of child node IDs (see `nodes`, `floating_nodes` and `id`) in focus order. Traversing
     /// the tree by following the first entry in this array will result in eventually reaching the
     /// one node with `focused` set to true.
+    #[serde(default)]
     pub focus: Vec<i64>,
 
     /// The child nodes of this container.
+    #[serde(default)]
     pub nodes: Vec<Node>,
 
     /// The child floating nodes of this container.
+    #[serde(default)]
     pub floating_nodes: Vec<Node>,
 
     /// The internal ID (actually a C pointer value) of this container. Do not make any
@@ -184,8 +551,27 @@ pub struct Node {
     /// defined (yet).
     pub name: Option<String>,
 
+    /// The number of a workspace, if its name starts with one. `None` for named workspaces and
+    /// for containers that aren't workspaces.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_workspace_num",
+        serialize_with = "serialize_workspace_num",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub num: Option<i32>,
+
+    /// The format for the title of this window set with `title_format`, if any.
+    #[serde(default)]
+    pub title_format: Option<String>,
+
+    /// The name of the output this container is on. Not every version of i3 sends it.
+    #[serde(default)]
+    pub output: Option<String>,
+
     /// Type of this container. Can be one of "root", "output", "con", "floating_con",
     /// "workspace" or "dockarea".
+    #[serde(rename = "type")]
     pub nodetype: NodeType,
 
     /// Can be either "normal", "none" or "1pixel", dependending on the container’s border
@@ -195,44 +581,102 @@ pub struct Node {
     /// Number of pixels of the border width.
     pub current_border_width: i32,
 
+    /// The padding in pixels between the window icon and the title, or -1 if the icon isn't
+    /// shown. Sent by i3 4.21 and later.
+    #[serde(default)]
+    pub window_icon_padding: Option<i32>,
+
     /// Can be either "splith", "splitv", "stacked", "tabbed", "dockarea" or "output". Other values
     /// might be possible in the future, should we add new layouts.
     pub layout: NodeLayout,
 
+    /// The direction this container splits in: "horizontal", "vertical" or "none". It follows
+    /// from `layout` and is only sent for compatibility.
+    #[serde(default)]
+    pub orientation: Option<String>,
+
+    /// The layout new containers on this workspace get: "default", "stacked" or "tabbed".
+    #[serde(default)]
+    pub workspace_layout: Option<String>,
+
+    /// The split layout this container had last, "splith" or "splitv", which toggling the
+    /// layout goes back to.
+    #[serde(default)]
+    pub last_split_layout: Option<String>,
+
     /// The percentage which this container takes in its parent. A value of null means that the
     /// percent property does not make sense for this container, for example for the root
     /// container.
     pub percent: Option<f64>,
 
-    /// The (x, y, width, height) absolute display coordinates for this container. Display
+    /// The absolute display coordinates for this container. Display
     /// coordinates means that when you have two 1600x1200 monitors on a single X11 Display
     /// (the standard way), the coordinates of the first window on the second monitor are
     /// (1600, 0, 1600, 1200).
-    pub rect: (i32, i32, i32, i32),
+    pub rect: Rect,
 
-    /// The (x, y, width, height) coordinates of the actual client window inside its container.
+    /// The coordinates of the actual client window inside its container.
     /// These coordinates are  relative to the container and do not include the window
     /// decoration (which is actually rendered on the parent container). So for example, when
     /// using the default layout, you will have a 2 pixel border on each side, making the
     /// window_rect (2, 0, 632, 366).
-    pub window_rect: (i32, i32, i32, i32),
+    pub window_rect: Rect,
 
-    /// The (x, y, width, height) coordinates of the window decoration inside its container.
+    /// The coordinates of the window decoration inside its container.
     /// These coordinates are relative to the container and do not include the actual client
     /// window.
-    pub deco_rect: (i32, i32, i32, i32),
+    pub deco_rect: Rect,
 
     /// The original geometry the window specified when i3 mapped it. Used when switching a
     /// window to floating mode, for example.
-    pub geometry: (i32, i32, i32, i32),
+    pub geometry: Rect,
 
     /// The X11 window ID of the actual client window inside this container. This field is set
     /// to null for split containers or otherwise empty containers. This ID corresponds to what
     /// xwininfo(1) and other X11-related tools display (usually in hex).
     pub window: Option<i32>,
 
-    /// X11 window properties title, instance, class, window_role and transient_for.
-    pub window_properties: Option<HashMap<WindowProperty, String>>,
+    /// The type of the window in this container. `None` for containers without a window.
+    #[serde(default)]
+    pub window_type: Option<WindowType>,
+
+    /// X11 window properties such as the title, class and instance.
+    #[serde(default)]
+    pub window_properties: Option<WindowProperties>,
+
+    /// The marks set on this container.
+    #[serde(default)]
+    pub marks: Vec<String>,
+
+    /// The criteria of placeholder containers from `append_layout` that windows will be put
+    /// in when they appear, as i3 sends them.
+    #[serde(default)]
+    pub swallows: Vec<HashMap<String, json::Value>>,
+
+    /// Whether this container is fullscreen, on its output or across all of them.
+    #[serde(default)]
+    pub fullscreen_mode: FullscreenMode,
+
+    /// Whether this container is floating. `None` if the window manager doesn't say, as sway
+    /// doesn't; there, floating windows are the children of `floating_nodes`.
+    #[serde(default)]
+    pub floating: Option<FloatingState>,
+
+    /// Whether this floating container stays on screen when switching workspaces.
+    #[serde(default)]
+    pub sticky: bool,
+
+    /// Whether and how this container is a scratchpad window: "none", "fresh" for one that
+    /// was never moved or resized, or "changed".
+    #[serde(default)]
+    pub scratchpad_state: Option<String>,
+
+    /// The gaps configured for this container, as reported by i3-gaps, i3 4.22+ and sway.
+    /// Usually only set on workspaces.
+    #[cfg(feature = "gaps")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "gaps")))]
+    #[serde(default)]
+    pub gaps: Option<Gaps>,
 
     /// Whether this container (window, split container, floating container or workspace) has the
     /// urgency hint set, directly or indirectly. All parent containers up until the workspace
@@ -241,6 +685,364 @@ pub struct Node {
 
     /// Whether this container is currently focused.
     pub focused: bool,
+
+    /// For views, the Wayland app id of the application. None for xwayland views and for
+    /// containers without a view.
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    pub app_id: Option<String>,
+
+    /// For views, the process ID of the application.
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    pub pid: Option<i32>,
+
+    /// For views, the shell of the application, such as "xdg_shell" or "xwayland".
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    pub shell: Option<String>,
+
+    /// For views, whether the node is visible.
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    pub visible: Option<bool>,
+
+    /// For views, whether the application is inhibiting idle.
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    pub inhibit_idle: Option<bool>,
+
+    /// For views, the idle inhibitors that apply to the view.
+    #[cfg(feature = "sway")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+    pub idle_inhibitors: Option<IdleInhibitors>,
+}
+
+/// Gap sizes in pixels. `inner` is the space between containers, the others the space
+/// between the containers and the respective screen edge.
+#[cfg(feature = "gaps")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "gaps")))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+#[serde(default)]
+pub struct Gaps {
+    pub inner: i32,
+    pub outer: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+impl Node {
+    /// Returns the first node of this subtree (including this node itself) for which
+    /// `predicate` returns true. Children are searched depth-first, tiling before floating.
+    pub fn find<P: FnMut(&Node) -> bool>(&self, mut predicate: P) -> Option<&Node> {
+        self.find_dyn(&mut predicate)
+    }
+
+    fn find_dyn(&self, predicate: &mut dyn FnMut(&Node) -> bool) -> Option<&Node> {
+        if predicate(self) {
+            return Some(self);
+        }
+        self.nodes
+            .iter()
+            .chain(self.floating_nodes.iter())
+            .find_map(|node| node.find_dyn(predicate))
+    }
+
+    /// Finds the container with the given i3 container ID.
+    pub fn find_by_id(&self, id: i64) -> Option<&Node> {
+        self.find(|node| node.id == id)
+    }
+
+    /// Finds the container holding the given X11 window.
+    pub fn find_by_window(&self, window: u32) -> Option<&Node> {
+        self.find(|node| node.window.map(|w| w as u32) == Some(window))
+    }
+
+    /// Finds the first window whose class is exactly `class`.
+    pub fn find_by_class(&self, class: &str) -> Option<&Node> {
+        self.find(|node| node.window_property(WindowProperty::Class) == Some(class))
+    }
+
+    /// Finds the container carrying the given mark.
+    pub fn find_by_mark(&self, mark: &str) -> Option<&Node> {
+        self.find(|node| node.marks.iter().any(|m| m == mark))
+    }
+
+    /// Whether this container is fullscreen, either on its output or globally.
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen_mode != FullscreenMode::None
+    }
+
+    /// Whether this is the hidden workspace holding the scratchpad windows.
+    pub fn is_scratchpad(&self) -> bool {
+        self.nodetype == NodeType::Workspace && self.name.as_deref() == Some("__i3_scratch")
+    }
+
+    /// Finds the scratchpad workspace in this subtree.
+    pub fn find_scratchpad(&self) -> Option<&Node> {
+        self.find(Node::is_scratchpad)
+    }
+
+    /// Whether the container with the given ID is hidden in the scratchpad. A scratchpad
+    /// window that is currently shown floats on a regular workspace instead.
+    pub fn is_in_scratchpad(&self, id: i64) -> bool {
+        self.find_scratchpad()
+            .is_some_and(|scratchpad| scratchpad.id != id && scratchpad.find_by_id(id).is_some())
+    }
+
+    /// Follows the `focus` arrays down from this node to the node with `focused` set, which is
+    /// usually a window but can be a split container or an empty workspace.
+    pub fn find_focused(&self) -> Option<&Node> {
+        let mut node = self;
+        while !node.focused {
+            let id = *node.focus.first()?;
+            node = node
+                .nodes
+                .iter()
+                .chain(node.floating_nodes.iter())
+                .find(|child| child.id == id)?;
+        }
+        Some(node)
+    }
+
+    /// Follows the first entry of the `focus` arrays down from this node for as long as it
+    /// leads to a child, returning the container that would get focus if this one did. Unlike
+    /// `find_focused` this works on any subtree, such as an unfocused workspace, where it
+    /// gives the window that was focused last. A container without children is its own leaf.
+    pub fn focused_leaf(&self) -> &Node {
+        let mut node = self;
+        while let Some(child) = node.focus.first().and_then(|id| {
+            node.nodes
+                .iter()
+                .chain(node.floating_nodes.iter())
+                .find(|child| child.id == *id)
+        }) {
+            node = child;
+        }
+        node
+    }
+
+    /// Renders this subtree as indented text, one container per line, for debugging layouts.
+    /// Each line shows the type, layout, name and ID of a container; floating containers are
+    /// marked with `floating` and the focused one with `*`:
+    ///
+    /// ```text
+    /// workspace splith "1" #3
+    ///   con splith #4
+    ///     con splith "Mozilla Firefox" #5
+    ///     con splith "~" #6 *
+    ///   floating_con splith #7 floating
+    ///     con splith "htop" #8
+    /// ```
+    pub fn render_tree(&self) -> String {
+        let mut rendered = String::new();
+        self.render_into(&mut rendered, 0, false);
+        rendered
+    }
+
+    fn render_into(&self, out: &mut String, depth: usize, floating: bool) {
+        use std::fmt::Write;
+        let _ = write!(
+            out,
+            "{:indent$}{} {}",
+            "",
+            self.nodetype.as_str(),
+            self.layout.as_str(),
+            indent = depth * 2
+        );
+        if let Some(ref name) = self.name {
+            let _ = write!(out, " {:?}", name);
+        }
+        let _ = write!(out, " #{}", self.id);
+        if floating {
+            out.push_str(" floating");
+        }
+        if self.focused {
+            out.push_str(" *");
+        }
+        out.push('\n');
+        for child in &self.nodes {
+            child.render_into(out, depth + 1, false);
+        }
+        for child in &self.floating_nodes {
+            child.render_into(out, depth + 1, true);
+        }
+    }
+
+    /// Renders this subtree as a Graphviz digraph, for debugging layouts and drawing them in
+    /// documentation (`dot -Tsvg`). Containers are labeled with their type, layout, name and
+    /// ID; the edges i3 would follow from each container to its focused child are bold and red,
+    /// edges to floating children are dashed, and the focused container is filled.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tree {\n    node [shape=box];\n");
+        self.dot_into(&mut dot);
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn dot_into(&self, out: &mut String) {
+        use std::fmt::Write;
+        let mut label = format!("{} {}", self.nodetype.as_str(), self.layout.as_str());
+        if let Some(ref name) = self.name {
+            label.push('\n');
+            label.push_str(name);
+        }
+        let _ = write!(label, "\n#{}", self.id);
+        let _ = write!(out, "    \"{}\" [label={}", self.id, dot_string(&label));
+        if self.focused {
+            out.push_str(", style=filled");
+        }
+        out.push_str("];\n");
+        let children = self.nodes.iter().map(|child| (child, false));
+        let floating = self.floating_nodes.iter().map(|child| (child, true));
+        for (child, floating) in children.chain(floating) {
+            let mut attributes = Vec::new();
+            if self.focus.first() == Some(&child.id) {
+                attributes.push("color=red, penwidth=2");
+            }
+            if floating {
+                attributes.push("style=dashed");
+            }
+            let _ = write!(out, "    \"{}\" -> \"{}\"", self.id, child.id);
+            if !attributes.is_empty() {
+                let _ = write!(out, " [{}]", attributes.join(", "));
+            }
+            out.push_str(";\n");
+        }
+        for child in self.nodes.iter().chain(self.floating_nodes.iter()) {
+            child.dot_into(out);
+        }
+    }
+
+    /// Gets one of the X11 window properties of this container, if it has a window.
+    pub fn window_property(&self, property: WindowProperty) -> Option<&str> {
+        self.window_properties
+            .as_ref()
+            .and_then(|properties| properties.get(property))
+    }
+}
+
+/// A difference between two snapshots of the layout tree, as reported by `diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TreeChange {
+    /// A container that wasn't in the old tree, with the ID of its parent.
+    Added { id: i64, parent: Option<i64> },
+    /// A container that is gone from the new tree, with the ID of its old parent.
+    Removed { id: i64, parent: Option<i64> },
+    /// A container that is held by a different parent now.
+    Moved {
+        id: i64,
+        old_parent: Option<i64>,
+        new_parent: Option<i64>,
+    },
+    /// A container whose name (usually the window title) changed.
+    Renamed {
+        id: i64,
+        old_name: Option<String>,
+        new_name: Option<String>,
+    },
+    /// The focus moved from one container to another.
+    Refocused { old: Option<i64>, new: Option<i64> },
+}
+
+/// Quotes `s` as a DOT string, keeping line breaks as DOT's `\n`.
+fn dot_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Visits every node of the subtree, tiling before floating, along with its parent's ID.
+fn walk<'a>(node: &'a Node, parent: Option<i64>, visit: &mut dyn FnMut(&'a Node, Option<i64>)) {
+    visit(node, parent);
+    for child in node.nodes.iter().chain(node.floating_nodes.iter()) {
+        walk(child, Some(node.id), visit);
+    }
+}
+
+/// Compares two snapshots of the layout tree, such as two `get_tree` replies, and reports the
+/// containers that were added, removed, moved or renamed and whether the focus moved.
+///
+/// Containers are matched by ID. Additions, moves and renames are listed in the order of the
+/// new tree, followed by removals in the order of the old tree and finally the focus change.
+pub fn diff(old: &Node, new: &Node) -> Vec<TreeChange> {
+    let mut old_nodes = HashMap::new();
+    walk(old, None, &mut |node, parent| {
+        old_nodes.insert(node.id, (node, parent));
+    });
+
+    let mut changes = Vec::new();
+    let mut new_ids = HashSet::new();
+    walk(new, None, &mut |node, parent| {
+        new_ids.insert(node.id);
+        match old_nodes.get(&node.id) {
+            None => changes.push(TreeChange::Added {
+                id: node.id,
+                parent,
+            }),
+            Some(&(old_node, old_parent)) => {
+                if old_parent != parent {
+                    changes.push(TreeChange::Moved {
+                        id: node.id,
+                        old_parent,
+                        new_parent: parent,
+                    });
+                }
+                if old_node.name != node.name {
+                    changes.push(TreeChange::Renamed {
+                        id: node.id,
+                        old_name: old_node.name.clone(),
+                        new_name: node.name.clone(),
+                    });
+                }
+            }
+        }
+    });
+    walk(old, None, &mut |node, parent| {
+        if !new_ids.contains(&node.id) {
+            changes.push(TreeChange::Removed {
+                id: node.id,
+                parent,
+            });
+        }
+    });
+
+    let old_focus = old.find(|node| node.focused).map(|node| node.id);
+    let new_focus = new.find(|node| node.focused).map(|node| node.id);
+    if old_focus != new_focus {
+        changes.push(TreeChange::Refocused {
+            old: old_focus,
+            new: new_focus,
+        });
+    }
+    changes
+}
+
+/// The idle inhibitors of a sway view.
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct IdleInhibitors {
+    /// The user idle inhibitor: "focus", "fullscreen", "open", "visible" or "none".
+    pub user: String,
+    /// The application idle inhibitor: "enabled" or "none".
+    pub application: String,
 }
 
 /// The reply to the `get_marks` request.
@@ -248,22 +1050,27 @@ pub struct Node {
 /// Consists of a single vector of strings for each container that has a mark. A mark can only
 /// be set on one container, so the vector is unique. The order of that vector is undefined. If
 /// no window has a mark the response will be an empty vector.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct Marks {
     pub marks: Vec<String>,
 }
 
+list_reply!(Marks { marks: String });
+
 /// The reply to the `get_bar_ids` request.
 ///
 /// This can be used by third-party workspace bars (especially i3bar, but others are free to
 /// implement compatible alternatives) to get the bar block configuration from i3.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct BarIds {
     /// A vector of configured bar IDs.
     pub ids: Vec<String>,
 }
 
-#[derive(Hash, Eq, PartialEq, Debug)]
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
 pub enum ColorableBarPart {
     /// Background color of the bar.
     Background,
@@ -275,20 +1082,14 @@ pub enum ColorableBarPart {
     Separator,
 
     /// Background color of the bar on the currently focused monitor output.
-    #[cfg(feature = "i3-4-12")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-12")))]
     FocusedBackground,
 
     /// Text color to be used for the statusline on the currently focused
     /// monitor output.
-    #[cfg(feature = "i3-4-12")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-12")))]
     FocusedStatusline,
 
     /// Text color to be used for the separator on the currently focused
     /// monitor output.
-    #[cfg(feature = "i3-4-12")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-12")))]
     FocusedSeparator,
 
     /// Text color for a workspace button when the workspace has focus.
@@ -346,15 +1147,200 @@ pub enum ColorableBarPart {
     /// Border color for the binding mode indicator.
     BindingModeBorder,
 
-    /// A ColorableBarPart we don't support yet.
-    Unknown,
+    /// A ColorableBarPart we don't support yet, holding the raw string i3 sent.
+    Unknown(String),
+}
+
+serde_enum_str!(ColorableBarPart {
+    "background" => Background,
+    "statusline" => Statusline,
+    "separator" => Separator,
+    "focused_background" => FocusedBackground,
+    "focused_statusline" => FocusedStatusline,
+    "focused_separator" => FocusedSeparator,
+    "focused_workspace_text" => FocusedWorkspaceText,
+    "focused_workspace_bg" => FocusedWorkspaceBg,
+    "focused_workspace_border" => FocusedWorkspaceBorder,
+    "active_workspace_text" => ActiveWorkspaceText,
+    "active_workspace_bg" => ActiveWorkspaceBg,
+    "active_workspace_border" => ActiveWorkspaceBorder,
+    "inactive_workspace_text" => InactiveWorkspaceText,
+    "inactive_workspace_bg" => InactiveWorkspaceBg,
+    "inactive_workspace_border" => InactiveWorkspaceBorder,
+    "urgent_workspace_text" => UrgentWorkspaceText,
+    "urgent_workspace_bg" => UrgentWorkspaceBg,
+    "urgent_workspace_border" => UrgentWorkspaceBorder,
+    "binding_mode_text" => BindingModeText,
+    "binding_mode_bg" => BindingModeBg,
+    "binding_mode_border" => BindingModeBorder,
+});
+
+/// A color, as i3 writes it: `#rrggbb`, or `#rrggbbaa` with an alpha channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    /// 255 for an opaque color, which is what a color without an alpha channel is.
+    pub alpha: u8,
+}
+
+impl std::str::FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Color, String> {
+        let invalid = || format!("expected a color like #rrggbb, got {:?}", s);
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.is_ascii())
+            .ok_or_else(invalid)?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Color {
+            red: channel(0)?,
+            green: channel(2)?,
+            blue: channel(4)?,
+            alpha: if hex.len() == 8 { channel(6)? } else { 255 },
+        })
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)?;
+        if self.alpha != 255 {
+            write!(f, "{:02x}", self.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The colors of a bar. i3 only sends the colors set in the bar's configuration, so each of
+/// them may be missing.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct BarColors {
+    /// Background of the bar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<Color>,
+    /// Text of the statusline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statusline: Option<Color>,
+    /// The separator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub separator: Option<Color>,
+    /// Background of the bar on the focused output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focused_background: Option<Color>,
+    /// Text of the statusline on the focused output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focused_statusline: Option<Color>,
+    /// The separator on the focused output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focused_separator: Option<Color>,
+    /// Text of the focused workspace's button.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focused_workspace_text: Option<Color>,
+    /// Background of the focused workspace's button.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focused_workspace_bg: Option<Color>,
+    /// Border of the focused workspace's button.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focused_workspace_border: Option<Color>,
+    /// Text of the buttons of workspaces visible on other outputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_workspace_text: Option<Color>,
+    /// Background of the buttons of workspaces visible on other outputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_workspace_bg: Option<Color>,
+    /// Border of the buttons of workspaces visible on other outputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_workspace_border: Option<Color>,
+    /// Text of the buttons of workspaces that aren't visible.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactive_workspace_text: Option<Color>,
+    /// Background of the buttons of workspaces that aren't visible.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactive_workspace_bg: Option<Color>,
+    /// Border of the buttons of workspaces that aren't visible.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactive_workspace_border: Option<Color>,
+    /// Text of the buttons of urgent workspaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub urgent_workspace_text: Option<Color>,
+    /// Background of the buttons of urgent workspaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub urgent_workspace_bg: Option<Color>,
+    /// Border of the buttons of urgent workspaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub urgent_workspace_border: Option<Color>,
+    /// Text of the binding mode indicator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binding_mode_text: Option<Color>,
+    /// Background of the binding mode indicator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binding_mode_bg: Option<Color>,
+    /// Border of the binding mode indicator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binding_mode_border: Option<Color>,
+    /// Colors this library doesn't know, as i3 sent them.
+    #[serde(flatten)]
+    #[cfg_attr(
+        feature = "deny-unknown-fields",
+        serde(deserialize_with = "deny_other_fields")
+    )]
+    pub other: HashMap<String, String>,
+}
+
+impl BarColors {
+    /// The color of the given part of the bar, if it is set.
+    pub fn get(&self, part: &ColorableBarPart) -> Option<Color> {
+        match *part {
+            ColorableBarPart::Background => self.background,
+            ColorableBarPart::Statusline => self.statusline,
+            ColorableBarPart::Separator => self.separator,
+            ColorableBarPart::FocusedBackground => self.focused_background,
+            ColorableBarPart::FocusedStatusline => self.focused_statusline,
+            ColorableBarPart::FocusedSeparator => self.focused_separator,
+            ColorableBarPart::FocusedWorkspaceText => self.focused_workspace_text,
+            ColorableBarPart::FocusedWorkspaceBg => self.focused_workspace_bg,
+            ColorableBarPart::FocusedWorkspaceBorder => self.focused_workspace_border,
+            ColorableBarPart::ActiveWorkspaceText => self.active_workspace_text,
+            ColorableBarPart::ActiveWorkspaceBg => self.active_workspace_bg,
+            ColorableBarPart::ActiveWorkspaceBorder => self.active_workspace_border,
+            ColorableBarPart::InactiveWorkspaceText => self.inactive_workspace_text,
+            ColorableBarPart::InactiveWorkspaceBg => self.inactive_workspace_bg,
+            ColorableBarPart::InactiveWorkspaceBorder => self.inactive_workspace_border,
+            ColorableBarPart::UrgentWorkspaceText => self.urgent_workspace_text,
+            ColorableBarPart::UrgentWorkspaceBg => self.urgent_workspace_bg,
+            ColorableBarPart::UrgentWorkspaceBorder => self.urgent_workspace_border,
+            ColorableBarPart::BindingModeText => self.binding_mode_text,
+            ColorableBarPart::BindingModeBg => self.binding_mode_bg,
+            ColorableBarPart::BindingModeBorder => self.binding_mode_border,
+            ColorableBarPart::Unknown(ref key) => {
+                self.other.get(key).and_then(|color| color.parse().ok())
+            }
+        }
+    }
 }
 
 /// The reply to the `get_bar_config` request.
 ///
 /// This can be used by third-party workspace bars (especially i3bar, but others are free to
 /// implement compatible alternatives) to get the bar block configuration from i3.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 pub struct BarConfig {
     /// The ID for this bar. Included in case you request multiple configurations and want to
     /// differentiate the different replies.
@@ -371,25 +1357,89 @@ pub struct BarConfig {
     /// will be displayed in the bar. At the moment, no formatting is supported.
     pub status_command: String,
 
+    /// The command that runs the bar, if it isn't i3bar from the `PATH`.
+    #[serde(default)]
+    pub i3bar_command: Option<String>,
+
+    /// The command whose output replaces the workspace buttons, if any.
+    #[serde(default)]
+    pub workspace_command: Option<String>,
+
     /// The font to use for text on the bar.
     pub font: String,
 
+    /// The height of the bar in pixels, if it isn't worked out from the font.
+    #[serde(default)]
+    pub bar_height: Option<i32>,
+
     /// Display workspace buttons or not? Defaults to true.
     pub workspace_buttons: bool,
 
+    /// Whether workspace buttons leave out the number of workspaces named like "1:web".
+    #[serde(default)]
+    pub strip_workspace_numbers: bool,
+
+    /// Whether workspace buttons show only the number of workspaces named like "1:web".
+    #[serde(default)]
+    pub strip_workspace_name: bool,
+
     /// Display the mode indicator or not? Defaults to true.
     pub binding_mode_indicator: bool,
 
     /// Should the bar enable verbose output for debugging? Defaults to false.
     pub verbose: bool,
 
-    /// Contains key/value pairs of colors. Each value is a color code in hex, formatted
-    /// \#rrggbb (like in HTML).
-    pub colors: HashMap<ColorableBarPart, String>,
+    /// The colors of the bar.
+    pub colors: BarColors,
+
+    /// The outputs the tray is shown on; empty if it isn't limited to particular outputs.
+    /// "none" disables the tray.
+    #[serde(default)]
+    pub tray_outputs: Vec<String>,
+
+    /// The padding around the tray icons, in pixels.
+    #[serde(default)]
+    pub tray_padding: Option<i32>,
+
+    /// The text drawn between the blocks of the statusline, if it isn't a line.
+    #[serde(default)]
+    pub separator_symbol: Option<String>,
+
+    /// The minimum width of workspace buttons, in pixels.
+    #[serde(default)]
+    pub workspace_min_width: Option<i32>,
+
+    /// Whether a bar in hide mode is currently hidden ("hide") or shown ("show").
+    #[serde(default)]
+    pub hidden_state: Option<String>,
+
+    /// The modifier key that shows a bar in hide mode, such as "Mod4", or "none".
+    #[serde(default)]
+    pub modifier: Option<String>,
+
+    /// The commands to run for mouse buttons clicked on the bar.
+    #[serde(default)]
+    pub bindings: Vec<BarBinding>,
+}
+
+/// A mouse binding of a bar.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct BarBinding {
+    /// The mouse button, such as 4 for scrolling up.
+    pub input_code: i32,
+
+    /// The i3 command to run.
+    pub command: String,
+
+    /// Whether the command runs when the button is released rather than pressed.
+    #[serde(default)]
+    pub release: bool,
 }
 
 /// The reply to the `get_version` request.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 pub struct Version {
     /// The major version of i3, such as 4.
     pub major: i32,
@@ -411,22 +1461,225 @@ pub struct Version {
 
     /// The current config path.
     pub loaded_config_file_name: String,
+
+    /// The paths of the files the config includes. Sent by i3 4.20 and later.
+    #[serde(default)]
+    pub included_config_file_names: Vec<String>,
+
+    /// Which window manager this is; sway sets it to "sway", i3 leaves it out.
+    #[serde(default)]
+    pub variant: Option<String>,
 }
 
 /// The reply to the `get_binding_modes` request.
-#[cfg(feature = "i3-4-13")]
-#[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-13")))]
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct BindingModes {
     /// A vector of all currently configured binding modes.
     pub modes: Vec<String>,
 }
 
+/// The reply to the `get_binding_state` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct BindingState {
+    /// The name of the currently active binding mode, such as "default".
+    pub name: String,
+}
+
 /// The reply to the `get_config` request.
-#[cfg(feature = "i3-4-14")]
-#[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
 pub struct Config {
     /// A string containing the config file as loaded by i3 most recently.
     pub config: String,
+
+    /// The main config file followed by every file it included, in the order i3 loaded them.
+    /// `None` before i3 4.20, which doesn't report them.
+    #[serde(default)]
+    pub included_configs: Option<Vec<IncludedConfig>>,
+}
+
+/// A single config file that was loaded as part of the i3 config.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct IncludedConfig {
+    /// The absolute path of the config file.
+    pub path: String,
+
+    /// The contents of the file as it is on disk.
+    pub raw_contents: String,
+
+    /// The contents of the file after variables (`set $var ...`) have been replaced.
+    pub variable_replaced_contents: String,
+}
+
+/// The reply to the `send_tick` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct Tick {
+    /// Whether the tick was sent successfully.
+    pub success: bool,
+}
+
+/// The reply to the `sync` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct Sync {
+    /// Whether the sync request was accepted.
+    pub success: bool,
+}
+
+/// The kind of device a sway input is.
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[non_exhaustive]
+pub enum InputDeviceType {
+    Keyboard,
+    Pointer,
+    Touch,
+    TabletTool,
+    TabletPad,
+    Switch,
+    /// An InputDeviceType we don't support yet, holding the raw string sway sent.
+    Unknown(String),
+}
+
+#[cfg(feature = "sway")]
+serde_enum_str!(InputDeviceType {
+    "keyboard" => Keyboard,
+    "pointer" => Pointer,
+    "touch" => Touch,
+    "tablet_tool" => TabletTool,
+    "tablet_pad" => TabletPad,
+    "switch" => Switch,
+});
+
+/// The libinput configuration of a sway input. Settings the device doesn't support are `None`.
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+#[serde(default)]
+pub struct Libinput {
+    /// Whether events are sent: "enabled", "disabled" or "disabled_on_external_mouse".
+    pub send_events: Option<String>,
+    /// Whether tap to click is "enabled" or "disabled".
+    pub tap: Option<String>,
+    /// The finger to button mapping for taps, "lrm" or "lmr".
+    pub tap_button_map: Option<String>,
+    /// Whether tap and drag is "enabled" or "disabled".
+    pub tap_drag: Option<String>,
+    /// Whether drag lock is "enabled" or "disabled".
+    pub tap_drag_lock: Option<String>,
+    /// The pointer acceleration, between -1 and 1.
+    pub accel_speed: Option<f64>,
+    /// The acceleration profile, "none", "flat" or "adaptive".
+    pub accel_profile: Option<String>,
+    /// Whether natural scrolling is "enabled" or "disabled".
+    pub natural_scroll: Option<String>,
+    /// Whether left-handed mode is "enabled" or "disabled".
+    pub left_handed: Option<String>,
+    /// The click method, "none", "button_areas" or "clickfinger".
+    pub click_method: Option<String>,
+    /// Whether middle button emulation is "enabled" or "disabled".
+    pub middle_emulation: Option<String>,
+    /// The scroll method, "none", "two_finger", "edge" or "on_button_down".
+    pub scroll_method: Option<String>,
+    /// The button used for `on_button_down` scrolling, as a Linux input event code.
+    pub scroll_button: Option<i32>,
+    /// Whether disable while typing is "enabled" or "disabled".
+    pub dwt: Option<String>,
+    /// Whether disable while trackpointing is "enabled" or "disabled".
+    pub dwtp: Option<String>,
+    /// The 6 element calibration matrix of a touch device.
+    pub calibration_matrix: Option<Vec<f64>>,
+}
+
+/// A single input device.
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct Input {
+    /// The identifier used in sway's config to address the input, like
+    /// "1:1:AT_Translated_Set_2_keyboard".
+    pub identifier: String,
+    /// The human readable name of the device.
+    pub name: String,
+    /// The vendor code.
+    pub vendor: i32,
+    /// The product code.
+    pub product: i32,
+    /// The kind of device.
+    #[serde(rename = "type")]
+    pub input_type: InputDeviceType,
+    /// For keyboards, the name of the active keyboard layout.
+    pub xkb_active_layout_name: Option<String>,
+    /// For keyboards, the names of all configured keyboard layouts.
+    #[serde(default)]
+    pub xkb_layout_names: Vec<String>,
+    /// For keyboards, the index of the active layout in `xkb_layout_names`.
+    pub xkb_active_layout_index: Option<i32>,
+    /// For pointers, the configured scroll factor.
+    pub scroll_factor: Option<f64>,
+    /// The libinput configuration, for libinput devices only.
+    pub libinput: Option<Libinput>,
+}
+
+/// The reply to the `get_inputs` request.
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Inputs {
+    /// A list of the input devices.
+    pub inputs: Vec<Input>,
+}
+
+/// A sway seat: a set of input devices sharing a keyboard focus and a pointer.
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "deny-unknown-fields", serde(deny_unknown_fields))]
+pub struct Seat {
+    /// The name of the seat, like "seat0".
+    pub name: String,
+    /// The wayland `wl_seat` capabilities of the seat, a bitmask of 1 for a pointer, 2 for a
+    /// keyboard and 4 for touch. See `has_pointer`, `has_keyboard` and `has_touch`.
+    pub capabilities: u32,
+    /// The id of the node the seat has focused, or 0 if nothing is focused.
+    pub focus: i64,
+    /// The input devices attached to the seat.
+    #[serde(default)]
+    pub devices: Vec<Input>,
+}
+
+#[cfg(feature = "sway")]
+impl Seat {
+    /// Whether the seat has a pointer device.
+    pub fn has_pointer(&self) -> bool {
+        self.capabilities & 1 != 0
+    }
+
+    /// Whether the seat has a keyboard.
+    pub fn has_keyboard(&self) -> bool {
+        self.capabilities & 2 != 0
+    }
+
+    /// Whether the seat has a touch device.
+    pub fn has_touch(&self) -> bool {
+        self.capabilities & 4 != 0
+    }
+}
+
+/// The reply to the `get_seats` request.
+#[cfg(feature = "sway")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway")))]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Seats {
+    /// A list of the seats.
+    pub seats: Vec<Seat>,
 }
@@ -0,0 +1,173 @@
+//! How persistently to reconnect to i3 after losing the connection.
+//!
+//! `ResilientEventListener` and `ResilientConnection` reconnect when i3 restarts in place. A
+//! `RetryPolicy` decides how often they try and how long they wait between attempts:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use i3ipc::retry::RetryPolicy;
+//! use i3ipc::ResilientEventListener;
+//!
+//! let policy = RetryPolicy::new()
+//!     .max_attempts(10)
+//!     .initial_delay(Duration::from_millis(50))
+//!     .backoff(2.0)
+//!     .max_delay(Duration::from_secs(2))
+//!     .jitter(0.2)
+//!     .on_reconnect(|attempts| eprintln!("reconnected after {} attempts", attempts));
+//! let mut listener = ResilientEventListener::connect().unwrap();
+//! listener.set_retry_policy(policy);
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The number of attempts and the delays between them for reconnecting to i3.
+///
+/// The delay before attempt `n` (counting from 0) is `initial_delay * backoff^n`, capped at
+/// `max_delay`, and then moved up or down by a random part of up to `jitter` of it, so
+/// that many clients don't all reconnect at the same moment. The default is 50 attempts 100ms
+/// apart, without backoff or jitter.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    backoff: f64,
+    jitter: f64,
+    on_reconnect: Option<Arc<dyn Fn(u32) + Send + Sync>>,
+}
+
+impl RetryPolicy {
+    /// The default policy.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy::fixed(50, Duration::from_millis(100))
+    }
+
+    /// Makes `attempts` attempts, waiting `delay` before each of them.
+    pub fn fixed(attempts: u32, delay: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: attempts,
+            initial_delay: delay,
+            max_delay: delay,
+            backoff: 1.0,
+            jitter: 0.0,
+            on_reconnect: None,
+        }
+    }
+
+    /// Sets how many attempts are made before giving up.
+    pub fn max_attempts(mut self, attempts: u32) -> RetryPolicy {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Sets the delay before the first attempt. The maximum delay is raised to it if needed.
+    pub fn initial_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.initial_delay = delay;
+        self.max_delay = self.max_delay.max(delay);
+        self
+    }
+
+    /// Sets the longest delay between two attempts.
+    pub fn max_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Sets the factor each delay is multiplied by for the next attempt. Factors below 1 are
+    /// treated as 1.
+    pub fn backoff(mut self, factor: f64) -> RetryPolicy {
+        self.backoff = if factor.is_finite() {
+            factor.max(1.0)
+        } else {
+            1.0
+        };
+        self
+    }
+
+    /// Sets the largest part of a delay, between 0 and 1, by which it is randomly shortened or
+    /// lengthened.
+    pub fn jitter(mut self, fraction: f64) -> RetryPolicy {
+        self.jitter = if fraction.is_finite() {
+            fraction.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// Sets a function that is called after every successful reconnect with the number of
+    /// attempts it took, for logging or for refreshing state built from earlier replies.
+    pub fn on_reconnect<F: Fn(u32) + Send + Sync + 'static>(mut self, hook: F) -> RetryPolicy {
+        self.on_reconnect = Some(Arc::new(hook));
+        self
+    }
+
+    /// The delay before attempt `attempt`, counting from 0, before jitter is applied.
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let factor = self.backoff.powi(attempt.min(i32::MAX as u32) as i32);
+        let delay = self.initial_delay.as_secs_f64() * factor;
+        if delay.is_finite() && delay < self.max_delay.as_secs_f64() {
+            Duration::from_secs_f64(delay)
+        } else {
+            self.max_delay
+        }
+    }
+
+    /// The delay before attempt `attempt`, counting from 0, with jitter applied.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay(attempt);
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        delay.mul_f64(1.0 + self.jitter * (2.0 * random - 1.0))
+    }
+
+    /// Calls `attempt` until it succeeds, waiting before each call, and returns its result.
+    /// After the last failed attempt, returns the last error, or `None` if no attempts are
+    /// allowed.
+    pub(crate) fn retry<T, E>(
+        &self,
+        mut attempt: impl FnMut() -> Result<T, E>,
+    ) -> Result<T, Option<E>> {
+        let mut last_error = None;
+        for n in 0..self.max_attempts {
+            thread::sleep(self.delay(n));
+            match attempt() {
+                Ok(value) => {
+                    if let Some(ref hook) = self.on_reconnect {
+                        hook(n + 1);
+                    }
+                    return Ok(value);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::new()
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_delay", &self.initial_delay)
+            .field("max_delay", &self.max_delay)
+            .field("backoff", &self.backoff)
+            .field("jitter", &self.jitter)
+            .field("on_reconnect", &self.on_reconnect.is_some())
+            .finish()
+    }
+}
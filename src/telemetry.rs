@@ -0,0 +1,67 @@
+//! Diagnostics about connecting and talking to i3.
+//!
+//! With the `tracing` feature these are `tracing` events under the `i3ipc` target: messages
+//! sent and received (with their type and payload size) at debug level, and payloads that
+//! couldn't be parsed or held values this library doesn't know at warn level. Without it, only
+//! the warnings are logged through `log`.
+
+use crate::MessageError;
+
+/// A message went out to i3.
+pub(crate) fn sent(message_type: u32, payload_len: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(target: "i3ipc", message_type, payload_len, "sent message");
+    #[cfg(not(feature = "tracing"))]
+    let _ = (message_type, payload_len);
+}
+
+/// A reply or event came in from i3. `message_type` still has the event bit.
+pub(crate) fn received(message_type: u32, payload_len: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        target: "i3ipc",
+        message_type = message_type & !crate::EVENT_BIT,
+        event = message_type & crate::EVENT_BIT != 0,
+        payload_len,
+        "received message"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (message_type, payload_len);
+}
+
+/// A payload couldn't be parsed, or an event had an unknown type.
+pub(crate) fn parse_failed(error: &MessageError) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        target: "i3ipc",
+        message_type = error.message_type(),
+        error = %error,
+        "couldn't parse payload"
+    );
+    #[cfg(not(feature = "tracing"))]
+    log::warn!(target: "i3ipc", "Couldn't parse payload: {}", error);
+}
+
+/// i3 sent a string for an enum that this library doesn't know.
+pub(crate) fn unknown_value(type_name: &str, value: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(target: "i3ipc", type_name, value, "unknown value");
+    #[cfg(not(feature = "tracing"))]
+    log::warn!(target: "i3ipc", "Unknown {} {}", type_name, value);
+}
+
+/// A lossy listener skipped an event that couldn't be parsed.
+pub(crate) fn malformed_event(error: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(target: "i3ipc", error, "skipping malformed event");
+    #[cfg(not(feature = "tracing"))]
+    log::warn!(target: "i3ipc", "Skipping malformed event: {}", error);
+}
+
+/// Bytes that didn't start with the magic string were skipped.
+pub(crate) fn resynchronized(skipped: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(target: "i3ipc", skipped, "skipped bytes to resynchronize");
+    #[cfg(not(feature = "tracing"))]
+    log::warn!(target: "i3ipc", "Skipped {} bytes to resynchronize", skipped);
+}
@@ -0,0 +1,197 @@
+//! A fake i3 for integration tests of code built on this crate.
+//!
+//! `MockI3` listens on a temporary unix socket, answers requests with canned replies keyed by
+//! message type, and can push events to the clients that subscribed to any. Nothing about the
+//! requests is checked, so a test sets up exactly the replies the code under test needs:
+//!
+//! ```no_run
+//! use i3ipc::testing::MockI3;
+//! use i3ipc::Subscription;
+//!
+//! let i3 = MockI3::start().unwrap();
+//! i3.set_reply(5, r#"["scratch"]"#);
+//! let mut connection = i3.connect().unwrap();
+//! assert_eq!(connection.get_marks().unwrap().marks, vec!["scratch"]);
+//!
+//! let mut listener = i3.connect_listener().unwrap();
+//! listener.subscribe(&[Subscription::Mode]).unwrap();
+//! i3.push_event(2, r#"{"change":"resize"}"#).unwrap();
+//! ```
+//!
+//! Code that connects with `I3Connection::connect` can be pointed at the mock by setting the
+//! `I3SOCK` environment variable to `socket_path()`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::{I3Connection, I3EventListener};
+
+static MOCK_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Default)]
+struct State {
+    replies: HashMap<u32, String>,
+    requests: Vec<(u32, String)>,
+    /// Write halves of the clients that subscribed to events.
+    subscribers: Vec<Arc<Mutex<UnixStream>>>,
+}
+
+/// A fake i3 IPC server on a temporary unix socket. It stops and removes the socket when
+/// dropped.
+#[derive(Debug)]
+pub struct MockI3 {
+    dir: PathBuf,
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+    stopped: Arc<AtomicBool>,
+    accept_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MockI3 {
+    /// Binds a new socket in the temporary directory and starts serving it.
+    ///
+    /// Until replies are set with `set_reply`, subscribe requests succeed and every other
+    /// request gets `{"success":false,"error":"no canned reply"}`.
+    pub fn start() -> io::Result<MockI3> {
+        let dir = std::env::temp_dir().join(format!(
+            "i3ipc-mock-{}-{}",
+            std::process::id(),
+            MOCK_COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("ipc-socket");
+        let listener = UnixListener::bind(&path)?;
+
+        let state = Arc::new(Mutex::new(State::default()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let accept_thread = {
+            let state = state.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let state = state.clone();
+                        thread::spawn(move || serve(stream, state));
+                    }
+                }
+            })
+        };
+
+        Ok(MockI3 {
+            dir,
+            path,
+            state,
+            stopped,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// The path of the socket, for `I3SOCK`.
+    pub fn socket_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Answers requests of the given message type (0 for `run_command`, 4 for `get_tree`, ...)
+    /// with `payload` from now on.
+    pub fn set_reply(&self, message_type: u32, payload: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .replies
+            .insert(message_type, payload.to_owned());
+    }
+
+    /// Sends an event of the given type (0 for workspace events, 1 for output events, ...) to
+    /// every client that has subscribed to anything.
+    pub fn push_event(&self, event_type: u32, payload: &str) -> io::Result<()> {
+        let message = crate::encode_message(crate::EVENT_BIT | event_type, payload)?;
+        let subscribers = self.state.lock().unwrap().subscribers.clone();
+        for subscriber in subscribers {
+            subscriber.lock().unwrap().write_all(&message)?;
+        }
+        Ok(())
+    }
+
+    /// The requests received so far, as (message type, payload) in the order they arrived.
+    pub fn requests(&self) -> Vec<(u32, String)> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Connects an `I3Connection` to the mock.
+    pub fn connect(&self) -> io::Result<I3Connection> {
+        Ok(I3Connection::from_stream(UnixStream::connect(&self.path)?))
+    }
+
+    /// Connects an `I3EventListener` to the mock.
+    pub fn connect_listener(&self) -> io::Result<I3EventListener> {
+        Ok(I3EventListener::from_stream(UnixStream::connect(
+            &self.path,
+        )?))
+    }
+}
+
+impl Drop for MockI3 {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wake up the accept loop so it notices.
+        let _ = UnixStream::connect(&self.path);
+        if let Some(thread) = self.accept_thread.take() {
+            let _ = thread.join();
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Answers the requests of one client until it disconnects.
+fn serve(mut stream: UnixStream, state: Arc<Mutex<State>>) {
+    let writer = match stream.try_clone() {
+        Ok(writer) => Arc::new(Mutex::new(writer)),
+        Err(_) => return,
+    };
+    loop {
+        let mut header = [0_u8; 14];
+        if stream.read_exact(&mut header).is_err() {
+            return;
+        }
+        let (payload_len, message_type) = match crate::decode_header(&header) {
+            Ok(decoded) => decoded,
+            Err(_) => return,
+        };
+        let mut payload = vec![0_u8; payload_len as usize];
+        if stream.read_exact(&mut payload).is_err() {
+            return;
+        }
+        let payload = String::from_utf8_lossy(&payload).into_owned();
+
+        let reply = {
+            let mut state = state.lock().unwrap();
+            state.requests.push((message_type, payload));
+            let reply = state.replies.get(&message_type).cloned();
+            if message_type == 2 {
+                // like i3, send each event only once to a client that subscribes repeatedly.
+                if !state.subscribers.iter().any(|s| Arc::ptr_eq(s, &writer)) {
+                    state.subscribers.push(writer.clone());
+                }
+                reply.unwrap_or_else(|| r#"{"success":true}"#.to_owned())
+            } else {
+                reply.unwrap_or_else(|| r#"{"success":false,"error":"no canned reply"}"#.to_owned())
+            }
+        };
+        let message = match crate::encode_message(message_type, &reply) {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+        if writer.lock().unwrap().write_all(&message).is_err() {
+            return;
+        }
+    }
+}
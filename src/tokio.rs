@@ -0,0 +1,96 @@
+//! Asynchronous versions of `I3Connection` and `I3EventListener` built on tokio.
+//!
+//! These mirror the blocking types in the crate root, but every request is an `async fn` that
+//! runs on a `tokio::net::UnixStream`, so bars and daemons already running a tokio runtime
+//! don't have to spawn blocking threads for each IPC call. The framing itself lives in the
+//! `transport` module; this module only adapts tokio's stream to it.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::ReadBuf;
+use tokio::net::UnixStream;
+
+use crate::transport::{AsyncI3Connection, AsyncI3EventListener};
+use crate::EstablishError;
+
+/// A tokio `UnixStream` adapted to the `futures` io traits.
+#[derive(Debug)]
+pub struct TokioStream(UnixStream);
+
+impl TokioStream {
+    /// Wraps an already connected tokio stream.
+    pub fn new(stream: UnixStream) -> TokioStream {
+        TokioStream(stream)
+    }
+}
+
+impl futures::io::AsyncRead for TokioStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut read_buf = ReadBuf::new(buf);
+        match tokio::io::AsyncRead::poll_read(Pin::new(&mut self.0), cx, &mut read_buf) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(read_buf.filled().len())),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl futures::io::AsyncWrite for TokioStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.0), cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.0), cx)
+    }
+}
+
+async fn connect_stream() -> Result<TokioStream, EstablishError> {
+    match crate::find_socket_path(cfg!(feature = "sway")) {
+        Ok((path, source)) => match UnixStream::connect(&path).await {
+            Ok(stream) => Ok(TokioStream(stream)),
+            Err(error) => Err(crate::socket_error(path, source, error)),
+        },
+        Err(error) => Err(EstablishError::GetSocketPathError(error)),
+    }
+}
+
+/// Abstraction over an async ipc socket to i3. Handles events.
+pub type I3EventListener = AsyncI3EventListener<TokioStream>;
+
+impl AsyncI3EventListener<TokioStream> {
+    /// Establishes the IPC connection.
+    ///
+    /// Note that finding the socket path may fall back to running `i3 --get-socketpath`, which
+    /// briefly blocks the calling task.
+    pub async fn connect() -> Result<I3EventListener, EstablishError> {
+        Ok(AsyncI3EventListener::new(connect_stream().await?))
+    }
+}
+
+/// Abstraction over an async ipc socket to i3. Handles messages/replies.
+pub type I3Connection = AsyncI3Connection<TokioStream>;
+
+impl AsyncI3Connection<TokioStream> {
+    /// Establishes the IPC connection.
+    ///
+    /// Note that finding the socket path may fall back to running `i3 --get-socketpath`, which
+    /// briefly blocks the calling task.
+    pub async fn connect() -> Result<I3Connection, EstablishError> {
+        Ok(AsyncI3Connection::new(connect_stream().await?))
+    }
+}
@@ -0,0 +1,682 @@
+//! Caches of i3's state that are kept up to date by events.
+//!
+//! Tools that look at the layout tree after every event don't have to ask i3 for all of it
+//! each time. A `TreeTracker` fetches the tree once and applies the changes the events
+//! describe, asking i3 again only for changes it can't work out by itself:
+//!
+//! ```no_run
+//! use i3ipc::tracker::TreeTracker;
+//! use i3ipc::{I3Connection, I3EventListener, Subscription};
+//!
+//! let mut tracker = TreeTracker::new(I3Connection::connect().unwrap()).unwrap();
+//! let mut listener = I3EventListener::connect().unwrap();
+//! listener
+//!     .subscribe(&[Subscription::Window, Subscription::Workspace, Subscription::Output])
+//!     .unwrap();
+//! for event in listener.listen() {
+//!     tracker.handle_event(&event.unwrap()).unwrap();
+//!     let focused = tracker.snapshot().find_focused().unwrap();
+//!     println!("{:?}", focused.name);
+//! }
+//! ```
+//!
+//! A `WorkspaceTracker` does the same for the list of workspaces, for bars that redraw their
+//! workspace buttons on every event.
+//!
+//! A `PreviousWorkspace` remembers the order in which workspaces were focused, like i3's
+//! `workspace back_and_forth` but further back than one step.
+//!
+//! A `FocusHistory` does the same for windows, and picks the next or previous window to
+//! cycle to in that order.
+//!
+//! An `OutputTracker` keeps the list of outputs and reports which ones were plugged in,
+//! unplugged or reconfigured, for monitor hotplug scripts:
+//!
+//! ```no_run
+//! use i3ipc::tracker::{OutputTracker, OutputUpdate};
+//! use i3ipc::{I3Connection, I3EventListener, Subscription};
+//!
+//! let mut tracker = OutputTracker::new(I3Connection::connect().unwrap()).unwrap();
+//! let mut listener = I3EventListener::connect().unwrap();
+//! listener.subscribe(&[Subscription::Output]).unwrap();
+//! for event in listener.listen() {
+//!     for update in tracker.handle_event(&event.unwrap()).unwrap() {
+//!         if let OutputUpdate::Added(output) = update {
+//!             println!("{} was plugged in", output.name);
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::command::Command;
+use crate::criteria::Criteria;
+use crate::event::inner::{WindowChange, WorkspaceChange};
+use crate::event::Event;
+use crate::reply::{self, Node, Output, Workspace};
+use crate::tree::TreeIndex;
+use crate::{I3Connection, MessageError};
+
+fn find_mut(node: &mut Node, id: i64) -> Option<&mut Node> {
+    if node.id == id {
+        return Some(node);
+    }
+    node.nodes
+        .iter_mut()
+        .chain(node.floating_nodes.iter_mut())
+        .find_map(|child| find_mut(child, id))
+}
+
+/// Collects the IDs of the containers from `node` down to the one with the given ID.
+fn path_to(node: &Node, id: i64, path: &mut Vec<i64>) -> bool {
+    path.push(node.id);
+    if node.id == id
+        || node
+            .nodes
+            .iter()
+            .chain(node.floating_nodes.iter())
+            .any(|child| path_to(child, id, path))
+    {
+        return true;
+    }
+    path.pop();
+    false
+}
+
+fn clear_focused(node: &mut Node) {
+    node.focused = false;
+    for child in node.nodes.iter_mut().chain(node.floating_nodes.iter_mut()) {
+        clear_focused(child);
+    }
+}
+
+/// Makes the given container the focused one: it is the only node with `focused` set, and
+/// every container above it has the path to it first in its `focus` list. Returns false if
+/// there is no such container.
+fn set_focused(root: &mut Node, id: i64) -> bool {
+    let mut path = Vec::new();
+    if !path_to(root, id, &mut path) {
+        return false;
+    }
+    clear_focused(root);
+    let mut node = root;
+    for child_id in path.into_iter().skip(1) {
+        node.focus.retain(|focus_id| *focus_id != child_id);
+        node.focus.insert(0, child_id);
+        node = match find_mut(node, child_id) {
+            Some(child) => child,
+            None => return false,
+        };
+    }
+    node.focused = true;
+    true
+}
+
+/// Replaces the container with the same ID as `new` by `new`. Returns false if there is no
+/// such container.
+fn replace(root: &mut Node, new: &Node) -> bool {
+    match find_mut(root, new.id) {
+        Some(node) => {
+            *node = new.clone();
+            true
+        }
+        None => false,
+    }
+}
+
+/// The layout tree, kept up to date by window, workspace and output events.
+///
+/// Changes confined to a single container (a new title, marks, urgency, fullscreen mode,
+/// focus, a workspace being renamed or becoming urgent) are patched into the cached tree.
+/// Anything that moves containers around, such as windows opening, closing or moving and
+/// workspaces or outputs coming and going, makes the tracker fetch the whole tree again.
+#[derive(Debug)]
+pub struct TreeTracker<S = std::os::unix::net::UnixStream> {
+    connection: I3Connection<S>,
+    tree: Node,
+}
+
+impl<S: Read + Write> TreeTracker<S> {
+    /// Fetches the tree over `connection`, which is kept for fetching it again later.
+    pub fn new(mut connection: I3Connection<S>) -> Result<TreeTracker<S>, MessageError> {
+        let tree = connection.get_tree()?;
+        Ok(TreeTracker { connection, tree })
+    }
+
+    /// The tree as of the last handled event.
+    pub fn snapshot(&self) -> &Node {
+        &self.tree
+    }
+
+    /// Updates the tree with an event. Events that don't affect the tree are ignored.
+    pub fn handle_event(&mut self, event: &Event) -> Result<(), MessageError> {
+        let patched = match *event {
+            Event::WindowEvent(ref info) => match info.change {
+                WindowChange::Title | WindowChange::Urgent | WindowChange::FullscreenMode => {
+                    replace(&mut self.tree, &info.container)
+                }
+                WindowChange::Mark => replace(&mut self.tree, &info.container),
+                WindowChange::Focus => {
+                    replace(&mut self.tree, &info.container)
+                        && set_focused(&mut self.tree, info.container.id)
+                }
+                _ => false,
+            },
+            Event::WorkspaceEvent(ref info) => match (&info.change, &info.current) {
+                (WorkspaceChange::Rename, Some(current))
+                | (WorkspaceChange::Urgent, Some(current)) => replace(&mut self.tree, current),
+                (WorkspaceChange::Focus, Some(current)) => {
+                    let focused = match current.find(|node| node.focused) {
+                        Some(node) => node.id,
+                        None => current.focused_leaf().id,
+                    };
+                    replace(&mut self.tree, current) && set_focused(&mut self.tree, focused)
+                }
+                _ => false,
+            },
+            Event::OutputEvent(_) => false,
+            _ => return Ok(()),
+        };
+        if patched {
+            Ok(())
+        } else {
+            self.refresh()
+        }
+    }
+
+    /// Fetches the whole tree again, for example after events were missed.
+    pub fn refresh(&mut self) -> Result<(), MessageError> {
+        self.tree = self.connection.get_tree()?;
+        Ok(())
+    }
+
+    /// The connection used to fetch the tree, which can be used for other requests too.
+    pub fn connection(&mut self) -> &mut I3Connection<S> {
+        &mut self.connection
+    }
+
+    /// Gives back the connection.
+    pub fn into_connection(self) -> I3Connection<S> {
+        self.connection
+    }
+}
+
+/// The list of workspaces, kept up to date by workspace and output events.
+///
+/// Focus and urgency changes are patched into the cached list. Workspaces being created,
+/// emptied, renamed or moved, and outputs changing, make the tracker fetch the list again, as
+/// does the first event after the reconcile interval (a minute by default) has passed, in case
+/// an event was missed.
+#[derive(Debug)]
+pub struct WorkspaceTracker<S = std::os::unix::net::UnixStream> {
+    connection: I3Connection<S>,
+    workspaces: Vec<Workspace>,
+    fetched: Instant,
+    reconcile_interval: Duration,
+    last_move: Option<WorkspaceMove>,
+}
+
+/// A workspace moving to another output, as seen by `WorkspaceTracker::last_move`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceMove {
+    /// The name of the workspace.
+    pub workspace: String,
+    /// The output it was on, unless the tracker didn't know the workspace.
+    pub from: Option<String>,
+    /// The output it is on now, unless it has disappeared since.
+    pub to: Option<String>,
+}
+
+impl<S: Read + Write> WorkspaceTracker<S> {
+    /// Fetches the workspaces over `connection`, which is kept for fetching them again later.
+    pub fn new(mut connection: I3Connection<S>) -> Result<WorkspaceTracker<S>, MessageError> {
+        let workspaces = connection.get_workspaces()?.workspaces;
+        Ok(WorkspaceTracker {
+            connection,
+            workspaces,
+            fetched: Instant::now(),
+            reconcile_interval: Duration::from_secs(60),
+            last_move: None,
+        })
+    }
+
+    /// Sets how old the cached list may get before an event causes it to be fetched again.
+    pub fn set_reconcile_interval(&mut self, interval: Duration) {
+        self.reconcile_interval = interval;
+    }
+
+    /// The workspaces as of the last handled event, in the order i3 reported them.
+    pub fn workspaces(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    /// The workspace with the given name.
+    pub fn get(&self, name: &str) -> Option<&Workspace> {
+        self.workspaces
+            .iter()
+            .find(|workspace| workspace.name == name)
+    }
+
+    /// The focused workspace.
+    pub fn focused(&self) -> Option<&Workspace> {
+        self.workspaces.iter().find(|workspace| workspace.focused)
+    }
+
+    /// The last workspace move handled, with the outputs it moved between. i3's move events
+    /// only tell where the workspace is now, so the output it came from is taken from the
+    /// list as it was before the event.
+    pub fn last_move(&self) -> Option<&WorkspaceMove> {
+        self.last_move.as_ref()
+    }
+
+    /// Updates the list with an event. Events that don't affect workspaces are ignored.
+    pub fn handle_event(&mut self, event: &Event) -> Result<(), MessageError> {
+        let moved = match *event {
+            Event::WorkspaceEvent(ref info) if info.change == WorkspaceChange::Move => info
+                .current
+                .as_ref()
+                .and_then(|current| current.name.clone())
+                .map(|name| {
+                    let from = self.get(&name).map(|workspace| workspace.output.clone());
+                    (name, from.or_else(|| info.old_output().map(str::to_owned)))
+                }),
+            _ => None,
+        };
+        let patched = match *event {
+            _ if self.fetched.elapsed() >= self.reconcile_interval => false,
+            Event::WorkspaceEvent(ref info) => match (&info.change, &info.current) {
+                (WorkspaceChange::Focus, Some(current)) => self.focus(current),
+                (WorkspaceChange::Urgent, Some(current)) => {
+                    match self
+                        .workspaces
+                        .iter_mut()
+                        .find(|workspace| Some(&workspace.name) == current.name.as_ref())
+                    {
+                        Some(workspace) => {
+                            workspace.urgent = current.urgent;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                _ => false,
+            },
+            Event::OutputEvent(_) => false,
+            _ => return Ok(()),
+        };
+        if patched {
+            return Ok(());
+        }
+        self.refresh()?;
+        if let Some((workspace, from)) = moved {
+            let to = self
+                .get(&workspace)
+                .map(|workspace| workspace.output.clone());
+            self.last_move = Some(WorkspaceMove {
+                workspace,
+                from,
+                to,
+            });
+        }
+        Ok(())
+    }
+
+    /// Moves the focus to `current`, which also becomes the visible workspace on its output.
+    fn focus(&mut self, current: &Node) -> bool {
+        let output = match self
+            .workspaces
+            .iter()
+            .find(|workspace| Some(&workspace.name) == current.name.as_ref())
+        {
+            Some(workspace) => workspace.output.clone(),
+            None => return false,
+        };
+        for workspace in &mut self.workspaces {
+            let is_current = Some(&workspace.name) == current.name.as_ref();
+            workspace.focused = is_current;
+            if workspace.output == output {
+                workspace.visible = is_current;
+            }
+        }
+        true
+    }
+
+    /// Fetches the list again.
+    pub fn refresh(&mut self) -> Result<(), MessageError> {
+        self.workspaces = self.connection.get_workspaces()?.workspaces;
+        self.fetched = Instant::now();
+        Ok(())
+    }
+
+    /// The connection used to fetch the workspaces, which can be used for other requests too.
+    pub fn connection(&mut self) -> &mut I3Connection<S> {
+        &mut self.connection
+    }
+
+    /// Gives back the connection.
+    pub fn into_connection(self) -> I3Connection<S> {
+        self.connection
+    }
+}
+
+/// A difference between two lists of outputs, as reported by `OutputTracker`. Outputs are
+/// matched by name.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+pub enum OutputUpdate {
+    /// An output that wasn't listed before.
+    Added(Output),
+    /// An output that isn't listed anymore.
+    Removed(Output),
+    /// An output whose mode, position, activity or current workspace changed.
+    Changed { old: Output, new: Output },
+}
+
+/// The list of outputs, kept up to date by output events.
+///
+/// i3's output events don't say what changed, so every one of them makes the tracker fetch
+/// the list again and compare it with the cached one. i3 lists connected outputs that are
+/// turned off as inactive, so turning an output on or off is a change rather than an addition
+/// or removal.
+#[derive(Debug)]
+pub struct OutputTracker<S = std::os::unix::net::UnixStream> {
+    connection: I3Connection<S>,
+    outputs: Vec<Output>,
+}
+
+impl<S: Read + Write> OutputTracker<S> {
+    /// Fetches the outputs over `connection`, which is kept for fetching them again later.
+    pub fn new(mut connection: I3Connection<S>) -> Result<OutputTracker<S>, MessageError> {
+        let outputs = connection.get_outputs()?.outputs;
+        Ok(OutputTracker {
+            connection,
+            outputs,
+        })
+    }
+
+    /// The outputs as of the last handled event, in the order i3 reported them.
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs
+    }
+
+    /// The output with the given name.
+    pub fn get(&self, name: &str) -> Option<&Output> {
+        self.outputs.iter().find(|output| output.name == name)
+    }
+
+    /// Updates the list with an event and returns what changed. Events other than output
+    /// events are ignored.
+    pub fn handle_event(&mut self, event: &Event) -> Result<Vec<OutputUpdate>, MessageError> {
+        match *event {
+            Event::OutputEvent(_) => self.refresh(),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Fetches the list again and returns what changed, for example after events were missed.
+    /// Changes are listed in the order of the new list, followed by removals in the order of
+    /// the old one.
+    pub fn refresh(&mut self) -> Result<Vec<OutputUpdate>, MessageError> {
+        let new = self.connection.get_outputs()?.outputs;
+        let old = std::mem::replace(&mut self.outputs, new);
+        let mut changes = Vec::new();
+        for output in &self.outputs {
+            match old.iter().find(|old| old.name == output.name) {
+                None => changes.push(OutputUpdate::Added(output.clone())),
+                Some(old) if old != output => changes.push(OutputUpdate::Changed {
+                    old: old.clone(),
+                    new: output.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for output in old {
+            if self.get(&output.name).is_none() {
+                changes.push(OutputUpdate::Removed(output));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// The connection used to fetch the outputs, which can be used for other requests too.
+    pub fn connection(&mut self) -> &mut I3Connection<S> {
+        &mut self.connection
+    }
+
+    /// Gives back the connection.
+    pub fn into_connection(self) -> I3Connection<S> {
+        self.connection
+    }
+}
+
+/// The workspaces in the order they were last focused, kept up to date by workspace events.
+///
+/// Every workspace is listed once, at the position it was last focused, starting with the
+/// focused one. Workspaces are remembered by name, and keep their place when renamed. Like
+/// `workspace back_and_forth`, switching back to a workspace that has been emptied and
+/// destroyed since creates it again.
+#[derive(Debug)]
+pub struct PreviousWorkspace<S = std::os::unix::net::UnixStream> {
+    connection: I3Connection<S>,
+    /// The container ID and name of each workspace, most recently focused first. The ID is
+    /// only known once a focus event for the workspace came along.
+    history: Vec<(Option<i64>, String)>,
+}
+
+impl<S: Read + Write> PreviousWorkspace<S> {
+    /// Starts the history with the focused workspace, fetched over `connection`, which is kept
+    /// for switching workspaces later.
+    pub fn new(mut connection: I3Connection<S>) -> Result<PreviousWorkspace<S>, MessageError> {
+        let history = connection
+            .get_workspaces()?
+            .focused()
+            .map(|workspace| (None, workspace.name.clone()))
+            .into_iter()
+            .collect();
+        Ok(PreviousWorkspace {
+            connection,
+            history,
+        })
+    }
+
+    /// The focused workspace, as of the last handled event.
+    pub fn current(&self) -> Option<&str> {
+        self.nth_previous(0)
+    }
+
+    /// The workspace focused before the current one.
+    pub fn previous(&self) -> Option<&str> {
+        self.nth_previous(1)
+    }
+
+    /// The workspace focused `steps` switches ago, not counting switches to workspaces that
+    /// were focused again later. `nth_previous(1)` is the same as `previous()`.
+    pub fn nth_previous(&self, steps: usize) -> Option<&str> {
+        self.history.get(steps).map(|(_, name)| name.as_str())
+    }
+
+    /// The names of the workspaces, most recently focused first.
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.history.iter().map(|(_, name)| name.as_str())
+    }
+
+    /// Updates the history with an event. Events other than workspace focus changes and
+    /// renames are ignored.
+    pub fn handle_event(&mut self, event: &Event) {
+        let (change, current) = match *event {
+            Event::WorkspaceEvent(ref info) => match info.current {
+                Some(ref current) => (&info.change, current),
+                None => return,
+            },
+            _ => return,
+        };
+        let name = match current.name {
+            Some(ref name) => name.clone(),
+            None => return,
+        };
+        match *change {
+            WorkspaceChange::Focus => {
+                self.history
+                    .retain(|(id, old)| *old != name && *id != Some(current.id));
+                self.history.insert(0, (Some(current.id), name));
+            }
+            WorkspaceChange::Rename => {
+                for entry in &mut self.history {
+                    if entry.0 == Some(current.id) {
+                        entry.1 = name.clone();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Switches to the previous workspace. Returns `None` if there is none.
+    pub fn switch_back(&mut self) -> Result<Option<reply::Command>, MessageError> {
+        self.switch_back_by(1)
+    }
+
+    /// Switches to the workspace focused `steps` switches ago, see `nth_previous`. Returns
+    /// `None` if the history doesn't go back that far. The history itself changes once the
+    /// focus event for the switch is handled.
+    pub fn switch_back_by(&mut self, steps: usize) -> Result<Option<reply::Command>, MessageError> {
+        let command = match self.nth_previous(steps) {
+            Some(name) => Command::focus().workspace(name),
+            None => return Ok(None),
+        };
+        self.connection.run_command(command.as_str()).map(Some)
+    }
+
+    /// The connection used for switching workspaces, which can be used for other requests too.
+    pub fn connection(&mut self) -> &mut I3Connection<S> {
+        &mut self.connection
+    }
+
+    /// Gives back the connection.
+    pub fn into_connection(self) -> I3Connection<S> {
+        self.connection
+    }
+}
+
+/// The windows `FocusHistory::next_window` and `FocusHistory::prev_window` cycle through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WindowFilter {
+    /// All windows, except those hidden in the scratchpad.
+    All,
+    /// The windows on the focused workspace.
+    CurrentWorkspace,
+    /// The windows whose class is exactly the given one, except those hidden in the
+    /// scratchpad.
+    Class(String),
+}
+
+/// The IDs of the windows in the order they were focused, kept up to date by window events.
+///
+/// Windows that haven't been focused since the history was started are ranked after the
+/// others, in the order the tree's `focus` lists give (see `Node::windows_by_focus`).
+#[derive(Debug, Clone, Default)]
+pub struct FocusHistory {
+    /// Container IDs, most recently focused first.
+    history: Vec<i64>,
+}
+
+impl FocusHistory {
+    /// Starts an empty history.
+    pub fn new() -> FocusHistory {
+        FocusHistory::default()
+    }
+
+    /// The container IDs of the windows focused since the history was started, most recently
+    /// focused first. Closed windows are left out.
+    pub fn history(&self) -> &[i64] {
+        &self.history
+    }
+
+    /// Updates the history with an event. Events other than windows being focused or closed
+    /// are ignored.
+    pub fn handle_event(&mut self, event: &Event) {
+        if let Event::WindowEvent(ref info) = *event {
+            let id = info.container.id;
+            match info.change {
+                WindowChange::Focus => {
+                    self.history.retain(|other| *other != id);
+                    self.history.insert(0, id);
+                }
+                WindowChange::Close => self.history.retain(|other| *other != id),
+                _ => {}
+            }
+        }
+    }
+
+    /// The windows of `tree` that `filter` lets through, most recently focused first.
+    pub fn windows<'a>(&self, tree: &'a Node, filter: &WindowFilter) -> Vec<&'a Node> {
+        let index = TreeIndex::new(tree);
+        let focused_workspace = tree
+            .find_focused()
+            .and_then(|focused| index.workspace_of(focused.id))
+            .map(|workspace| workspace.id);
+        let mut windows: Vec<&Node> = tree
+            .windows_by_focus()
+            .into_iter()
+            .filter(|window| {
+                let workspace = index.workspace_of(window.id);
+                match *filter {
+                    WindowFilter::CurrentWorkspace => {
+                        workspace.map(|workspace| workspace.id) == focused_workspace
+                    }
+                    _ if workspace.is_some_and(Node::is_scratchpad) => false,
+                    WindowFilter::All => true,
+                    WindowFilter::Class(ref class) => {
+                        window.window_property(reply::WindowProperty::Class) == Some(class)
+                    }
+                }
+            })
+            .collect();
+        windows.sort_by_key(|window| {
+            self.history
+                .iter()
+                .position(|id| *id == window.id)
+                .unwrap_or(usize::MAX)
+        });
+        windows
+    }
+
+    /// The command focusing the window focused before the current one, among those `filter`
+    /// lets through. If the focused window isn't one of them, that's the most recently
+    /// focused one that is. Returns `None` if there is no other window to go to.
+    ///
+    /// As focusing a window moves it to the front of the history, calling this again after
+    /// the switch goes back to where it started, like Alt+Tab.
+    pub fn next_window(&self, tree: &Node, filter: &WindowFilter) -> Option<Command> {
+        self.cycle(tree, filter, true)
+    }
+
+    /// The command focusing the least recently focused window among those `filter` lets
+    /// through. Returns `None` if there is no other window to go to.
+    ///
+    /// As focusing a window moves it to the front of the history, calling this again after
+    /// every switch goes through all windows.
+    pub fn prev_window(&self, tree: &Node, filter: &WindowFilter) -> Option<Command> {
+        self.cycle(tree, filter, false)
+    }
+
+    fn cycle(&self, tree: &Node, filter: &WindowFilter, forward: bool) -> Option<Command> {
+        let windows = self.windows(tree, filter);
+        let focused = tree.find_focused().map(|focused| focused.id);
+        let position = windows.iter().position(|window| Some(window.id) == focused);
+        let target = match (position, forward) {
+            (Some(position), true) => windows.get((position + 1) % windows.len()),
+            (None, true) => windows.first(),
+            (Some(position), false) => windows.get((position + windows.len() - 1) % windows.len()),
+            (None, false) => windows.last(),
+        }?;
+        if Some(target.id) == focused {
+            return None;
+        }
+        Some(Criteria::new().con_id(target.id).apply("focus"))
+    }
+}
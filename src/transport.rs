@@ -0,0 +1,209 @@
+//! Runtime-agnostic async connections to i3.
+//!
+//! The protocol framing is implemented once over any `AsyncTransport`, which is just a stream
+//! implementing the `futures` `AsyncRead` and `AsyncWrite` traits. The `tokio` and `async-std`
+//! modules are thin adapters that pick a concrete stream and know how to connect it; other
+//! runtimes (such as smol's `Async<UnixStream>`) can use `AsyncI3Connection::new` and
+//! `AsyncI3EventListener::new` directly.
+
+use std::io;
+
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{common, event, reply};
+use crate::{MessageError, Subscription};
+
+/// A byte stream that i3 messages can be sent and received over.
+///
+/// This is implemented for every type that is `AsyncRead + AsyncWrite + Unpin + Send`.
+pub trait AsyncTransport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncTransport for T {}
+
+/// Fills `buf`, however few bytes each read returns, retrying interrupted reads.
+async fn read_full<T: AsyncTransport>(stream: &mut T, buf: &mut [u8]) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match stream.read(&mut buf[filled..]).await {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "i3 closed the connection",
+                ))
+            }
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Writes and flushes all of `buf`, however few bytes each write takes, retrying interrupted
+/// writes.
+async fn write_full<T: AsyncTransport>(stream: &mut T, buf: &[u8]) -> io::Result<()> {
+    let mut written = 0;
+    while written < buf.len() {
+        match stream.write(&buf[written..]).await {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "i3 stopped accepting data",
+                ))
+            }
+            Ok(n) => written += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    loop {
+        match stream.flush().await {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            result => return result,
+        }
+    }
+}
+
+async fn send_i3_message<T: AsyncTransport>(
+    stream: &mut T,
+    message_type: u32,
+    payload: &str,
+) -> io::Result<()> {
+    let bytes = crate::encode_message(message_type, payload)?;
+    crate::telemetry::sent(message_type, payload.len());
+    write_full(stream, &bytes).await
+}
+
+/// returns a tuple of (message type, payload)
+async fn receive_i3_message<T: AsyncTransport>(stream: &mut T) -> io::Result<(u32, String)> {
+    let mut header = [0_u8; 14];
+    read_full(stream, &mut header).await?;
+    let (payload_len, message_type) = crate::decode_header(&header)?;
+    let mut payload_data = vec![0_u8; payload_len as usize];
+    read_full(stream, &mut payload_data).await?;
+    let payload_string = String::from_utf8_lossy(&payload_data).into_owned();
+    crate::telemetry::received(message_type, payload_data.len());
+    Ok((message_type, payload_string))
+}
+
+async fn send_receive_i3_message<T: AsyncTransport, D: serde::de::DeserializeOwned>(
+    stream: &mut T,
+    message_type: u32,
+    payload: &str,
+) -> Result<D, MessageError> {
+    if let Err(e) = send_i3_message(stream, message_type, payload).await {
+        return Err(MessageError::Send {
+            message_type: Some(message_type),
+            error: e,
+        });
+    }
+    let received = match receive_i3_message(stream).await {
+        Ok((received_type, _)) if received_type != message_type => {
+            return Err(MessageError::UnexpectedReplyType {
+                expected: message_type,
+                received: received_type,
+            })
+        }
+        Ok((_, payload)) => payload,
+        Err(e) => {
+            return Err(MessageError::Receive {
+                message_type: Some(message_type),
+                error: e,
+            });
+        }
+    };
+    common::parse(message_type, &received, common::MAX_TREE_DEPTH)
+        .inspect_err(crate::telemetry::parse_failed)
+}
+
+/// Abstraction over an async ipc socket to i3. Handles events.
+#[derive(Debug)]
+pub struct AsyncI3EventListener<T> {
+    stream: T,
+    lossy: bool,
+    max_tree_depth: usize,
+}
+
+impl<T: AsyncTransport> AsyncI3EventListener<T> {
+    /// Wraps an already connected transport.
+    pub fn new(stream: T) -> AsyncI3EventListener<T> {
+        AsyncI3EventListener {
+            stream,
+            lossy: false,
+            max_tree_depth: common::MAX_TREE_DEPTH,
+        }
+    }
+
+    /// Sets whether an event that can't be parsed is delivered as `Event::Malformed` and
+    /// logged, rather than returned as an error. Off by default. See
+    /// `I3EventListener::set_lossy`.
+    pub fn set_lossy(&mut self, lossy: bool) {
+        self.lossy = lossy;
+    }
+
+    /// Sets how deeply containers may be nested in an event before it is returned as a
+    /// `TreeTooDeep` error instead of being parsed. See `I3EventListener::set_max_tree_depth`.
+    pub fn set_max_tree_depth(&mut self, depth: usize) {
+        self.max_tree_depth = depth;
+    }
+
+    /// Subscribes your connection to certain events.
+    pub async fn subscribe(
+        &mut self,
+        events: &[Subscription],
+    ) -> Result<reply::Subscribe, MessageError> {
+        let json = common::build_subscribe_payload(events);
+        send_receive_i3_message(&mut self.stream, 2, &json).await
+    }
+
+    /// Waits for the next subscribed event.
+    pub async fn next_event(&mut self) -> Result<event::Event, MessageError> {
+        match receive_i3_message(&mut self.stream).await {
+            Ok((msgint, payload)) => {
+                // strip the highest order bit indicating it's an event.
+                let msgtype = (msgint << 1) >> 1;
+
+                common::build_event_lossy(msgtype, &payload, self.max_tree_depth, self.lossy)
+            }
+            Err(e) => Err(crate::receive_failed(e)),
+        }
+    }
+
+    /// Turns the listener into a `futures::Stream` of subscribed events, so it composes with
+    /// the `StreamExt` combinators.
+    pub fn into_stream(self) -> impl futures::Stream<Item = Result<event::Event, MessageError>> {
+        futures::stream::unfold(self, |mut listener| async move {
+            let event = listener.next_event().await;
+            Some((event, listener))
+        })
+    }
+}
+
+/// Abstraction over an async ipc socket to i3. Handles messages/replies.
+#[derive(Debug)]
+pub struct AsyncI3Connection<T> {
+    stream: T,
+}
+
+impl<T: AsyncTransport> AsyncI3Connection<T> {
+    /// Wraps an already connected transport.
+    pub fn new(stream: T) -> AsyncI3Connection<T> {
+        AsyncI3Connection { stream }
+    }
+
+    /// The payload of the message is a command for i3 (like the commands you can bind to keys
+    /// in the configuration file) and will be executed directly after receiving it.
+    pub async fn run_command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
+        send_receive_i3_message(&mut self.stream, 0, string).await
+    }
+
+    /// Gets the layout tree. i3 uses a tree as data structure which includes every container.
+    pub async fn get_tree(&mut self) -> Result<reply::Node, MessageError> {
+        send_receive_i3_message(&mut self.stream, 4, "").await
+    }
+
+    /// Gets the list of currently configured binding modes.
+    pub async fn get_binding_modes(&mut self) -> Result<reply::BindingModes, MessageError> {
+        send_receive_i3_message(&mut self.stream, 8, "").await
+    }
+}
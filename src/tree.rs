@@ -0,0 +1,314 @@
+//! Navigating the layout tree returned by `I3Connection::get_tree`.
+//!
+//! `reply::Node` only points down to its children. `TreeIndex` remembers every node's parent so
+//! questions like "which workspace is this window on" don't need a manual search:
+//!
+//! ```no_run
+//! use i3ipc::tree::TreeIndex;
+//! use i3ipc::I3Connection;
+//!
+//! let mut connection = I3Connection::connect().unwrap();
+//! let tree = connection.get_tree().unwrap();
+//! let index = TreeIndex::new(&tree);
+//! if let Some(focused) = tree.find_focused() {
+//!     let workspace = index.workspace_of(focused.id).unwrap();
+//!     println!("focus is on workspace {:?}", workspace.name);
+//! }
+//! ```
+//!
+//! `Node::flatten` walks a tree as a flat list instead, and `Node::write_windows_jsonl` writes
+//! one line of JSON per window, for pickers like fzf or rofi:
+//!
+//! ```no_run
+//! use i3ipc::I3Connection;
+//!
+//! let mut connection = I3Connection::connect().unwrap();
+//! let tree = connection.get_tree().unwrap();
+//! for (depth, _, node) in tree.flatten() {
+//!     println!("{:indent$}{:?}", "", node.name, indent = depth * 2);
+//! }
+//! tree.write_windows_jsonl(std::io::stdout()).unwrap();
+//! ```
+//!
+//! For bars, `Node::window_counts_per_workspace` counts the windows on every workspace in one
+//! go, and `Node::windows_on_workspace` and `Node::workspace_of_window` answer the same
+//! question for a single workspace or window.
+
+use std::collections::HashMap;
+use std::io;
+
+use serde::Serialize;
+use serde_json as json;
+
+use crate::reply::{Node, NodeType};
+
+/// An index over a borrowed layout tree, mapping container IDs to their node and parent.
+#[derive(Debug, Clone)]
+pub struct TreeIndex<'a> {
+    root: &'a Node,
+    nodes: HashMap<i64, &'a Node>,
+    parents: HashMap<i64, i64>,
+}
+
+impl<'a> TreeIndex<'a> {
+    /// Indexes every node below (and including) `root`, both tiling and floating.
+    pub fn new(root: &'a Node) -> TreeIndex<'a> {
+        let mut index = TreeIndex {
+            root,
+            nodes: HashMap::new(),
+            parents: HashMap::new(),
+        };
+        let mut todo = vec![root];
+        while let Some(node) = todo.pop() {
+            index.nodes.insert(node.id, node);
+            for child in node.nodes.iter().chain(node.floating_nodes.iter()) {
+                index.parents.insert(child.id, node.id);
+                todo.push(child);
+            }
+        }
+        index
+    }
+
+    /// The node the index was built from.
+    pub fn root(&self) -> &'a Node {
+        self.root
+    }
+
+    /// Looks up the node with the given container ID.
+    pub fn get(&self, id: i64) -> Option<&'a Node> {
+        self.nodes.get(&id).cloned()
+    }
+
+    /// The container directly holding the given one, or `None` for the root or an unknown ID.
+    pub fn parent_of(&self, id: i64) -> Option<&'a Node> {
+        self.parents.get(&id).and_then(|parent| self.get(*parent))
+    }
+
+    /// The containers holding the given one, starting with its parent and ending with the root.
+    pub fn ancestors(&self, id: i64) -> Ancestors<'_, 'a> {
+        Ancestors { index: self, id }
+    }
+
+    /// The workspace the given container is on. For a workspace, that's the workspace itself.
+    pub fn workspace_of(&self, id: i64) -> Option<&'a Node> {
+        self.first_of_type(id, NodeType::Workspace)
+    }
+
+    /// The output the given container is on. For an output, that's the output itself.
+    pub fn output_of(&self, id: i64) -> Option<&'a Node> {
+        self.first_of_type(id, NodeType::Output)
+    }
+
+    /// The floating container holding the given one, or `None` if it isn't floating. For a
+    /// floating container, that's the container itself.
+    pub fn floating_con_of(&self, id: i64) -> Option<&'a Node> {
+        self.first_of_type(id, NodeType::FloatingCon)
+    }
+
+    fn first_of_type(&self, id: i64, nodetype: NodeType) -> Option<&'a Node> {
+        self.get(id)
+            .into_iter()
+            .chain(self.ancestors(id))
+            .find(|node| node.nodetype == nodetype)
+    }
+}
+
+/// Iterates over the ancestors of a container, created by `TreeIndex::ancestors`.
+#[derive(Debug)]
+pub struct Ancestors<'i, 'a> {
+    index: &'i TreeIndex<'a>,
+    id: i64,
+}
+
+impl<'i, 'a> Iterator for Ancestors<'i, 'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        let parent = self.index.parent_of(self.id)?;
+        self.id = parent.id;
+        Some(parent)
+    }
+}
+
+impl Node {
+    /// Iterates over this container and everything below it, parents before their children
+    /// and tiling children before floating ones. Each item holds the depth below this
+    /// container, the IDs of the containers from this one down to the item's, and the item's
+    /// container itself.
+    pub fn flatten(&self) -> Flatten<'_> {
+        Flatten {
+            todo: vec![(vec![self.id], self)],
+        }
+    }
+
+    /// Writes one JSON object per window below this container, each on its own line. The
+    /// objects have the fields of `FlatWindow`.
+    pub fn write_windows_jsonl<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut outputs: Vec<(usize, &str)> = Vec::new();
+        let mut workspaces: Vec<(usize, &str)> = Vec::new();
+        for (depth, path, node) in self.flatten() {
+            outputs.retain(|&(d, _)| d < depth);
+            workspaces.retain(|&(d, _)| d < depth);
+            let name = node.name.as_deref().unwrap_or("");
+            match node.nodetype {
+                NodeType::Output => outputs.push((depth, name)),
+                NodeType::Workspace => workspaces.push((depth, name)),
+                _ => {}
+            }
+            if !is_window(node) {
+                continue;
+            }
+            let properties = node.window_properties.as_ref();
+            let window = FlatWindow {
+                id: node.id,
+                window: node.window,
+                name: node.name.as_deref(),
+                class: properties.and_then(|p| p.class.as_deref()),
+                instance: properties.and_then(|p| p.instance.as_deref()),
+                workspace: workspaces.last().map(|&(_, name)| name),
+                output: outputs.last().map(|&(_, name)| name),
+                focused: node.focused,
+                urgent: node.urgent,
+                depth,
+                path: &path,
+            };
+            json::to_writer(&mut writer, &window)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// The windows on the workspace named `name`, tiling and floating, in tree order. Empty
+    /// if there's no such workspace.
+    pub fn windows_on_workspace(&self, name: &str) -> Vec<&Node> {
+        self.workspaces()
+            .find(|workspace| workspace.name.as_deref() == Some(name))
+            .map(|workspace| workspace.windows().collect())
+            .unwrap_or_default()
+    }
+
+    /// The workspace holding the given X11 window.
+    pub fn workspace_of_window(&self, window: u32) -> Option<&Node> {
+        self.workspaces()
+            .find(|workspace| workspace.find_by_window(window).is_some())
+    }
+
+    /// The number of windows on each workspace, in tree order and including empty workspaces.
+    /// The scratchpad is left out, as it is from `I3Connection::get_workspaces`.
+    pub fn window_counts_per_workspace(&self) -> Vec<(&str, usize)> {
+        self.workspaces()
+            .filter(|workspace| !workspace.is_scratchpad())
+            .map(|workspace| {
+                let name = workspace.name.as_deref().unwrap_or("");
+                (name, workspace.windows().count())
+            })
+            .collect()
+    }
+
+    /// The urgent windows together with the workspace each is on, in tree order.
+    pub fn urgent_windows(&self) -> Vec<(&Node, &Node)> {
+        self.workspaces()
+            .flat_map(|workspace| {
+                workspace
+                    .windows()
+                    .filter(|window| window.urgent)
+                    .map(move |window| (workspace, window))
+            })
+            .collect()
+    }
+
+    /// The windows below this container, most recently focused first as far as the `focus`
+    /// lists i3 keeps for every container tell: the children of each container are visited in
+    /// the order of its `focus` list. This is exact within a container, but a window in
+    /// another container may have been focused between two windows listed next to each other.
+    pub fn windows_by_focus(&self) -> Vec<&Node> {
+        let mut windows = Vec::new();
+        let mut todo = vec![self];
+        while let Some(node) = todo.pop() {
+            if is_window(node) {
+                windows.push(node);
+                continue;
+            }
+            let mut children: Vec<&Node> = node
+                .nodes
+                .iter()
+                .chain(node.floating_nodes.iter())
+                .collect();
+            children.sort_by_key(|child| {
+                node.focus
+                    .iter()
+                    .position(|id| *id == child.id)
+                    .unwrap_or(usize::MAX)
+            });
+            todo.extend(children.into_iter().rev());
+        }
+        windows
+    }
+
+    fn workspaces(&self) -> impl Iterator<Item = &Node> {
+        self.flatten()
+            .map(|(_, _, node)| node)
+            .filter(|node| node.nodetype == NodeType::Workspace)
+    }
+
+    fn windows(&self) -> impl Iterator<Item = &Node> {
+        self.flatten()
+            .map(|(_, _, node)| node)
+            .filter(|node| is_window(node))
+    }
+}
+
+/// Whether `node` holds a window rather than other containers.
+pub(crate) fn is_window(node: &Node) -> bool {
+    matches!(node.nodetype, NodeType::Con | NodeType::FloatingCon)
+        && node.nodes.is_empty()
+        && node.floating_nodes.is_empty()
+}
+
+/// A window as written by `Node::write_windows_jsonl`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlatWindow<'a> {
+    /// The container ID.
+    pub id: i64,
+    /// The X11 window ID, if any.
+    pub window: Option<i32>,
+    /// The title.
+    pub name: Option<&'a str>,
+    /// The window class.
+    pub class: Option<&'a str>,
+    /// The window instance.
+    pub instance: Option<&'a str>,
+    /// The name of the workspace the window is on.
+    pub workspace: Option<&'a str>,
+    /// The name of the output the window is on.
+    pub output: Option<&'a str>,
+    /// Whether the window is focused.
+    pub focused: bool,
+    /// Whether the window is urgent.
+    pub urgent: bool,
+    /// How far below the flattened container the window is.
+    pub depth: usize,
+    /// The IDs of the containers from the flattened one down to the window's.
+    pub path: &'a [i64],
+}
+
+/// Iterates over a subtree as a flat list, created by `Node::flatten`.
+#[derive(Debug, Clone)]
+pub struct Flatten<'a> {
+    todo: Vec<(Vec<i64>, &'a Node)>,
+}
+
+impl<'a> Iterator for Flatten<'a> {
+    type Item = (usize, Vec<i64>, &'a Node);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, node) = self.todo.pop()?;
+        let children = node.nodes.iter().chain(node.floating_nodes.iter());
+        for child in children.rev() {
+            let mut child_path = path.clone();
+            child_path.push(child.id);
+            self.todo.push((child_path, child));
+        }
+        Some((path.len() - 1, path, node))
+    }
+}